@@ -0,0 +1,484 @@
+//! Module for the enemy actors and their AI
+
+use rsfml::system::{Vector2f, Vector2i};
+
+use crate::{
+    animation::*,
+    collision,
+    map::{ActorSpawn, Map},
+    random::Random,
+    save::{SaveError, StateReader, StateWriter},
+    sprite::Sprite,
+    texture_loader::TextureLoader,
+    vswap::FIRST_SPRITE,
+};
+
+/// Health of a guard.
+pub const GUARD_HEALTH: i32 = 25;
+
+/// Walking speed of a patrolling guard, in tiles per second.
+const PATROL_SPEED: f32 = 0.55;
+
+/// Running speed of a guard chasing the player, in tiles per second.
+const CHASE_SPEED: f32 = 1.6;
+
+/// Half the width of an actor, kept away from walls.
+pub const ACTOR_RADIUS: f32 = 0.3;
+
+/// Closest an actor walks up to the player, in tiles.
+const MIN_PLAYER_DISTANCE: f32 = 1.;
+
+/// Seconds between two frames of the walking and shooting animations.
+const FRAME_TIME: f32 = 0.15;
+
+/// Seconds an actor stays in pain after being hit.
+const PAIN_TIME: f32 = 0.3;
+
+/// Seconds an actor waits after shooting before it can shoot again.
+const ATTACK_COOLDOWN: f32 = 1.;
+
+/// Guard sprites, numbered like the sprite pages of the original VSWAP files (front views).
+const GUARD_STAND_SPRITE: i32 = FIRST_SPRITE + 50;
+const GUARD_WALK_SPRITES: [i32; 4] = [
+    FIRST_SPRITE + 58,
+    FIRST_SPRITE + 66,
+    FIRST_SPRITE + 74,
+    FIRST_SPRITE + 82,
+];
+const GUARD_PAIN_SPRITE: i32 = FIRST_SPRITE + 90;
+const GUARD_DIE_SPRITES: [i32; 3] = [FIRST_SPRITE + 91, FIRST_SPRITE + 92, FIRST_SPRITE + 93];
+const GUARD_DEAD_SPRITE: i32 = FIRST_SPRITE + 95;
+const GUARD_SHOOT_SPRITES: [i32; 3] = [FIRST_SPRITE + 96, FIRST_SPRITE + 97, FIRST_SPRITE + 98];
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ActorState {
+    Stand,
+    Patrol,
+    Chase,
+    Attack,
+    Pain,
+    Death,
+    Dead,
+}
+
+/// An enemy guard.
+pub struct Actor {
+    position: Vector2f,
+    // Position before the last update, to draw the actor in between updates
+    previous_position: Vector2f,
+    direction: Vector2f,
+    state: ActorState,
+    health: i32,
+    // Seconds spent in the current state
+    state_time: f32,
+    attack_cooldown: f32,
+    has_fired: bool,
+    // Animation of each state, in the order of ActorState
+    animations: Vec<Animation>,
+}
+
+/// Names of the textures used by guards.
+pub fn guard_texture_names() -> Vec<String> {
+    let mut sprites = vec![GUARD_STAND_SPRITE, GUARD_PAIN_SPRITE, GUARD_DEAD_SPRITE];
+    sprites.extend(GUARD_WALK_SPRITES.iter());
+    sprites.extend(GUARD_DIE_SPRITES.iter());
+    sprites.extend(GUARD_SHOOT_SPRITES.iter());
+    sprites
+        .iter()
+        .map(|sprite| format!("sprite_{}", sprite))
+        .collect()
+}
+
+/// Returns a headless texture loader holding a plain texture for each guard sprite.
+#[cfg(test)]
+pub(crate) fn guard_textures() -> TextureLoader {
+    let image = rsfml::graphics::Image::create_from_pixels(4, 4, &[128; 4 * 4 * 4]).unwrap();
+    let mut texture_loader = TextureLoader::headless();
+    for name in guard_texture_names().iter() {
+        texture_loader.load_named_image(name, &image).unwrap();
+    }
+    texture_loader
+}
+
+impl Actor {
+    /// Creates a guard from its map spawn.
+    ///
+    /// # Arguments
+    /// `spawn` - Where the guard starts and whether it patrols
+    ///
+    /// `texture_loader` - A [TextureLoader] holding the textures of [guard_texture_names]
+    pub fn new(spawn: &ActorSpawn, texture_loader: &TextureLoader) -> Actor {
+        let frames = |sprites: &[i32]| -> Vec<i32> {
+            sprites
+                .iter()
+                .map(|sprite| texture_loader.require_id(&format!("sprite_{}", sprite)))
+                .collect()
+        };
+        // With an offset of 1, playing a stopped animation starts from its first frame
+        let looping = |sprites: &[i32]| {
+            Animation::new(
+                frames(sprites),
+                AnimationState::Play,
+                AnimationPlayMode::Infinite,
+                FRAME_TIME,
+                1,
+            )
+        };
+        let animations = vec![
+            looping(&[GUARD_STAND_SPRITE]),
+            looping(&GUARD_WALK_SPRITES),
+            looping(&GUARD_WALK_SPRITES),
+            looping(&GUARD_SHOOT_SPRITES),
+            looping(&[GUARD_PAIN_SPRITE]),
+            looping(&GUARD_DIE_SPRITES),
+            looping(&[GUARD_DEAD_SPRITE]),
+        ];
+        let position = Vector2f::new(spawn.position.x as f32 + 0.5, spawn.position.y as f32 + 0.5);
+        Actor {
+            position,
+            previous_position: position,
+            direction: spawn.direction,
+            state: if spawn.patrol {
+                ActorState::Patrol
+            } else {
+                ActorState::Stand
+            },
+            health: GUARD_HEALTH,
+            state_time: 0.,
+            attack_cooldown: 0.,
+            has_fired: false,
+            animations,
+        }
+    }
+
+    pub fn get_position(&self) -> Vector2f {
+        self.position
+    }
+
+    pub fn get_state(&self) -> ActorState {
+        self.state
+    }
+
+    pub fn get_health(&self) -> i32 {
+        self.health
+    }
+
+    /// Whether the actor is still alive, including while it is hurt.
+    pub fn is_alive(&self) -> bool {
+        !matches!(self.state, ActorState::Death | ActorState::Dead)
+    }
+
+    pub fn get_tile(&self) -> Vector2i {
+        Vector2i::new(self.position.x as i32, self.position.y as i32)
+    }
+
+    /// Returns the sprite to draw for the current animation frame.
+    ///
+    /// # Arguments
+    /// `alpha` - How far the frame is between the previous update (0) and the last one (1)
+    pub fn get_sprite(&self, alpha: f32) -> Sprite {
+        Sprite::new(
+            self.previous_position + (self.position - self.previous_position) * alpha,
+            self.animations[self.state as usize].get_current_texture_id(),
+        )
+    }
+
+    /// Hurts the actor, which dies when its health runs out and chases the player otherwise.
+    pub fn take_damage(&mut self, damage: i32) {
+        if !self.is_alive() {
+            return;
+        }
+        self.health -= damage;
+        if self.health <= 0 {
+            self.health = 0;
+            self.set_state(ActorState::Death);
+        } else {
+            self.set_state(ActorState::Pain);
+        }
+    }
+
+    /// Runs the AI for one frame. Returns the damage dealt to the player.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `map` - The level, doors in the way get opened
+    ///
+    /// `player_position` - Where the player stands
+    ///
+    /// `random` - The generator used for shooting decisions and damage
+    pub fn update(
+        &mut self,
+        delta: f32,
+        map: &mut Map,
+        player_position: &Vector2f,
+        random: &mut Random,
+    ) -> i32 {
+        self.previous_position = self.position;
+        self.state_time += delta;
+        self.attack_cooldown -= delta;
+        self.animations[self.state as usize].update(delta);
+
+        let mut damage = 0;
+        match self.state {
+            ActorState::Stand => {
+                if self.can_see_player(map, player_position) {
+                    self.set_state(ActorState::Chase);
+                }
+            }
+            ActorState::Patrol => {
+                if self.can_see_player(map, player_position) {
+                    self.set_state(ActorState::Chase);
+                } else {
+                    self.patrol(delta, map);
+                }
+            }
+            ActorState::Chase => self.chase(delta, map, player_position, random),
+            ActorState::Attack => {
+                // Fire on the second frame of the shooting animation
+                if !self.has_fired && self.state_time >= FRAME_TIME {
+                    self.has_fired = true;
+                    damage = self.shoot(map, player_position, random);
+                }
+                if self.state_time >= FRAME_TIME * GUARD_SHOOT_SPRITES.len() as f32 {
+                    self.attack_cooldown = ATTACK_COOLDOWN;
+                    self.set_state(ActorState::Chase);
+                }
+            }
+            ActorState::Pain => {
+                if self.state_time >= PAIN_TIME {
+                    self.set_state(ActorState::Chase);
+                }
+            }
+            ActorState::Death => {
+                if self.state_time >= FRAME_TIME * GUARD_DIE_SPRITES.len() as f32 {
+                    self.set_state(ActorState::Dead);
+                }
+            }
+            ActorState::Dead => {}
+        }
+        damage
+    }
+
+    /// Writes where the actor is, what it does and its animations to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vector2f(&self.position);
+        writer.write_vector2f(&self.previous_position);
+        writer.write_vector2f(&self.direction);
+        writer.write_u8(self.state as u8);
+        writer.write_i32(self.health);
+        writer.write_f32(self.state_time);
+        writer.write_f32(self.attack_cooldown);
+        writer.write_bool(self.has_fired);
+        for animation in self.animations.iter() {
+            animation.save_state(writer);
+        }
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.position = reader.read_vector2f()?;
+        self.previous_position = reader.read_vector2f()?;
+        self.direction = reader.read_vector2f()?;
+        self.state = match reader.read_u8()? {
+            0 => ActorState::Stand,
+            1 => ActorState::Patrol,
+            2 => ActorState::Chase,
+            3 => ActorState::Attack,
+            4 => ActorState::Pain,
+            5 => ActorState::Death,
+            6 => ActorState::Dead,
+            state => return Err(SaveError::Corrupt(format!("actor state {}", state))),
+        };
+        self.health = reader.read_i32()?;
+        self.state_time = reader.read_f32()?;
+        self.attack_cooldown = reader.read_f32()?;
+        self.has_fired = reader.read_bool()?;
+        for animation in self.animations.iter_mut() {
+            animation.load_state(reader)?;
+        }
+        Ok(())
+    }
+
+    fn set_state(&mut self, state: ActorState) {
+        self.state = state;
+        self.state_time = 0.;
+        self.has_fired = false;
+        // Restart the animation from its first frame
+        self.animations[state as usize].set_state(AnimationState::Stop);
+        self.animations[state as usize].set_state(AnimationState::Play);
+    }
+
+    /// Whether the player is in front of the actor and nothing blocks the view.
+    fn can_see_player(&self, map: &Map, player_position: &Vector2f) -> bool {
+        let to_player = Vector2f::new(
+            player_position.x - self.position.x,
+            player_position.y - self.position.y,
+        );
+        let in_front = to_player.x * self.direction.x + to_player.y * self.direction.y > 0.;
+        in_front && map.has_line_of_sight(&self.position, player_position)
+    }
+
+    /// Walks straight ahead, turning right when something blocks the way.
+    fn patrol(&mut self, delta: f32, map: &mut Map) {
+        for _ in 0..4 {
+            if self.walk(&self.direction.clone(), PATROL_SPEED * delta, map) {
+                return;
+            }
+            self.direction = Vector2f::new(-self.direction.y, self.direction.x);
+        }
+    }
+
+    fn chase(
+        &mut self,
+        delta: f32,
+        map: &mut Map,
+        player_position: &Vector2f,
+        random: &mut Random,
+    ) {
+        let to_player = Vector2f::new(
+            player_position.x - self.position.x,
+            player_position.y - self.position.y,
+        );
+        let distance = (to_player.x * to_player.x + to_player.y * to_player.y).sqrt();
+        if distance > 0. {
+            self.direction = Vector2f::new(to_player.x / distance, to_player.y / distance);
+        }
+
+        let sees_player = map.has_line_of_sight(&self.position, player_position);
+        // Closer players get shot at more often, like the original chance of 16 / distance
+        // per game tic
+        if sees_player
+            && self.attack_cooldown <= 0.
+            && random.next_f32() < delta * 4. / distance.max(1.)
+        {
+            self.set_state(ActorState::Attack);
+            return;
+        }
+        if distance > MIN_PLAYER_DISTANCE {
+            self.walk(&self.direction.clone(), CHASE_SPEED * delta, map);
+        }
+    }
+
+    /// Moves along a direction, sliding along walls. Opens closed doors in the way.
+    /// Returns false when the actor could not move at all.
+    fn walk(&mut self, direction: &Vector2f, distance: f32, map: &mut Map) -> bool {
+        let ahead = Vector2i::new(
+            (self.position.x + direction.x * (ACTOR_RADIUS + 0.5)).floor() as i32,
+            (self.position.y + direction.y * (ACTOR_RADIUS + 0.5)).floor() as i32,
+        );
+        map.open_door(&ahead);
+
+        let start = self.position;
+        self.position = collision::slide(map, &start, ACTOR_RADIUS, &(*direction * distance));
+        self.position != start
+    }
+
+    /// Fires a hitscan shot at the player, missing more often from afar. Returns the damage.
+    fn shoot(&self, map: &Map, player_position: &Vector2f, random: &mut Random) -> i32 {
+        if !map.has_line_of_sight(&self.position, player_position) {
+            return 0;
+        }
+        let distance = ((player_position.x - self.position.x).powi(2)
+            + (player_position.y - self.position.y).powi(2))
+        .sqrt();
+        // Same odds and damage as the original guards
+        let hit_chance = 256 - (distance * 16.) as i32;
+        if random.next_byte() >= hit_chance {
+            return 0;
+        }
+        let roll = random.next_byte();
+        if distance < 2. {
+            roll / 4
+        } else if distance < 4. {
+            roll / 8
+        } else {
+            roll / 16
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+# = 1
+g = 2 guard
+map:
+#########
+#..g...>#
+#########
+";
+
+    /// Seconds of one update at the game's tick rate.
+    const TICK: f32 = 1. / 70.;
+
+    fn guard() -> (Map, Actor) {
+        let map = Map::parse_ascii(LEVEL).unwrap();
+        let actor = Actor::new(&map.get_actor_spawns()[0], &guard_textures());
+        (map, actor)
+    }
+
+    /// Updates the actor for a number of seconds, returning the damage it dealt.
+    fn run(actor: &mut Actor, map: &mut Map, player: &Vector2f, seconds: f32) -> i32 {
+        let mut random = Random::new(7);
+        let mut damage = 0;
+        for _ in 0..(seconds / TICK).ceil() as i32 {
+            damage += actor.update(TICK, map, player, &mut random);
+        }
+        damage
+    }
+
+    #[test]
+    fn guards_chase_players_they_see_in_front_of_them() {
+        let (mut map, mut actor) = guard();
+        assert_eq!(actor.get_state(), ActorState::Stand);
+
+        run(&mut actor, &mut map, &Vector2f::new(1.5, 1.5), 1.);
+        assert_eq!(actor.get_state(), ActorState::Stand);
+
+        run(&mut actor, &mut map, &Vector2f::new(7.5, 1.5), TICK);
+        assert_eq!(actor.get_state(), ActorState::Chase);
+    }
+
+    #[test]
+    fn hurt_guards_chase_once_the_pain_is_over() {
+        let (mut map, mut actor) = guard();
+        let behind = Vector2f::new(1.5, 1.5);
+
+        actor.take_damage(10);
+        assert_eq!(actor.get_state(), ActorState::Pain);
+        assert_eq!(actor.get_health(), GUARD_HEALTH - 10);
+        assert!(actor.is_alive());
+
+        run(&mut actor, &mut map, &behind, PAIN_TIME - 2. * TICK);
+        assert_eq!(actor.get_state(), ActorState::Pain);
+        run(&mut actor, &mut map, &behind, 2. * TICK);
+        assert_eq!(actor.get_state(), ActorState::Chase);
+    }
+
+    #[test]
+    fn guards_out_of_health_die_then_stay_dead() {
+        let (mut map, mut actor) = guard();
+        let in_front = Vector2f::new(7.5, 1.5);
+
+        actor.take_damage(GUARD_HEALTH + 5);
+        assert_eq!(actor.get_state(), ActorState::Death);
+        assert_eq!(actor.get_health(), 0);
+        assert!(!actor.is_alive());
+
+        // The dying animation plays to its end, whatever else happens
+        actor.take_damage(10);
+        assert_eq!(actor.get_state(), ActorState::Death);
+        let dying_time = FRAME_TIME * GUARD_DIE_SPRITES.len() as f32;
+        run(&mut actor, &mut map, &in_front, dying_time - 2. * TICK);
+        assert_eq!(actor.get_state(), ActorState::Death);
+        run(&mut actor, &mut map, &in_front, 2. * TICK);
+        assert_eq!(actor.get_state(), ActorState::Dead);
+
+        let position = actor.get_position();
+        assert_eq!(run(&mut actor, &mut map, &in_front, 5.), 0);
+        assert_eq!(actor.get_state(), ActorState::Dead);
+        assert_eq!(actor.get_position(), position);
+    }
+}
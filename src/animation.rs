@@ -1,6 +1,6 @@
 //! Module for animation management
 
-use rsfml::system::Clock;
+use crate::save::{SaveError, StateReader, StateWriter};
 
 #[derive(PartialEq, Eq, Clone, Copy)]
 pub enum AnimationState {
@@ -22,9 +22,11 @@ pub struct Animation {
     texture_ids: Vec<i32>,
     state: AnimationState,
     mode: AnimationPlayMode,
+    // Seconds a frame is shown
     lag: f32,
     current_texture: u32,
-    clock: Clock,
+    // Seconds since the current frame was shown
+    elapsed: f32,
 }
 
 impl Animation {
@@ -36,7 +38,8 @@ impl Animation {
         offset: u32,
     ) -> Animation {
         Animation {
-            a: 1,
+            // Animations of a single frame loop on it
+            a: 1.min(texture_ids.len() as u32 - 1u32),
             b: texture_ids.len() as u32 - 1u32,
             offset,
             texture_ids,
@@ -44,7 +47,7 @@ impl Animation {
             mode,
             lag,
             current_texture: 0,
-            clock: Clock::start(),
+            elapsed: 0.,
         }
     }
 
@@ -53,11 +56,11 @@ impl Animation {
         match new_state {
             AnimationState::Stop => {
                 self.current_texture = 0;
-                self.clock.restart();
+                self.elapsed = 0.;
             }
             AnimationState::Play if self.offset <= self.current_texture => {
                 self.current_texture = self.a;
-                self.clock.restart();
+                self.elapsed = 0.;
             }
             _ => {}
         }
@@ -83,6 +86,11 @@ impl Animation {
         self.texture_ids[self.current_texture as usize]
     }
 
+    /// Returns the index of the frame being shown.
+    pub fn get_current_frame(&self) -> u32 {
+        self.current_texture
+    }
+
     pub fn set_loop_anim(&mut self, a: u32, b: u32) -> () {
         self.a = a;
         self.b = b;
@@ -92,14 +100,70 @@ impl Animation {
         self.offset = offset
     }
 
-    pub fn update(&mut self) -> () {
+    /// Writes where the animation is to a save. The frames are not saved, they are the same in
+    /// the game loading it.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.a);
+        writer.write_u32(self.b);
+        writer.write_u32(self.offset);
+        writer.write_u8(self.state as u8);
+        writer.write_u8(self.mode as u8);
+        writer.write_f32(self.lag);
+        writer.write_u32(self.current_texture);
+        writer.write_f32(self.elapsed);
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.a = reader.read_u32()?;
+        self.b = reader.read_u32()?;
+        self.offset = reader.read_u32()?;
+        // An offset past the last frame never restarts the animation
+        let frames = self.texture_ids.len() as u32;
+        if self.a >= frames || self.b >= frames || self.offset > frames {
+            return Err(SaveError::Corrupt(format!(
+                "animation loop {}-{} from frame {} of {}",
+                self.a, self.b, self.offset, frames
+            )));
+        }
+        self.state = match reader.read_u8()? {
+            0 => AnimationState::Play,
+            1 => AnimationState::Pause,
+            2 => AnimationState::Stop,
+            state => return Err(SaveError::Corrupt(format!("animation state {}", state))),
+        };
+        self.mode = match reader.read_u8()? {
+            0 => AnimationPlayMode::Once,
+            1 => AnimationPlayMode::Infinite,
+            mode => return Err(SaveError::Corrupt(format!("animation mode {}", mode))),
+        };
+        self.lag = reader.read_f32()?;
+        self.current_texture = reader.read_u32()?;
+        if self.current_texture as usize >= self.texture_ids.len() {
+            return Err(SaveError::Corrupt(format!(
+                "animation frame {} of {}",
+                self.current_texture,
+                self.texture_ids.len()
+            )));
+        }
+        self.elapsed = reader.read_f32()?;
+        Ok(())
+    }
+
+    /// Moves to the next frame once the current one was shown long enough.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    pub fn update(&mut self, delta: f32) -> () {
         if self.state != AnimationState::Play {
             return;
         }
 
-        if self.clock.elapsed_time().as_seconds() < self.lag {
+        self.elapsed += delta;
+        if self.elapsed < self.lag {
             return;
         }
+        // Keep the remainder so the frame rate does not change the speed of the animation
+        self.elapsed = (self.elapsed - self.lag).min(self.lag);
 
         if self.current_texture != self.texture_ids.len() as u32 - 1 {
             self.current_texture += 1;
@@ -109,7 +173,61 @@ impl Animation {
                 self.state = AnimationState::Stop
             }
         }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn animation(frames: usize) -> Animation {
+        Animation::new(
+            (0..frames as i32).collect(),
+            AnimationState::Play,
+            AnimationPlayMode::Infinite,
+            0.1,
+            1,
+        )
+    }
+
+    #[test]
+    fn saved_animations_load_back() {
+        for frames in [1, 4].iter() {
+            let mut saved = animation(*frames);
+            saved.update(0.25);
+            let mut writer = StateWriter::new();
+            saved.save_state(&mut writer);
+            let bytes = writer.into_bytes();
+
+            let mut loaded = animation(*frames);
+            loaded.load_state(&mut StateReader::new(&bytes)).unwrap();
+            assert_eq!(loaded.get_current_frame(), saved.get_current_frame());
+            loaded.set_state(AnimationState::Play);
+            assert!(loaded.get_current_texture_id() < *frames as i32);
+        }
+    }
 
-        self.clock.restart();
+    #[test]
+    fn frames_outside_the_animation_are_refused() {
+        let mut saved = animation(4);
+        saved.set_loop_anim(4, 3);
+        let mut writer = StateWriter::new();
+        saved.save_state(&mut writer);
+        let bytes = writer.into_bytes();
+        assert_eq!(
+            animation(4).load_state(&mut StateReader::new(&bytes)),
+            Err(SaveError::Corrupt(String::from(
+                "animation loop 4-3 from frame 1 of 4"
+            )))
+        );
+
+        let mut saved = animation(4);
+        saved.set_need_anim_offset(5);
+        let mut writer = StateWriter::new();
+        saved.save_state(&mut writer);
+        let bytes = writer.into_bytes();
+        assert!(animation(4)
+            .load_state(&mut StateReader::new(&bytes))
+            .is_err());
     }
 }
@@ -0,0 +1,265 @@
+//! Module for moving round bodies through the map, sliding along walls
+
+use rsfml::{
+    graphics::FloatRect,
+    system::{Vector2f, Vector2i},
+};
+
+use crate::map::Map;
+
+/// Longest part of a move checked at once, as a fraction of the radius, so fast bodies do not
+/// go through walls.
+const MAX_STEP: f32 = 0.5;
+
+/// Times a body is pushed out of the walls it overlaps after each step. Inner corners need two
+/// pushes, one for each wall.
+const SOLVER_PASSES: usize = 4;
+
+/// Gap left between a body and the wall it was pushed out of, so rounding does not leave it
+/// touching the wall.
+const SKIN: f32 = 1e-4;
+
+/// Returns the tiles a circle overlaps.
+///
+/// # Arguments
+/// `position` - The centre of the circle
+///
+/// `radius` - The radius of the circle, in tiles
+pub fn covered_tiles(position: &Vector2f, radius: f32) -> Vec<Vector2i> {
+    let mut tiles = Vec::new();
+    for y in (position.y - radius).floor() as i32..=(position.y + radius).floor() as i32 {
+        for x in (position.x - radius).floor() as i32..=(position.x + radius).floor() as i32 {
+            let tile = FloatRect::new(x as f32, y as f32, 1., 1.);
+            if penetration(&tile, position, radius).is_some() {
+                tiles.push(Vector2i::new(x, y));
+            }
+        }
+    }
+    tiles
+}
+
+/// Whether a circle overlaps a wall, the closed part of a door or the moving push-wall.
+///
+/// # Arguments
+/// `map` - The level
+///
+/// `position` - The centre of the circle
+///
+/// `radius` - The radius of the circle, in tiles
+pub fn overlaps(map: &Map, position: &Vector2f, radius: f32) -> bool {
+    blocking_boxes(map, position, radius)
+        .iter()
+        .any(|rect| penetration(rect, position, radius).is_some())
+}
+
+/// Moves a circle through the map and returns where it ends. The part of the move going into
+/// a wall is dropped, so the circle slides along walls and around corners. A circle already
+/// stuck in a wall, e.g. under a door that closed on it, may still move out.
+///
+/// # Arguments
+/// `map` - The level
+///
+/// `position` - The centre of the circle
+///
+/// `radius` - The radius of the circle, in tiles
+///
+/// `movement` - The wanted move
+pub fn slide(map: &Map, position: &Vector2f, radius: f32, movement: &Vector2f) -> Vector2f {
+    let length = (movement.x * movement.x + movement.y * movement.y).sqrt();
+    let steps = (length / (radius * MAX_STEP).max(0.01)).ceil().max(1.);
+    let step = *movement / steps;
+    let stuck = overlaps(map, position, radius);
+    let mut current = *position;
+    for _ in 0..steps as usize {
+        let mut next = current + step;
+        for _ in 0..SOLVER_PASSES {
+            let mut pushed = false;
+            for rect in blocking_boxes(map, &next, radius) {
+                if let Some(push) = penetration(&rect, &next, radius) {
+                    next += push;
+                    pushed = true;
+                }
+            }
+            if !pushed {
+                break;
+            }
+        }
+        // Wedged between walls the pushes cannot solve
+        if !stuck && overlaps(map, &next, radius) {
+            break;
+        }
+        current = next;
+    }
+    current
+}
+
+/// Returns the boxes that may block a circle: the tiles around it and the moving push-wall.
+fn blocking_boxes(map: &Map, position: &Vector2f, radius: f32) -> Vec<FloatRect> {
+    let mut boxes = Vec::new();
+    for y in (position.y - radius).floor() as i32..=(position.y + radius).floor() as i32 {
+        for x in (position.x - radius).floor() as i32..=(position.x + radius).floor() as i32 {
+            if let Some(rect) = map.get_blocking_box(&Vector2i::new(x, y)) {
+                boxes.push(rect);
+            }
+        }
+    }
+    if let Some(moving_wall) = map.get_moving_wall() {
+        let corner = moving_wall.get_position();
+        boxes.push(FloatRect::new(corner.x, corner.y, 1., 1.));
+    }
+    boxes
+}
+
+/// Returns the shortest move taking a circle out of a box, or None when they do not overlap.
+fn penetration(rect: &FloatRect, position: &Vector2f, radius: f32) -> Option<Vector2f> {
+    let closest = Vector2f::new(
+        position.x.max(rect.left).min(rect.left + rect.width),
+        position.y.max(rect.top).min(rect.top + rect.height),
+    );
+    let offset = *position - closest;
+    let distance_squared = offset.x * offset.x + offset.y * offset.y;
+    if distance_squared >= radius * radius {
+        return None;
+    }
+    if distance_squared > 0. {
+        let distance = distance_squared.sqrt();
+        return Some(offset * ((radius - distance + SKIN) / distance));
+    }
+    // The centre is inside the box, leave through the nearest side
+    let exits = [
+        Vector2f::new(rect.left - radius - SKIN - position.x, 0.),
+        Vector2f::new(rect.left + rect.width + radius + SKIN - position.x, 0.),
+        Vector2f::new(0., rect.top - radius - SKIN - position.y),
+        Vector2f::new(0., rect.top + rect.height + radius + SKIN - position.y),
+    ];
+    exits
+        .iter()
+        .min_by(|a, b| {
+            (a.x.abs() + a.y.abs())
+                .partial_cmp(&(b.x.abs() + b.y.abs()))
+                .unwrap()
+        })
+        .copied()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{door::DOOR_SLIDE_TIME, random::Random};
+
+    const RADIUS: f32 = 0.3;
+
+    const LEVEL: &str = "\
+# = 1
+D = 2 door
+map:
+#######
+#.....#
+#.#...#
+#.....#
+###D###
+#.....#
+#<....#
+#######
+";
+
+    fn level() -> Map {
+        Map::parse_ascii(LEVEL).unwrap()
+    }
+
+    #[test]
+    fn moving_into_a_wall_stops_at_the_radius() {
+        let map = level();
+        let end = slide(
+            &map,
+            &Vector2f::new(4.5, 1.5),
+            RADIUS,
+            &Vector2f::new(3., 0.),
+        );
+        assert!((end.x - (6. - RADIUS)).abs() < 1e-3);
+        assert!((end.y - 1.5).abs() < 1e-6);
+        assert!(!overlaps(&map, &end, RADIUS));
+    }
+
+    #[test]
+    fn diagonal_moves_slide_along_walls() {
+        let map = level();
+        // Against the right wall, only the move along it is kept
+        let end = slide(
+            &map,
+            &Vector2f::new(5.6, 1.5),
+            RADIUS,
+            &Vector2f::new(0.5, 0.5),
+        );
+        assert!((end.x - (6. - RADIUS)).abs() < 1e-3);
+        assert!((end.y - 2.).abs() < 1e-3);
+        // Grazing the corner of the pillar pushes the body round it instead of stopping it
+        let start = Vector2f::new(1.5, 1.5);
+        let end = slide(&map, &start, RADIUS, &Vector2f::new(1.5, 0.3));
+        assert!(end.x > 2.9);
+        assert!(!overlaps(&map, &end, RADIUS));
+    }
+
+    #[test]
+    fn fast_moves_do_not_go_through_walls() {
+        let map = level();
+        let end = slide(
+            &map,
+            &Vector2f::new(1.5, 3.5),
+            RADIUS,
+            &Vector2f::new(0., 3.),
+        );
+        assert!(end.y < 4. - RADIUS + 1e-3);
+    }
+
+    #[test]
+    fn doors_block_until_open() {
+        let mut map = level();
+        let start = Vector2f::new(3.5, 3.5);
+        let through = Vector2f::new(0., 2.);
+        let end = slide(&map, &start, RADIUS, &through);
+        assert!((end.y - (4. - RADIUS)).abs() < 1e-3);
+
+        map.open_door(&Vector2i::new(3, 4));
+        map.update_doors(DOOR_SLIDE_TIME, &[]);
+        let end = slide(&map, &start, RADIUS, &through);
+        assert!((end.y - 5.5).abs() < 1e-3);
+    }
+
+    #[test]
+    fn random_moves_never_end_inside_a_wall() {
+        let map = level();
+        let mut random = Random::new(20);
+        let mut position = Vector2f::new(1.5, 6.5);
+        // Behind the closed door, in the room of the spawn
+        for _ in 0..5000 {
+            let movement =
+                Vector2f::new(random.next_f32() * 2. - 1., random.next_f32() * 2. - 1.) * 0.4;
+            position = slide(&map, &position, RADIUS, &movement);
+            assert!(!overlaps(&map, &position, RADIUS), "{:?}", position);
+        }
+        let mut position = Vector2f::new(4.5, 2.5);
+        // Around the pillar
+        for _ in 0..5000 {
+            let movement =
+                Vector2f::new(random.next_f32() * 2. - 1., random.next_f32() * 2. - 1.) * 0.4;
+            position = slide(&map, &position, RADIUS, &movement);
+            assert!(!overlaps(&map, &position, RADIUS), "{:?}", position);
+        }
+    }
+
+    #[test]
+    fn covered_tiles_follow_the_circle() {
+        assert_eq!(
+            covered_tiles(&Vector2f::new(1.5, 1.5), RADIUS),
+            vec![Vector2i::new(1, 1)]
+        );
+        assert_eq!(
+            covered_tiles(&Vector2f::new(1.9, 1.5), RADIUS),
+            vec![Vector2i::new(1, 1), Vector2i::new(2, 1)]
+        );
+        // Near a corner the circle also reaches the diagonal tile
+        assert_eq!(covered_tiles(&Vector2f::new(2.2, 2.2), RADIUS).len(), 4);
+        assert_eq!(covered_tiles(&Vector2f::new(2.25, 1.5), RADIUS).len(), 2);
+    }
+}
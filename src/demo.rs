@@ -0,0 +1,527 @@
+//! Module for demos: the input of each simulation tick of a run, recorded to a file and
+//! played back to repeat the run exactly
+
+use std::{fmt, fs, path::Path};
+
+use rsfml::system::Vector2i;
+
+use crate::{
+    input::{Action, ACTIONS},
+    map::Map,
+    save::{SaveError, StateReader},
+};
+
+/// Version of the demo files written, older or newer files are refused.
+pub const DEMO_VERSION: u16 = 1;
+
+/// First bytes of a demo file.
+const DEMO_MAGIC: &[u8; 4] = b"R3DM";
+
+/// Actions whose value is recorded, as sticks hold them anywhere between 0 and 1.
+pub const ANALOG_ACTIONS: [Action; 6] = [
+    Action::MoveForward,
+    Action::MoveBackward,
+    Action::StrafeLeft,
+    Action::StrafeRight,
+    Action::TurnLeft,
+    Action::TurnRight,
+];
+
+/// Actions handled outside the simulation, which demos leave out.
+const UNRECORDED_ACTIONS: [Action; 4] = [
+    Action::QuickSave,
+    Action::QuickLoad,
+    Action::Rebind,
+    Action::Quit,
+];
+
+// What follows the flags of a tick in a demo file
+const HELD_CHANGED: u8 = 1;
+const PRESSED: u8 = 2;
+const RELEASED: u8 = 4;
+const ANALOG_CHANGED: u8 = 8;
+const MOUSE_MOVED: u8 = 16;
+const GAMEPAD_CONNECTED: u8 = 32;
+const GAMEPAD_DISCONNECTED: u8 = 64;
+
+/// Error raised while reading a demo file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum DemoError {
+    Io(String),
+    Corrupt(String),
+    Version(u16),
+}
+
+impl fmt::Display for DemoError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            DemoError::Io(message) => write!(f, "ERROR: {}", message),
+            DemoError::Corrupt(message) => write!(f, "ERROR: Corrupt demo ({})", message),
+            DemoError::Version(version) => write!(
+                f,
+                "ERROR: The demo has version {}, this game plays version {}",
+                version, DEMO_VERSION
+            ),
+        }
+    }
+}
+
+impl std::error::Error for DemoError {}
+
+/// Demos are read with the [StateReader] of saves, whose errors all mean a broken file.
+impl From<SaveError> for DemoError {
+    fn from(error: SaveError) -> DemoError {
+        match error {
+            SaveError::Corrupt(message) => DemoError::Corrupt(message),
+            other => DemoError::Corrupt(other.to_string()),
+        }
+    }
+}
+
+/// Input of one simulation tick, as the game reads it through the actions.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct TickInput {
+    /// Actions held, one bit per action in the order of [ACTIONS]
+    pub held: u32,
+    /// Actions pressed since the tick before
+    pub pressed: u32,
+    /// Actions released since the tick before
+    pub released: u32,
+    /// How strongly each of [ANALOG_ACTIONS] is held
+    pub analog: [f32; 6],
+    /// Distance of the mouse from the centre of the window along x, when it moved
+    pub mouse_offset: Option<i32>,
+    pub gamepad_connected: bool,
+    pub gamepad_disconnected: bool,
+}
+
+/// Returns the bit of an action in the masks of a [TickInput].
+pub fn action_bit(action: Action) -> u32 {
+    let index = ACTIONS
+        .iter()
+        .position(|listed| *listed == action)
+        .expect("Every action is listed in ACTIONS");
+    1 << index
+}
+
+impl TickInput {
+    pub fn is_held(&self, action: Action) -> bool {
+        self.held & action_bit(action) != 0
+    }
+
+    pub fn is_pressed(&self, action: Action) -> bool {
+        self.pressed & action_bit(action) != 0
+    }
+
+    pub fn is_released(&self, action: Action) -> bool {
+        self.released & action_bit(action) != 0
+    }
+
+    /// Returns how strongly the action is held, from 0 to 1 except for sensitive sticks.
+    pub fn get_value(&self, action: Action) -> f32 {
+        match ANALOG_ACTIONS.iter().position(|analog| *analog == action) {
+            Some(index) => self.analog[index],
+            None if self.is_held(action) => 1.,
+            None => 0.,
+        }
+    }
+}
+
+/// The input of every tick of a run on a level, from its start.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Demo {
+    // Where the level was loaded from, see [MapSource](crate::MapSource)
+    map_name: String,
+    map_checksum: u32,
+    ticks: Vec<TickInput>,
+}
+
+impl Demo {
+    /// Creates an empty demo of a level.
+    ///
+    /// # Arguments
+    /// `map_name` - Where the level was loaded from, to load it again for playback
+    ///
+    /// `map` - The level as the run starts
+    pub fn new(map_name: &str, map: &Map) -> Demo {
+        Demo {
+            map_name: String::from(map_name),
+            map_checksum: map_checksum(map),
+            ticks: Vec::new(),
+        }
+    }
+
+    pub fn get_map_name(&self) -> &str {
+        &self.map_name
+    }
+
+    pub fn get_ticks(&self) -> &[TickInput] {
+        &self.ticks
+    }
+
+    /// Whether the demo was recorded on this level. Playing it on another level would not
+    /// repeat the run.
+    pub fn is_recorded_on(&self, map: &Map) -> bool {
+        self.map_checksum == map_checksum(map)
+    }
+
+    /// Adds the input of the next tick, without the actions handled outside the simulation.
+    pub fn push_tick(&mut self, mut tick: TickInput) {
+        for action in UNRECORDED_ACTIONS.iter() {
+            tick.held &= !action_bit(*action);
+            tick.pressed &= !action_bit(*action);
+            tick.released &= !action_bit(*action);
+        }
+        self.ticks.push(tick);
+    }
+
+    /// Encodes the demo. Each tick starts with flags telling what changed since the tick
+    /// before, so ticks where nothing happens take a single byte.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(DEMO_MAGIC);
+        bytes.extend_from_slice(&DEMO_VERSION.to_le_bytes());
+        bytes.extend_from_slice(&self.map_checksum.to_le_bytes());
+        bytes.extend_from_slice(&(self.map_name.len() as u16).to_le_bytes());
+        bytes.extend_from_slice(self.map_name.as_bytes());
+        bytes.extend_from_slice(&(self.ticks.len() as u32).to_le_bytes());
+        let mut previous = TickInput::default();
+        for tick in self.ticks.iter() {
+            let mut flags = 0;
+            let mut data = Vec::new();
+            if tick.held != previous.held {
+                flags |= HELD_CHANGED;
+                data.extend_from_slice(&tick.held.to_le_bytes());
+            }
+            if tick.pressed != 0 {
+                flags |= PRESSED;
+                data.extend_from_slice(&tick.pressed.to_le_bytes());
+            }
+            if tick.released != 0 {
+                flags |= RELEASED;
+                data.extend_from_slice(&tick.released.to_le_bytes());
+            }
+            if tick.analog != previous.analog {
+                flags |= ANALOG_CHANGED;
+                for value in tick.analog.iter() {
+                    data.extend_from_slice(&value.to_le_bytes());
+                }
+            }
+            if let Some(offset) = tick.mouse_offset {
+                flags |= MOUSE_MOVED;
+                data.extend_from_slice(&offset.to_le_bytes());
+            }
+            if tick.gamepad_connected {
+                flags |= GAMEPAD_CONNECTED;
+            }
+            if tick.gamepad_disconnected {
+                flags |= GAMEPAD_DISCONNECTED;
+            }
+            bytes.push(flags);
+            bytes.extend(data);
+            previous = tick.clone();
+        }
+        bytes
+    }
+
+    /// Decodes a demo written by [Demo::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Demo, DemoError> {
+        if !bytes.starts_with(DEMO_MAGIC) {
+            return Err(DemoError::Corrupt(String::from("not a demo file")));
+        }
+        let mut reader = StateReader::new(&bytes[DEMO_MAGIC.len()..]);
+        let version = reader.read_u16()?;
+        if version != DEMO_VERSION {
+            return Err(DemoError::Version(version));
+        }
+        let map_checksum = reader.read_u32()?;
+        let name_length = reader.read_u16()? as usize;
+        let map_name = String::from_utf8(reader.read_bytes(name_length)?.to_vec())
+            .map_err(|_| DemoError::Corrupt(String::from("level name is not UTF-8")))?;
+        let tick_count = reader.read_u32()?;
+
+        let mut ticks = Vec::new();
+        let mut previous = TickInput::default();
+        for index in 0..tick_count {
+            let truncated = |_| DemoError::Corrupt(format!("ends in the middle of tick {}", index));
+            let flags = reader.read_u8().map_err(truncated)?;
+            if flags & 0x80 != 0 {
+                return Err(DemoError::Corrupt(format!(
+                    "unknown flags at tick {}",
+                    index
+                )));
+            }
+            let mut tick = TickInput {
+                held: previous.held,
+                analog: previous.analog,
+                ..TickInput::default()
+            };
+            if flags & HELD_CHANGED != 0 {
+                tick.held = reader.read_u32().map_err(truncated)?;
+            }
+            if flags & PRESSED != 0 {
+                tick.pressed = reader.read_u32().map_err(truncated)?;
+            }
+            if flags & RELEASED != 0 {
+                tick.released = reader.read_u32().map_err(truncated)?;
+            }
+            if flags & ANALOG_CHANGED != 0 {
+                for value in tick.analog.iter_mut() {
+                    *value = f32::from_bits(reader.read_u32().map_err(truncated)?);
+                    if !value.is_finite() {
+                        return Err(DemoError::Corrupt(format!(
+                            "action value {} at tick {}",
+                            value, index
+                        )));
+                    }
+                }
+            }
+            if flags & MOUSE_MOVED != 0 {
+                tick.mouse_offset = Some(reader.read_i32().map_err(truncated)?);
+            }
+            tick.gamepad_connected = flags & GAMEPAD_CONNECTED != 0;
+            tick.gamepad_disconnected = flags & GAMEPAD_DISCONNECTED != 0;
+            previous = tick.clone();
+            ticks.push(tick);
+        }
+        if !reader.is_at_end() {
+            return Err(DemoError::Corrupt(String::from("data after the last tick")));
+        }
+        Ok(Demo {
+            map_name,
+            map_checksum,
+            ticks,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Demo, DemoError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| {
+            DemoError::Io(format!("Cannot read demo {} ({})", path.display(), err))
+        })?;
+        Demo::from_bytes(&bytes)
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), DemoError> {
+        let path = path.as_ref();
+        fs::write(path, self.to_bytes())
+            .map_err(|err| DemoError::Io(format!("Cannot write demo {} ({})", path.display(), err)))
+    }
+}
+
+/// Returns a checksum of the tiles, spawn and placed things of a level: where each door,
+/// decoration, guard and item is, with the lock of each door and the kind of each guard and
+/// item.
+pub fn map_checksum(map: &Map) -> u32 {
+    // FNV-1a
+    let mut hash: u32 = 0x811C_9DC5;
+    let mut add = |value: u32| {
+        for byte in value.to_le_bytes().iter() {
+            hash ^= *byte as u32;
+            hash = hash.wrapping_mul(0x0100_0193);
+        }
+    };
+    let size = *map.get_map_size();
+    add(size.x as u32);
+    add(size.y as u32);
+    for y in 0..size.y {
+        for x in 0..size.x {
+            let tile = Vector2i::new(x, y);
+            add(map.get_block(&tile).unwrap_or(-1) as u32);
+            add(map.is_push_wall(&tile) as u32);
+        }
+    }
+    let spawn = map.get_spawn();
+    for value in [
+        spawn.position.x,
+        spawn.position.y,
+        spawn.direction.x,
+        spawn.direction.y,
+    ]
+    .iter()
+    {
+        add(value.to_bits());
+    }
+    for door in map.get_doors().iter() {
+        let position = door.get_position();
+        let lock = map
+            .get_tile_info(&position)
+            .and_then(|info| info.lock)
+            .map_or(0, |key| key as u32 + 1);
+        for value in [
+            position.x as u32,
+            position.y as u32,
+            door.is_vertical() as u32,
+            lock,
+        ]
+        .iter()
+        {
+            add(*value);
+        }
+    }
+    for decoration in map.get_decorations().iter() {
+        let position = decoration.position;
+        for value in [
+            position.x as u32,
+            position.y as u32,
+            decoration.sprite as u32,
+        ]
+        .iter()
+        {
+            add(*value);
+        }
+    }
+    for spawn in map.get_actor_spawns().iter() {
+        let position = spawn.position;
+        for value in [
+            position.x as u32,
+            position.y as u32,
+            spawn.direction.x.to_bits(),
+            spawn.direction.y.to_bits(),
+            spawn.patrol as u32,
+        ]
+        .iter()
+        {
+            add(*value);
+        }
+    }
+    for item in map.get_items().iter() {
+        let position = item.position;
+        for value in [position.x as u32, position.y as u32, item.kind as u32].iter() {
+            add(*value);
+        }
+    }
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LEVEL: &str = "\
+# = 1
+map:
+#####
+#.>.#
+#####
+";
+
+    fn demo() -> Demo {
+        let map = Map::parse_ascii(LEVEL).unwrap();
+        let mut demo = Demo::new("map level.map", &map);
+        demo.push_tick(TickInput {
+            held: action_bit(Action::MoveForward) | action_bit(Action::Quit),
+            pressed: action_bit(Action::MoveForward),
+            analog: [1., 0., 0., 0., 0., 0.],
+            mouse_offset: Some(-12),
+            gamepad_connected: true,
+            ..TickInput::default()
+        });
+        for _ in 0..10 {
+            let held = demo.get_ticks().last().unwrap().held;
+            let analog = demo.get_ticks().last().unwrap().analog;
+            demo.push_tick(TickInput {
+                held,
+                analog,
+                ..TickInput::default()
+            });
+        }
+        demo.push_tick(TickInput {
+            released: action_bit(Action::MoveForward),
+            analog: [0., 0., 0.4, 0., 0., 0.25],
+            ..TickInput::default()
+        });
+        demo
+    }
+
+    #[test]
+    fn demos_survive_their_file() {
+        let demo = demo();
+        let bytes = demo.to_bytes();
+        let read = Demo::from_bytes(&bytes).unwrap();
+        assert_eq!(read, demo);
+        assert_eq!(read.get_map_name(), "map level.map");
+        assert!(read.is_recorded_on(&Map::parse_ascii(LEVEL).unwrap()));
+        // Ticks where nothing changes take a byte each
+        let header = 4 + 2 + 4 + 2 + "map level.map".len() + 4;
+        assert_eq!(
+            bytes.len(),
+            header + (1 + 4 + 4 + 24 + 4) + 10 + (1 + 4 + 4 + 24)
+        );
+    }
+
+    #[test]
+    fn actions_outside_the_simulation_are_left_out() {
+        let demo = demo();
+        let tick = &demo.get_ticks()[0];
+        assert!(tick.is_held(Action::MoveForward));
+        assert!(!tick.is_held(Action::Quit));
+        assert!(tick.is_pressed(Action::MoveForward));
+        assert_eq!(tick.get_value(Action::MoveForward), 1.);
+        assert_eq!(demo.get_ticks()[11].get_value(Action::StrafeLeft), 0.4);
+    }
+
+    #[test]
+    fn broken_files_are_refused() {
+        let bytes = demo().to_bytes();
+        assert_eq!(
+            Demo::from_bytes(b"GIF89a"),
+            Err(DemoError::Corrupt(String::from("not a demo file")))
+        );
+        assert_eq!(
+            Demo::from_bytes(&bytes[..8]).unwrap_err().to_string(),
+            "ERROR: Corrupt demo (the data is cut short)"
+        );
+        let error = Demo::from_bytes(&bytes[..bytes.len() - 3]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "ERROR: Corrupt demo (ends in the middle of tick 11)"
+        );
+        let mut old = bytes.clone();
+        old[4] = 0;
+        assert_eq!(Demo::from_bytes(&old), Err(DemoError::Version(0)));
+        let other_level = Map::parse_ascii(&LEVEL.replace(">.", ".>")).unwrap();
+        assert!(!demo().is_recorded_on(&other_level));
+    }
+
+    #[test]
+    fn checksums_tell_apart_where_things_are_and_what_they_are() {
+        let level = "\
+# = 1
+D = 1 door
++ = 48 item
+g = 2 guard
+map:
+#######
+#>.+.g#
+###D###
+#.....#
+#######
+";
+        let checksum = |level: &str| map_checksum(&Map::parse_ascii(level).unwrap());
+        let original = checksum(level);
+        assert_eq!(checksum(level), original);
+        for (from, to) in [
+            ("#>.+.g#", "#>+..g#"),
+            ("#>.+.g#", "#>.+g.#"),
+            ("48 item", "47 item"),
+            ("2 guard", "3 guard"),
+            ("2 guard", "2 patrol"),
+            ("1 door", "1 door gold"),
+        ]
+        .iter()
+        {
+            assert_ne!(
+                checksum(&level.replace(from, to)),
+                original,
+                "{} -> {}",
+                from,
+                to
+            );
+        }
+        assert_ne!(
+            checksum(&level.replace("1 door", "1 door gold")),
+            checksum(&level.replace("1 door", "1 door silver"))
+        );
+    }
+}
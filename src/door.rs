@@ -0,0 +1,296 @@
+//! Module for sliding doors
+
+use rsfml::{
+    graphics::FloatRect,
+    system::{Vector2f, Vector2i},
+};
+
+use crate::save::{SaveError, StateReader, StateWriter};
+
+/// Seconds a door takes to slide fully open or closed.
+pub const DOOR_SLIDE_TIME: f32 = 1.;
+
+/// Seconds a door stays open before closing by itself.
+pub const DOOR_OPEN_TIME: f32 = 4.;
+
+/// Room needed on each side of a point for it to fit through a half open door.
+const DOOR_CLEARANCE: f32 = 0.2;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum DoorState {
+    Closed,
+    Opening,
+    Open,
+    Closing,
+}
+
+/// A door tile. The panel sits in the middle of its tile and slides sideways into the
+/// neighbouring wall when opened.
+#[derive(Clone, Debug)]
+pub struct Door {
+    position: Vector2i,
+    vertical: bool,
+    state: DoorState,
+    openness: f32,
+    open_timer: f32,
+}
+
+impl Door {
+    /// Creates a closed door.
+    ///
+    /// # Arguments
+    /// `position` - The tile of the door
+    ///
+    /// `vertical` - Whether the panel runs along the y axis (walls above and below the door)
+    pub fn new(position: Vector2i, vertical: bool) -> Door {
+        Door {
+            position,
+            vertical,
+            state: DoorState::Closed,
+            openness: 0.,
+            open_timer: 0.,
+        }
+    }
+
+    pub fn get_position(&self) -> Vector2i {
+        self.position
+    }
+
+    pub fn is_vertical(&self) -> bool {
+        self.vertical
+    }
+
+    pub fn get_state(&self) -> DoorState {
+        self.state
+    }
+
+    /// How far the panel has slid, from 0 (closed) to 1 (open).
+    pub fn get_openness(&self) -> f32 {
+        self.openness
+    }
+
+    /// Starts opening the door if it is closed or closing.
+    pub fn open(&mut self) {
+        if let DoorState::Closed | DoorState::Closing = self.state {
+            self.state = DoorState::Opening;
+        }
+    }
+
+    /// Opens a closed door, or closes an open one.
+    ///
+    /// # Arguments
+    /// `blocked` - Whether something stands in the doorway, which keeps the door open
+    pub fn activate(&mut self, blocked: bool) {
+        self.state = match self.state {
+            DoorState::Closed | DoorState::Closing => DoorState::Opening,
+            DoorState::Open | DoorState::Opening if !blocked => DoorState::Closing,
+            state => state,
+        };
+    }
+
+    /// Slides the door and counts down the time before it closes.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `blocked` - Whether something stands in the doorway, which keeps the door open
+    pub fn update(&mut self, delta: f32, blocked: bool) {
+        match self.state {
+            DoorState::Opening => {
+                self.openness += delta / DOOR_SLIDE_TIME;
+                if self.openness >= 1. {
+                    self.openness = 1.;
+                    self.open_timer = 0.;
+                    self.state = DoorState::Open;
+                }
+            }
+            DoorState::Open => {
+                self.open_timer += delta;
+                if blocked {
+                    self.open_timer = 0.;
+                } else if self.open_timer >= DOOR_OPEN_TIME {
+                    self.state = DoorState::Closing;
+                }
+            }
+            DoorState::Closing => {
+                if blocked {
+                    self.state = DoorState::Opening;
+                    return;
+                }
+                self.openness -= delta / DOOR_SLIDE_TIME;
+                if self.openness <= 0. {
+                    self.openness = 0.;
+                    self.state = DoorState::Closed;
+                }
+            }
+            DoorState::Closed => {}
+        }
+    }
+
+    /// Writes the state, openness and timer of the door to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.state as u8);
+        writer.write_f32(self.openness);
+        writer.write_f32(self.open_timer);
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.state = match reader.read_u8()? {
+            0 => DoorState::Closed,
+            1 => DoorState::Opening,
+            2 => DoorState::Open,
+            3 => DoorState::Closing,
+            state => return Err(SaveError::Corrupt(format!("door state {}", state))),
+        };
+        self.openness = reader.read_f32()?.clamp(0., 1.);
+        self.open_timer = reader.read_f32()?;
+        Ok(())
+    }
+
+    /// Checks whether a ray entering the door tile hits the panel, which stands in the middle
+    /// of the tile. Returns how far along the ray the panel is hit, in lengths of `ray_dir`.
+    ///
+    /// # Arguments
+    /// `side` - 0 when the ray entered the tile through a vertical border (x side), 1 through a
+    /// horizontal one (y side)
+    ///
+    /// `ray_pos` - Start of the ray
+    ///
+    /// `ray_dir` - Direction of the ray
+    pub fn hit_by_ray(&self, side: i32, ray_pos: &Vector2f, ray_dir: &Vector2f) -> Option<f32> {
+        // Rays coming along the door frame pass through to the walls around it
+        if self.vertical != (side == 0) {
+            return None;
+        }
+        let (x, y) = (self.position.x as f32, self.position.y as f32);
+        let (t, along) = if self.vertical {
+            let t = (x + 0.5 - ray_pos.x) / ray_dir.x;
+            (t, ray_pos.y + t * ray_dir.y - y)
+        } else {
+            let t = (y + 0.5 - ray_pos.y) / ray_dir.y;
+            (t, ray_pos.x + t * ray_dir.x - x)
+        };
+        // The ray leaves the tile before reaching the panel, or goes through the open gap
+        if !(0. ..1.).contains(&along) || along < self.openness {
+            return None;
+        }
+        Some(t)
+    }
+
+    /// Returns the part of the door tile the panel still blocks, or None when it is fully open.
+    pub fn get_blocking_box(&self) -> Option<FloatRect> {
+        if self.openness >= 1. {
+            return None;
+        }
+        let (x, y) = (self.position.x as f32, self.position.y as f32);
+        Some(if self.vertical {
+            FloatRect::new(x, y + self.openness, 1., 1. - self.openness)
+        } else {
+            FloatRect::new(x + self.openness, y, 1. - self.openness, 1.)
+        })
+    }
+
+    /// Whether a point inside the door tile fits through the opened part of the doorway. The
+    /// whole doorway is free once the door is fully open, like [Door::get_blocking_box].
+    pub fn is_passable_at(&self, point: &Vector2f) -> bool {
+        if self.openness >= 1. {
+            return true;
+        }
+        let along = if self.vertical {
+            point.y - self.position.y as f32
+        } else {
+            point.x - self.position.x as f32
+        };
+        along + DOOR_CLEARANCE <= self.openness
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs door updates of a tenth of a second.
+    fn update(door: &mut Door, seconds: f32, blocked: bool) {
+        for _ in 0..(seconds * 10.).round() as i32 {
+            door.update(0.1, blocked);
+        }
+    }
+
+    #[test]
+    fn doors_open_then_close_by_themselves() {
+        let mut door = Door::new(Vector2i::new(3, 2), false);
+        door.activate(false);
+        update(&mut door, DOOR_SLIDE_TIME / 2., false);
+        assert_eq!(door.get_state(), DoorState::Opening);
+        assert!((door.get_openness() - 0.5).abs() < 1e-4);
+        update(&mut door, DOOR_SLIDE_TIME / 2., false);
+        assert_eq!(door.get_state(), DoorState::Open);
+        assert_eq!(door.get_openness(), 1.);
+
+        // Updates stop short of the timers by rounding, one more gets past them
+        update(&mut door, DOOR_OPEN_TIME + 0.1, false);
+        assert_eq!(door.get_state(), DoorState::Closing);
+        update(&mut door, DOOR_SLIDE_TIME + 0.1, false);
+        assert_eq!(door.get_state(), DoorState::Closed);
+        assert_eq!(door.get_openness(), 0.);
+    }
+
+    #[test]
+    fn doors_stay_open_on_whoever_stands_in_them() {
+        let mut door = Door::new(Vector2i::new(3, 2), false);
+        door.open();
+        update(&mut door, DOOR_SLIDE_TIME + DOOR_OPEN_TIME * 2., true);
+        assert_eq!(door.get_state(), DoorState::Open);
+        // Using a blocked door does not close it either
+        door.activate(true);
+        assert_eq!(door.get_state(), DoorState::Open);
+
+        update(&mut door, DOOR_OPEN_TIME, false);
+        door.update(0.1, false);
+        assert_eq!(door.get_state(), DoorState::Closing);
+        door.update(0.1, true);
+        assert_eq!(door.get_state(), DoorState::Opening);
+    }
+
+    #[test]
+    fn only_the_opened_part_of_the_doorway_is_passable() {
+        let mut door = Door::new(Vector2i::new(3, 2), false);
+        let near = Vector2f::new(3.1, 2.5);
+        let far = Vector2f::new(3.95, 2.5);
+        assert!(!door.is_passable_at(&near));
+        assert_eq!(
+            door.get_blocking_box(),
+            Some(FloatRect::new(3., 2., 1., 1.))
+        );
+
+        door.open();
+        update(&mut door, DOOR_SLIDE_TIME / 2., false);
+        assert!(door.is_passable_at(&near));
+        assert!(!door.is_passable_at(&Vector2f::new(3.4, 2.5)));
+        assert!(!door.is_passable_at(&far));
+
+        // Fully open, the doorway is free up to the wall the panel slid into
+        update(&mut door, DOOR_SLIDE_TIME / 2., false);
+        assert!(door.is_passable_at(&far));
+        assert_eq!(door.get_blocking_box(), None);
+
+        let mut vertical = Door::new(Vector2i::new(3, 2), true);
+        vertical.open();
+        update(&mut vertical, DOOR_SLIDE_TIME, false);
+        assert!(vertical.is_passable_at(&Vector2f::new(3.5, 2.95)));
+    }
+
+    #[test]
+    fn saved_openness_is_kept_between_closed_and_open() {
+        let mut door = Door::new(Vector2i::new(3, 2), false);
+        for &(saved, loaded) in [(1.5, 1.), (-0.25, 0.), (0.5, 0.5)].iter() {
+            let mut writer = StateWriter::new();
+            writer.write_u8(DoorState::Opening as u8);
+            writer.write_f32(saved);
+            writer.write_f32(0.);
+            let bytes = writer.into_bytes();
+            door.load_state(&mut StateReader::new(&bytes)).unwrap();
+            assert_eq!(door.get_openness(), loaded);
+        }
+    }
+}
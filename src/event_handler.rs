@@ -1,21 +1,123 @@
 //! Module for event handling
 
-use rsfml::{
-    graphics::RenderWindow,
-    window::{mouse::Button as MouseButton, Event, Key},
+use rsfml::window::{joystick::Axis, mouse::Button as MouseButton, Event, Key};
+
+use crate::{
+    demo::{action_bit, TickInput, ANALOG_ACTIONS},
+    input::{Action, Binding, InputMap, ACTIONS},
+    input_source::InputSource,
 };
 
+/// How far a gamepad axis must be pushed, from 0 to 1, to hold down the actions it is bound
+/// to, e.g. to fire with a trigger.
+pub const AXIS_HELD_THRESHOLD: f32 = 0.5;
+
 pub struct EventHandler {
     pub events: Vec<Event>,
+    input_map: InputMap,
+    // Bindings held down at the last update, and at the update before
+    held: Vec<Binding>,
+    previously_held: Vec<Binding>,
+    // Positions of the bound gamepad axes at the last update, from -100 to 100
+    axes: Vec<(Axis, f32)>,
+    // Tick of a demo being played back, which the actions are read from instead
+    replaying: Option<TickInput>,
 }
 
 impl EventHandler {
     pub fn new() -> EventHandler {
-        EventHandler { events: Vec::new() }
+        EventHandler::with_input_map(InputMap::default())
+    }
+
+    /// Creates an event handler resolving actions with the given bindings.
+    pub fn with_input_map(input_map: InputMap) -> EventHandler {
+        EventHandler {
+            events: Vec::new(),
+            input_map,
+            held: Vec::new(),
+            previously_held: Vec::new(),
+            axes: Vec::new(),
+            replaying: None,
+        }
+    }
+
+    pub fn get_input_map(&self) -> &InputMap {
+        &self.input_map
+    }
+
+    pub fn get_input_map_mut(&mut self) -> &mut InputMap {
+        &mut self.input_map
+    }
+
+    /// Whether a key or button bound to the action was held down at the last update, or a
+    /// gamepad axis bound to it pushed past [AXIS_HELD_THRESHOLD].
+    pub fn is_action_held(&self, action: Action) -> bool {
+        if let Some(tick) = &self.replaying {
+            return tick.is_held(action);
+        }
+        self.input_map.is_bound_in(action, &self.held)
+    }
+
+    /// Returns how strongly the action is held, from 0 to 1 for keys and buttons. Gamepad axes
+    /// bound to moving and turning give a value shaped by their
+    /// [AxisResponse](crate::input::AxisResponse), other axes count as keys.
+    pub fn get_action_value(&self, action: Action) -> f32 {
+        if let Some(tick) = &self.replaying {
+            return tick.get_value(action);
+        }
+        let response = self.input_map.get_axis_response(action);
+        self.input_map
+            .get_bindings(action)
+            .iter()
+            .map(|binding| match (binding, response) {
+                (Binding::GamepadAxis { axis, positive }, Some(response)) => {
+                    response.apply(self.get_axis_pushed(*axis, *positive))
+                }
+                _ if self.held.contains(binding) => 1.,
+                _ => 0.,
+            })
+            .fold(0., f32::max)
+    }
+
+    /// Returns how far an axis was pushed towards one end at the last update, from 0 to 1.
+    fn get_axis_pushed(&self, axis: Axis, positive: bool) -> f32 {
+        let position = self
+            .axes
+            .iter()
+            .find(|(moved, _)| *moved == axis)
+            .map_or(0., |(_, position)| *position / 100.);
+        if positive {
+            position.max(0.)
+        } else {
+            (-position).max(0.)
+        }
+    }
+
+    /// Whether a binding of the action was pressed since the update before: the events read
+    /// press it, or it is held down and was not before.
+    pub fn has_action_pressed_event(&self, action: Action) -> bool {
+        if let Some(tick) = &self.replaying {
+            return tick.is_pressed(action);
+        }
+        self.input_map.is_pressed(action, &self.events)
+            || (self.is_action_held(action)
+                && !self.input_map.is_bound_in(action, &self.previously_held))
+    }
+
+    /// Whether a binding of the action was released since the update before: the events read
+    /// release it, or it is not held down anymore.
+    pub fn has_action_released_event(&self, action: Action) -> bool {
+        if let Some(tick) = &self.replaying {
+            return tick.is_released(action);
+        }
+        self.input_map.is_released(action, &self.events)
+            || (!self.is_action_held(action)
+                && self.input_map.is_bound_in(action, &self.previously_held))
     }
 
-    pub fn is_key_pressed(&self, key: Key) -> bool {
-        Key::is_pressed(key)
+    /// Returns the first key, mouse button or wheel move pressed in the events read.
+    pub fn get_pressed_binding(&self) -> Option<Binding> {
+        self.events.iter().find_map(Binding::from_pressed_event)
     }
 
     pub fn has_closed_event(&self) -> bool {
@@ -30,6 +132,18 @@ impl EventHandler {
         self.events.iter().any(|ev| *ev == Event::LostFocus)
     }
 
+    pub fn has_gamepad_connected_event(&self) -> bool {
+        self.events
+            .iter()
+            .any(|ev| matches!(ev, Event::JoystickConnected { .. }))
+    }
+
+    pub fn has_gamepad_disconnected_event(&self) -> bool {
+        self.events
+            .iter()
+            .any(|ev| matches!(ev, Event::JoystickDisconnected { .. }))
+    }
+
     pub fn has_text_entered(&self) -> Option<char> {
         self.events.iter().find_map(|ev| match *ev {
             Event::TextEntered { unicode } => Some(unicode),
@@ -122,17 +236,100 @@ impl EventHandler {
         self.events.clone()
     }
 
-    pub fn update_events(&mut self, render_window: &mut RenderWindow) -> () {
+    /// Forgets the events read, e.g. once a simulation tick has handled them. The keys held
+    /// down stay held, without counting as pressed again.
+    pub fn clear_events(&mut self) {
         self.events.clear();
-        while let Some(ev) = render_window.poll_event() {
-            self.events.push(ev)
+        self.previously_held = self.held.clone();
+        if let Some(tick) = self.replaying.as_mut() {
+            tick.pressed = 0;
+            tick.released = 0;
         }
     }
-}
 
-// TODO IMPLEMENT FUNCTION FOR JOYSTICK HANDLE
-// JoystickButtonPressed { joystickid : int, button : int },
-// JoystickButtonReleased { joystickid : int, button : int },
-// JoystickMoved { joystickid : uint, axis : Axis, position : float },
-// JoystickConnected { joystickid : uint },
-// JoystickDisconnected { joystickid : uint },
+    /// Returns the input of the tick as the game reads it, to record it in a demo.
+    ///
+    /// # Arguments
+    /// `window_center_x` - Where mouse moves are measured from, like the view turning with them
+    pub fn get_tick_input(&self, window_center_x: i32) -> TickInput {
+        let mut tick = TickInput::default();
+        for action in ACTIONS.iter() {
+            if self.is_action_held(*action) {
+                tick.held |= action_bit(*action);
+            }
+            if self.has_action_pressed_event(*action) {
+                tick.pressed |= action_bit(*action);
+            }
+            if self.has_action_released_event(*action) {
+                tick.released |= action_bit(*action);
+            }
+        }
+        for (value, action) in tick.analog.iter_mut().zip(ANALOG_ACTIONS.iter()) {
+            *value = self.get_action_value(*action);
+        }
+        tick.mouse_offset = self
+            .has_mouse_moved_event()
+            .map(|(x, _)| x - window_center_x);
+        tick.gamepad_connected = self.has_gamepad_connected_event();
+        tick.gamepad_disconnected = self.has_gamepad_disconnected_event();
+        tick
+    }
+
+    /// Replaces the input with a tick recorded in a demo, until the next
+    /// [EventHandler::update_events].
+    ///
+    /// # Arguments
+    /// `tick` - The recorded input
+    ///
+    /// `window_center_x` - Where mouse moves are measured from
+    pub fn replay_tick(&mut self, tick: &TickInput, window_center_x: i32) {
+        self.events.clear();
+        if let Some(offset) = tick.mouse_offset {
+            self.events.push(Event::MouseMoved {
+                x: window_center_x + offset,
+                y: 0,
+            });
+        }
+        if tick.gamepad_connected {
+            self.events.push(Event::JoystickConnected { joystickid: 0 });
+        }
+        if tick.gamepad_disconnected {
+            self.events
+                .push(Event::JoystickDisconnected { joystickid: 0 });
+        }
+        self.held.clear();
+        self.previously_held.clear();
+        self.axes.clear();
+        self.replaying = Some(tick.clone());
+    }
+
+    /// Reads the new events, which keys and buttons are held down and where the gamepad axes
+    /// are.
+    ///
+    /// # Arguments
+    /// `input_source` - Where to read input from, e.g. a [WindowInput](crate::input_source::WindowInput)
+    pub fn update_events(&mut self, input_source: &mut dyn InputSource) -> () {
+        self.replaying = None;
+        self.events = input_source.poll_events();
+        self.previously_held = std::mem::take(&mut self.held);
+        let bindings = self.input_map.get_all_bindings();
+        self.axes.clear();
+        for binding in bindings.iter() {
+            if let Binding::GamepadAxis { axis, .. } = binding {
+                if !self.axes.iter().any(|(read, _)| read == axis) {
+                    self.axes
+                        .push((*axis, input_source.get_axis_position(*axis)));
+                }
+            }
+        }
+        self.held = bindings
+            .into_iter()
+            .filter(|binding| match binding {
+                Binding::GamepadAxis { axis, positive } => {
+                    self.get_axis_pushed(*axis, *positive) >= AXIS_HELD_THRESHOLD
+                }
+                _ => input_source.is_held(binding),
+            })
+            .collect();
+    }
+}
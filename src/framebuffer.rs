@@ -0,0 +1,106 @@
+//! Module for the pixel buffer the raycasting engine renders into
+
+use rsfml::graphics::Color;
+
+/// An RGBA image in memory, rows from the top. The engine renders a frame into it without
+/// needing a window, and the window path uploads it to a texture.
+#[derive(Clone, Debug, PartialEq)]
+pub struct FrameBuffer {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+impl FrameBuffer {
+    /// Creates a black buffer.
+    pub fn new(width: u32, height: u32) -> FrameBuffer {
+        FrameBuffer {
+            width,
+            height,
+            pixels: vec![0; (width * height * 4) as usize],
+        }
+    }
+
+    /// Creates a buffer from RGBA pixels. Returns None when their count does not match.
+    pub fn from_pixels(width: u32, height: u32, pixels: Vec<u8>) -> Option<FrameBuffer> {
+        if pixels.len() != (width * height * 4) as usize {
+            return None;
+        }
+        Some(FrameBuffer {
+            width,
+            height,
+            pixels,
+        })
+    }
+
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the RGBA bytes of the whole buffer.
+    pub fn get_pixels(&self) -> &[u8] {
+        &self.pixels
+    }
+
+    /// Fills the whole buffer with one color.
+    pub fn clear(&mut self, color: Color) {
+        for pixel in self.pixels.chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    /// Fills the rows from `top` to `bottom` (excluded) with one color.
+    pub fn fill_rows(&mut self, top: u32, bottom: u32, color: Color) {
+        let start = (top.min(self.height) * self.width * 4) as usize;
+        let end = (bottom.min(self.height) * self.width * 4) as usize;
+        if start >= end {
+            return;
+        }
+        for pixel in self.pixels[start..end].chunks_exact_mut(4) {
+            pixel.copy_from_slice(&[color.r, color.g, color.b, color.a]);
+        }
+    }
+
+    /// Returns the color of a pixel, or None outside of the buffer.
+    pub fn get_pixel(&self, x: u32, y: u32) -> Option<[u8; 4]> {
+        if x >= self.width || y >= self.height {
+            return None;
+        }
+        let index = ((y * self.width + x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[index..index + 4]);
+        Some(pixel)
+    }
+
+    /// Sets the color of a pixel, pixels outside of the buffer are ignored.
+    pub fn set_pixel(&mut self, x: u32, y: u32, pixel: [u8; 4]) {
+        if x >= self.width || y >= self.height {
+            return;
+        }
+        let index = ((y * self.width + x) * 4) as usize;
+        self.pixels[index..index + 4].copy_from_slice(&pixel);
+    }
+
+    /// Returns a copy of the buffer resized to another size, each pixel taking the color at
+    /// its centre in the original.
+    pub fn scaled(&self, width: u32, height: u32) -> FrameBuffer {
+        let mut scaled = FrameBuffer::new(width, height);
+        if self.width == 0 || self.height == 0 {
+            return scaled;
+        }
+        for y in 0..height {
+            let source_y = ((y as u64 * 2 + 1) * self.height as u64 / (height as u64 * 2)) as u32;
+            for x in 0..width {
+                let source_x = ((x as u64 * 2 + 1) * self.width as u64 / (width as u64 * 2)) as u32;
+                if let Some(pixel) = self.get_pixel(source_x, source_y) {
+                    scaled.set_pixel(x, y, pixel);
+                }
+            }
+        }
+        scaled
+    }
+}
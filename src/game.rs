@@ -4,10 +4,36 @@
 
 use rsfml::{
     graphics::{Color, Font, RenderTarget, RenderWindow},
-    window::Key,
+    system::{Clock, Vector2i},
 };
 
-use crate::{event_handler::*, fps::*, game_mode::*, texture_loader::TextureLoader};
+use crate::{
+    demo::Demo,
+    event_handler::*,
+    fps::*,
+    game_mode::*,
+    input::{Action, InputMap},
+    input_source::WindowInput,
+    map::Map,
+    save::{SaveError, SaveGame, QUICKSAVE_PATH},
+    texture_loader::TextureLoader,
+    timestep::FixedTimestep,
+};
+
+/// Simulation ticks per second, like the original game.
+pub const TICK_RATE: u32 = 70;
+
+/// Demos played instead of reading the player input.
+struct Playback {
+    // Each demo with the level it was recorded on
+    demos: Vec<(Demo, Map)>,
+    current: usize,
+    tick: usize,
+    // Replays the ticks of the demos, while the handler of the game loop reads the window
+    event_handler: EventHandler,
+    // Attract mode loops the demos until a key is pressed, then starts the game
+    attract: bool,
+}
 
 pub struct GameLoop<'s> {
     render_window: RenderWindow,
@@ -15,24 +41,132 @@ pub struct GameLoop<'s> {
     event_handler: EventHandler,
     clear_color: Color,
     game_mode: GameMode<'s>,
+    timestep: FixedTimestep,
+    texture_loader: &'s TextureLoader,
+    font: &'s Font,
+    no_ground: bool,
+    // The level played, kept to start it again after attract mode or to load a save into
+    map: Map,
+    // Where the level was loaded from, see [MapSource](crate::MapSource)
+    map_name: String,
+    // File written and read by the quicksave and quickload keys
+    save_path: String,
+    // Demo being recorded and the file it is saved to once the game ends
+    recording: Option<(Demo, String)>,
+    playback: Option<Playback>,
 }
 
 impl<'s> GameLoop<'s> {
     pub fn new(
         render_window: RenderWindow,
         texture_loader: &'s TextureLoader,
+        font: &'s Font,
+        map: Map,
+        map_name: &str,
         no_ground: bool,
+        input_map: InputMap,
     ) -> GameLoop<'s> {
         let tmp_size = render_window.size();
         GameLoop {
             render_window,
             fps_handler: None,
-            event_handler: EventHandler::new(),
+            event_handler: EventHandler::with_input_map(input_map),
             clear_color: Color::rgb(3, 64, 59),
-            game_mode: GameMode::new(tmp_size, texture_loader, no_ground),
+            game_mode: GameMode::new(tmp_size, texture_loader, font, map.clone(), no_ground),
+            timestep: FixedTimestep::new(TICK_RATE),
+            texture_loader,
+            font,
+            no_ground,
+            map,
+            map_name: String::from(map_name),
+            save_path: String::from(QUICKSAVE_PATH),
+            recording: None,
+            playback: None,
+        }
+    }
+
+    /// Records the input of every tick from the start of the level, and saves the demo when
+    /// the game loop ends.
+    ///
+    /// # Arguments
+    /// `path` - The demo file to write
+    pub fn record_demo(&mut self, path: &str) {
+        self.recording = Some((Demo::new(&self.map_name, &self.map), String::from(path)));
+    }
+
+    /// Sets the file written and read by the quicksave and quickload keys, [QUICKSAVE_PATH]
+    /// by default.
+    pub fn set_save_path(&mut self, path: &str) {
+        self.save_path = String::from(path);
+    }
+
+    /// Replaces the game in play with a save of the same level. The game in play is kept when
+    /// the save cannot be loaded.
+    pub fn load_game(&mut self, save: &SaveGame) -> Result<(), SaveError> {
+        let mut game_mode = self.new_game_mode(self.map.clone());
+        game_mode.load_game(save)?;
+        self.game_mode = game_mode;
+        Ok(())
+    }
+
+    fn quick_save(&mut self) {
+        let save = self.game_mode.save_game(&self.map_name);
+        match save.save(&self.save_path) {
+            Ok(()) => self.game_mode.show_message("Game saved"),
+            Err(err) => {
+                eprintln!("{}", err);
+                self.game_mode.show_message(err.get_summary());
+            }
+        }
+    }
+
+    fn quick_load(&mut self) {
+        // The run of a demo starts from the start of the level
+        if self.recording.is_some() {
+            self.game_mode
+                .show_message("Cannot load while recording a demo");
+            return;
+        }
+        match SaveGame::from_file(&self.save_path).and_then(|save| self.load_game(&save)) {
+            Ok(()) => self.game_mode.show_message("Game loaded"),
+            Err(err) => {
+                eprintln!("{}", err);
+                self.game_mode.show_message(err.get_summary());
+            }
         }
     }
 
+    /// Plays demos instead of reading the player input, from the start of their level.
+    ///
+    /// # Arguments
+    /// `demos` - Each demo with the level it was recorded on
+    ///
+    /// `attract` - Whether to loop the demos until a key is pressed and then start the game,
+    /// instead of closing the window after the last one
+    pub fn play_demos(&mut self, demos: Vec<(Demo, Map)>, attract: bool) {
+        if demos.is_empty() {
+            return;
+        }
+        self.game_mode = self.new_game_mode(demos[0].1.clone());
+        self.playback = Some(Playback {
+            demos,
+            current: 0,
+            tick: 0,
+            event_handler: EventHandler::new(),
+            attract,
+        });
+    }
+
+    fn new_game_mode(&self, map: Map) -> GameMode<'s> {
+        GameMode::new(
+            self.render_window.size(),
+            self.texture_loader,
+            self.font,
+            map,
+            self.no_ground,
+        )
+    }
+
     pub fn activate_FPS(&mut self, font: &'s Font) {
         if let None = self.fps_handler {
             self.fps_handler = Some(FPSHandler::new(font))
@@ -45,25 +179,114 @@ impl<'s> GameLoop<'s> {
         }
     }
 
+    /// Runs the game until the window is closed. The simulation advances by fixed ticks, as
+    /// many as the real time elapsed calls for, and each frame is drawn in between two ticks.
     pub fn run(&mut self) {
+        let mut clock = Clock::start();
         while self.render_window.is_open() {
-            self.update();
+            let ticks = self.timestep.advance(clock.restart().as_seconds());
+            for tick in 0..ticks {
+                self.update(tick == 0);
+            }
             self.draw();
         }
+        if let Some((demo, path)) = &self.recording {
+            match demo.save(path) {
+                Ok(()) => println!("Demo of {} ticks saved to {}", demo.get_ticks().len(), path),
+                Err(err) => eprintln!("{}", err),
+            }
+        }
     }
 
-    pub fn update(&mut self) {
-        self.event_handler.update_events(&mut self.render_window);
-        if self.event_handler.has_closed_event() || self.event_handler.is_key_pressed(Key::Escape) {
+    /// Simulates one tick.
+    ///
+    /// # Arguments
+    /// `read_events` - Whether to read the window events, only the first tick of a frame gets
+    /// them so each event is handled once
+    pub fn update(&mut self, read_events: bool) {
+        if read_events {
+            self.event_handler
+                .update_events(&mut WindowInput::new(&mut self.render_window));
+        } else {
+            self.event_handler.clear_events();
+        }
+        let quit = self.event_handler.has_action_pressed_event(Action::Quit)
+            && !self.game_mode.is_rebinding();
+        if self.event_handler.has_closed_event() || quit {
             self.render_window.close();
         }
-        self.game_mode.update(&self.event_handler);
-        self.fps_handler.as_mut().unwrap().update();
+        if self.playback.is_some() {
+            self.update_playback();
+        } else {
+            // While rebinding, the keys are bound instead
+            if !self.game_mode.is_rebinding() {
+                if self
+                    .event_handler
+                    .has_action_pressed_event(Action::QuickSave)
+                {
+                    self.quick_save();
+                } else if self
+                    .event_handler
+                    .has_action_pressed_event(Action::QuickLoad)
+                {
+                    self.quick_load();
+                }
+            }
+            let center_x = self.render_window.size().x as i32 / 2;
+            let tick_input = self.event_handler.get_tick_input(center_x);
+            let was_rebinding = self.game_mode.is_rebinding();
+            self.game_mode
+                .update(&mut self.event_handler, self.timestep.get_tick());
+            // Rebinding pauses the game, the ticks it takes are not part of the run
+            if let Some((demo, _)) = self.recording.as_mut() {
+                if !was_rebinding && !self.game_mode.is_rebinding() {
+                    demo.push_tick(tick_input);
+                }
+            }
+        }
+        if read_events {
+            // Mouse moves turn the view by their distance from the center of the window
+            let size = self.render_window.size();
+            self.render_window
+                .set_mouse_position(Vector2i::new(size.x as i32 / 2, size.y as i32 / 2));
+        }
+    }
+
+    /// Simulates the next tick of the demo played. In attract mode, a key, mouse button or
+    /// gamepad button pressed starts the game.
+    fn update_playback(&mut self) {
+        let center_x = self.render_window.size().x as i32 / 2;
+        let playback = self.playback.as_mut().unwrap();
+        if playback.attract && self.event_handler.get_pressed_binding().is_some() {
+            self.playback = None;
+            self.game_mode = self.new_game_mode(self.map.clone());
+            return;
+        }
+        if playback.tick >= playback.demos[playback.current].0.get_ticks().len() {
+            if !playback.attract && playback.current + 1 == playback.demos.len() {
+                self.render_window.close();
+                return;
+            }
+            playback.current = (playback.current + 1) % playback.demos.len();
+            playback.tick = 0;
+            let map = playback.demos[playback.current].1.clone();
+            self.game_mode = self.new_game_mode(map);
+            return;
+        }
+        let tick = &playback.demos[playback.current].0.get_ticks()[playback.tick];
+        playback.event_handler.replay_tick(tick, center_x);
+        playback.tick += 1;
+        self.game_mode
+            .update(&mut playback.event_handler, self.timestep.get_tick());
     }
 
     pub fn draw(&mut self) {
         self.render_window.clear(self.clear_color);
-        self.game_mode.draw(&mut self.render_window);
+        self.game_mode
+            .draw(&mut self.render_window, self.timestep.get_alpha());
+        if let Some(fps_handler) = self.fps_handler.as_mut() {
+            fps_handler.update();
+        }
         if let Some(_) = self.fps_handler {
             self.fps_handler
                 .as_mut()
@@ -1,126 +1,573 @@
 //! Module for configuration of selected game mode
 
 use rsfml::{
-    graphics::{Color, RectangleShape, RenderTarget, RenderWindow, Shape, Transformable},
+    graphics::{Color, Font, RectangleShape, RenderTarget, RenderWindow, Shape},
     system::{Vector2f, Vector2i, Vector2u},
-    window::Key,
 };
 
 use crate::{
-    event_handler::*, hud::HUD, map::Map, mini_map::*, raycasting_engine::RaycastEngine,
-    texture_loader::TextureLoader, weapon::Weapon,
+    actor::{Actor, ACTOR_RADIUS},
+    collision,
+    demo::map_checksum,
+    event_handler::*,
+    framebuffer::FrameBuffer,
+    hitscan::{cast_ray, RayHit},
+    hud::HUD,
+    input::{Action, ACTIONS},
+    item::Item,
+    map::Map,
+    mini_map::*,
+    player_state::{KeyColor, PlayerState, CHAINGUN, KNIFE, MACHINE_GUN, PISTOL},
+    random::Random,
+    raycasting_engine::RaycastEngine,
+    save::{SaveError, SaveGame, StateReader, StateWriter},
+    sprite::Sprite,
+    texture_loader::TextureLoader,
+    weapon::Weapon,
 };
 
+/// Seconds the screen stays tinted red after the player got hurt.
+const DAMAGE_FLASH_TIME: f32 = 0.3;
+
+/// Seconds the screen stays tinted gold after picking up an item.
+const PICKUP_FLASH_TIME: f32 = 0.2;
+
+/// Seed of the generator driving the enemies.
+const RANDOM_SEED: u32 = 0x5EED;
+
+/// Points scored for killing a guard.
+const GUARD_POINTS: i32 = 100;
+
 pub struct GameMode<'s> {
     r_engine: RaycastEngine,
     texture_loader: &'s TextureLoader,
-    window_size: Vector2u,
     mini_map: MiniMap,
     hud: HUD<'s>,
     weapon: Weapon<'s>,
-    sky: RectangleShape<'s>,
-    ground: RectangleShape<'s>,
+    decorations: Vec<Sprite>,
+    actors: Vec<Actor>,
+    // Items still lying on the ground, with their sprites
+    items: Vec<(Item, Sprite)>,
+    random: Random,
+    flash: RectangleShape<'s>,
+    // Color at the start of the flash, fading out over `flash_duration`
+    flash_color: Color,
+    flash_duration: f32,
+    flash_time: f32,
+    player: PlayerState,
+    // Index in [ACTIONS] of the action waiting for a new binding
+    rebinding: Option<usize>,
+    // Checksum of the level as it started, saves only load on the same level
+    level_checksum: u32,
 }
 
 impl<'s> GameMode<'s> {
     pub fn new(
         window_size: Vector2u,
         texture_loader: &'s TextureLoader,
+        font: &'s Font,
+        map: Map,
         no_ground: bool,
     ) -> GameMode<'s> {
-        let map = GameMode::get_map();
-        let mut sky = RectangleShape::with_size(Vector2f::new(
-            window_size.x as f32,
-            window_size.y as f32 / 2. - 40.,
-        ));
-        sky.set_fill_color(Color::rgb(63, 48, 21));
-        let mut ground = RectangleShape::with_size(Vector2f::new(
+        let decorations = GameMode::create_decorations(&map, texture_loader);
+        let actors = map
+            .get_actor_spawns()
+            .iter()
+            .map(|spawn| Actor::new(spawn, texture_loader))
+            .collect();
+        let items = GameMode::create_items(&map, texture_loader);
+        let flash = RectangleShape::with_size(Vector2f::new(
             window_size.x as f32,
-            window_size.y as f32 / 2. - 40.,
+            window_size.y as f32 - 80.,
         ));
-        ground.set_fill_color(Color::rgb(109, 108, 112));
-        ground.set_position(Vector2f::new(0., window_size.y as f32 / 2. - 40.));
+        let player = PlayerState::new(map.get_floor());
+        let level_checksum = map_checksum(&map);
         GameMode {
-            window_size,
-            mini_map: MiniMap::new(map.clone(), &window_size),
+            mini_map: MiniMap::new(&map, &window_size),
             r_engine: RaycastEngine::new(
                 map,
-                &Vector2f::new(window_size.x as f32, window_size.y as f32),
+                // The status bar takes the bottom of the window
+                &Vector2f::new(window_size.x as f32, window_size.y as f32 - 80.),
                 no_ground,
             ),
             texture_loader,
             hud: HUD::new(
                 &Vector2f::new(window_size.x as f32, window_size.y as f32),
                 texture_loader,
+                font,
             ),
             weapon: Weapon::new(
                 &Vector2f::new(window_size.x as f32, window_size.y as f32),
                 texture_loader,
             ),
-            sky,
-            ground,
-        }
-    }
-
-    pub fn get_map() -> Map {
-        let map_i32: Vec<i32> = vec![
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 2, 2, 2, 2, 2, 0, 0, 0, 0, 3, 0, 3, 0, 3,
-            0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1,
-            0, 0, 0, 0, 0, 2, 0, 0, 0, 2, 0, 0, 0, 0, 3, 0, 0, 0, 3, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0,
-            2, 0, 0, 0, 2, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 2, 2, 0, 2, 2,
-            0, 0, 0, 0, 3, 0, 3, 0, 3, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 1, 1, 4, 4, 4, 4, 4, 4, 4, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 1, 1, 4, 0, 4, 0, 0, 0, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 4, 0,
-            0, 0, 0, 5, 0, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 4, 0, 4, 0, 0, 0, 0,
-            4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 4, 0, 4, 4, 4, 4, 4, 4, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 1, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            0, 0, 0, 0, 0, 1, 1, 4, 4, 4, 4, 4, 4, 4, 4, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
-            1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
-        ];
-        Map::new(map_i32, &Vector2f::new(24., 24.))
-    }
-
-    pub fn update(&mut self, event_handler: &EventHandler) {
-        let mut rotation: f32 = 0.;
-        if event_handler.is_key_pressed(Key::Left) {
-            rotation = -5.25;
-        }
-        if event_handler.is_key_pressed(Key::Right) {
-            rotation = 5.25;
-        }
-        if let Some(_) = event_handler.has_key_pressed_event(Key::M) {
+            decorations,
+            actors,
+            items,
+            random: Random::new(RANDOM_SEED),
+            flash,
+            flash_color: Color::TRANSPARENT,
+            flash_duration: 1.,
+            flash_time: 0.,
+            player,
+            rebinding: None,
+            level_checksum,
+        }
+    }
+
+    /// Creates the sprites of the map decorations. Decorations without a `sprite_<n>` texture
+    /// are left out.
+    fn create_decorations(map: &Map, texture_loader: &TextureLoader) -> Vec<Sprite> {
+        map.get_decorations()
+            .iter()
+            .filter_map(|decoration| {
+                let texture_id = texture_loader.get_id(&format!("sprite_{}", decoration.sprite))?;
+                let position = Vector2f::new(
+                    decoration.position.x as f32 + 0.5,
+                    decoration.position.y as f32 + 0.5,
+                );
+                Some(Sprite::new(position, texture_id))
+            })
+            .collect()
+    }
+
+    fn create_items(map: &Map, texture_loader: &TextureLoader) -> Vec<(Item, Sprite)> {
+        map.get_items()
+            .iter()
+            .map(|item| {
+                let texture_id =
+                    texture_loader.require_id(&format!("sprite_{}", item.kind.get_sprite()));
+                let position =
+                    Vector2f::new(item.position.x as f32 + 0.5, item.position.y as f32 + 0.5);
+                (*item, Sprite::new(position, texture_id))
+            })
+            .collect()
+    }
+
+    /// Returns the sprites of a level as it starts: decorations, items and the actors standing
+    /// on their spawns.
+    ///
+    /// # Arguments
+    /// `map` - The level
+    ///
+    /// `texture_loader` - The loader holding the sprite textures
+    pub fn level_sprites(map: &Map, texture_loader: &TextureLoader) -> Vec<Sprite> {
+        let mut sprites = GameMode::create_decorations(map, texture_loader);
+        sprites.extend(
+            GameMode::create_items(map, texture_loader)
+                .iter()
+                .map(|(_, sprite)| *sprite),
+        );
+        sprites.extend(
+            map.get_actor_spawns()
+                .iter()
+                .map(|spawn| Actor::new(spawn, texture_loader).get_sprite(1.)),
+        );
+        sprites
+    }
+
+    /// Simulates one tick of the game.
+    ///
+    /// # Arguments
+    /// `event_handler` - The event handler to read input from
+    ///
+    /// `delta` - Seconds simulated by the tick
+    pub fn update(&mut self, event_handler: &mut EventHandler, delta: f32) {
+        // The game waits while the controls are rebound
+        if let Some(index) = self.rebinding {
+            self.update_rebinding(event_handler, index);
+            return;
+        }
+        if event_handler.has_action_pressed_event(Action::Rebind) {
+            self.rebinding = Some(0);
+            self.show_rebinding_prompt(event_handler, 0);
+            return;
+        }
+        if event_handler.has_gamepad_connected_event() {
+            self.hud.show_message("Gamepad connected");
+        } else if event_handler.has_gamepad_disconnected_event() {
+            self.hud.show_message("Gamepad disconnected");
+        }
+        if event_handler.has_action_pressed_event(Action::ToggleMap) {
             self.mini_map.toggle_active();
         }
-        self.r_engine.update(event_handler);
+        let occupied: Vec<Vector2i> = self
+            .actors
+            .iter()
+            .filter(|actor| actor.is_alive())
+            .flat_map(|actor| collision::covered_tiles(&actor.get_position(), ACTOR_RADIUS))
+            .collect();
+        self.r_engine.update(event_handler, delta, &occupied);
+        if event_handler.has_action_pressed_event(Action::Use) {
+            let player = &self.player;
+            if let Some(key) = self.r_engine.use_tile(&occupied, |key| player.has_key(key)) {
+                self.hud.show_message(match key {
+                    KeyColor::Gold => "You need the gold key",
+                    KeyColor::Silver => "You need the silver key",
+                });
+            }
+        }
+        self.update_actors(delta);
         if self.mini_map.is_active() {
-            self.mini_map
-                .update(self.r_engine.get_player_pos(), rotation);
+            self.mini_map.update(
+                self.r_engine.get_player_pos(),
+                self.r_engine.get_player_dir(),
+            );
+        }
+        // Once the last life is lost the player can only look around
+        if !self.player.is_dead() {
+            self.pick_up_items();
+            self.weapon.update(event_handler, &self.player, delta);
+            if self.weapon.has_fired() {
+                self.fire_weapon();
+            }
+        }
+        self.flash_time = (self.flash_time - delta).max(0.);
+        self.hud.update(&self.player, delta);
+    }
+
+    pub fn get_player_pos(&self) -> Vector2f {
+        self.r_engine.get_player_pos()
+    }
+
+    pub fn get_player_dir(&self) -> Vector2f {
+        self.r_engine.get_player_dir()
+    }
+
+    pub fn get_player(&self) -> &PlayerState {
+        &self.player
+    }
+
+    pub fn get_current_weapon(&self) -> usize {
+        self.weapon.get_current_weapon()
+    }
+
+    pub fn get_actors(&self) -> &[Actor] {
+        &self.actors
+    }
+
+    pub fn is_mini_map_active(&self) -> bool {
+        self.mini_map.is_active()
+    }
+
+    /// Shows a message above the status bar for a few seconds.
+    pub fn show_message(&mut self, message: &str) {
+        self.hud.show_message(message);
+    }
+
+    /// Saves the game, with a thumbnail of the view of the last tick.
+    ///
+    /// # Arguments
+    /// `map_name` - Where the level was loaded from, see [MapSource](crate::MapSource)
+    pub fn save_game(&mut self, map_name: &str) -> SaveGame {
+        let mut writer = StateWriter::new();
+        self.save_state(&mut writer);
+        let level_checksum = self.level_checksum;
+        SaveGame::new(
+            map_name,
+            level_checksum,
+            self.render_view(),
+            writer.into_bytes(),
+        )
+    }
+
+    /// Loads a save of the same level. The game is left half loaded on errors, so load into a
+    /// new game and only keep it on success.
+    pub fn load_game(&mut self, save: &SaveGame) -> Result<(), SaveError> {
+        if save.get_map_checksum() != self.level_checksum {
+            return Err(SaveError::OtherLevel(String::from(save.get_map_name())));
+        }
+        let mut reader = StateReader::new(save.get_state());
+        self.load_state(&mut reader)?;
+        if !reader.is_at_end() {
+            return Err(SaveError::Corrupt(String::from(
+                "the state is longer than the level",
+            )));
         }
-        self.hud.update();
-        self.weapon.update(event_handler);
+        Ok(())
     }
 
-    pub fn draw(&mut self, render_window: &mut RenderWindow) {
-        render_window.draw(&self.sky);
-        render_window.draw(&self.ground);
-        self.r_engine.draw(render_window, self.texture_loader);
+    /// Writes the state of the level in play to a save: the player, the map, the actors, the
+    /// items left and the weapon.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        self.random.save_state(writer);
+        let color = self.flash_color;
+        for channel in [color.r, color.g, color.b, color.a].iter() {
+            writer.write_u8(*channel);
+        }
+        writer.write_f32(self.flash_duration);
+        writer.write_f32(self.flash_time);
+        self.player.save_state(writer);
+        writer.write_bool(self.mini_map.is_active());
+        // Items keep their order when picked up, each one of the map is left or not
+        let map_items = self.r_engine.get_map().get_items();
+        writer.write_u32(map_items.len() as u32);
+        let mut left = self.items.iter().peekable();
+        for item in map_items.iter() {
+            let is_left = left.peek().map(|(left_item, _)| left_item) == Some(item);
+            if is_left {
+                left.next();
+            }
+            writer.write_bool(is_left);
+        }
+        self.hud.save_state(writer);
+        self.weapon.save_state(writer);
+        writer.write_u32(self.actors.len() as u32);
+        for actor in self.actors.iter() {
+            actor.save_state(writer);
+        }
+        self.r_engine.save_state(writer);
+    }
+
+    /// Reads back the state written by [GameMode::save_state] for the same level.
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.random.load_state(reader)?;
+        self.flash_color = Color::rgba(
+            reader.read_u8()?,
+            reader.read_u8()?,
+            reader.read_u8()?,
+            reader.read_u8()?,
+        );
+        self.flash_duration = reader.read_f32()?;
+        self.flash_time = reader.read_f32()?;
+        if self.flash_duration <= 0. {
+            return Err(SaveError::Corrupt(String::from("flash without duration")));
+        }
+        self.player.load_state(reader)?;
+        self.mini_map.set_active(reader.read_bool()?);
+        let mut items = GameMode::create_items(self.r_engine.get_map(), self.texture_loader);
+        reader.read_count(items.len(), "items")?;
+        let mut left = Vec::with_capacity(items.len());
+        for _ in 0..items.len() {
+            left.push(reader.read_bool()?);
+        }
+        let mut is_left = left.iter();
+        items.retain(|_| *is_left.next().unwrap());
+        self.items = items;
+        self.hud.load_state(reader)?;
+        self.weapon.load_state(reader)?;
+        reader.read_count(self.actors.len(), "actors")?;
+        for actor in self.actors.iter_mut() {
+            actor.load_state(reader)?;
+        }
+        self.r_engine.load_state(reader)?;
+        self.rebinding = None;
+        let (position, direction) = (self.get_player_pos(), self.get_player_dir());
+        self.mini_map.update(position, direction);
+        self.hud.update(&self.player, 0.);
+        Ok(())
+    }
+
+    /// Renders the view of the last tick and returns it, e.g. for the thumbnail of a save.
+    pub fn render_view(&mut self) -> &FrameBuffer {
+        self.render(1.)
+    }
+
+    /// Whether the game waits for the player to rebind the controls.
+    pub fn is_rebinding(&self) -> bool {
+        self.rebinding.is_some()
+    }
+
+    /// Binds the next key, mouse button or wheel move pressed to the action being rebound, or
+    /// keeps its bindings when a binding of [Action::Quit] is pressed, then asks for the next
+    /// action. The bindings are saved
+    /// once every action went by.
+    ///
+    /// # Arguments
+    /// `event_handler` - The event handler holding the bindings
+    ///
+    /// `index` - Index in [ACTIONS] of the action being rebound
+    fn update_rebinding(&mut self, event_handler: &mut EventHandler, index: usize) {
+        if !event_handler.has_action_pressed_event(Action::Quit) {
+            let binding = match event_handler.get_pressed_binding() {
+                Some(binding) => binding,
+                None => return,
+            };
+            if binding.get_name().is_none() {
+                self.hud.show_message("This key cannot be bound");
+                return;
+            }
+            event_handler
+                .get_input_map_mut()
+                .rebind(ACTIONS[index], binding);
+        }
+        if index + 1 < ACTIONS.len() {
+            self.rebinding = Some(index + 1);
+            self.show_rebinding_prompt(event_handler, index + 1);
+            return;
+        }
+        self.rebinding = None;
+        match event_handler.get_input_map().save() {
+            Ok(()) => self.hud.show_message("Controls saved"),
+            Err(err) => {
+                eprintln!("{}", err);
+                self.hud.show_message("Cannot save the controls");
+            }
+        }
+    }
+
+    fn show_rebinding_prompt(&mut self, event_handler: &EventHandler, index: usize) {
+        let binding_names = |action: Action| -> Vec<String> {
+            event_handler
+                .get_input_map()
+                .get_bindings(action)
+                .iter()
+                .filter_map(|binding| binding.get_name())
+                .collect()
+        };
+        let action = ACTIONS[index];
+        let bindings = binding_names(action);
+        let keep = binding_names(Action::Quit);
+        self.hud.show_message(&format!(
+            "Press a key for {} ({} keeps {})",
+            action.get_description(),
+            keep.first().map_or("Quit", |name| name.as_str()),
+            if bindings.is_empty() {
+                String::from("none")
+            } else {
+                bindings.join(", ")
+            }
+        ));
+    }
+
+    /// Picks up the items on the tile of the player. Items the player has no use for stay on
+    /// the ground.
+    fn pick_up_items(&mut self) {
+        let position = self.r_engine.get_player_pos();
+        let tile = Vector2i::new(position.x as i32, position.y as i32);
+        let mut index = 0;
+        while index < self.items.len() {
+            let kind = self.items[index].0.kind;
+            let had_ammo = self.player.get_ammo() > 0;
+            if self.items[index].0.position != tile || !kind.pick_up(&mut self.player) {
+                index += 1;
+                continue;
+            }
+            self.items.remove(index);
+            self.hud.show_message(kind.get_message());
+            self.start_flash(Color::rgba(255, 232, 120, 96), PICKUP_FLASH_TIME);
+            if let Some(slot) = kind.get_weapon() {
+                self.weapon.select(slot);
+            } else if !had_ammo
+                && self.player.get_ammo() > 0
+                && self.weapon.get_current_weapon() == KNIFE
+            {
+                // Back to the best gun once there is ammunition again
+                let gun = [CHAINGUN, MACHINE_GUN, PISTOL]
+                    .iter()
+                    .find(|&&slot| self.player.has_weapon(slot));
+                if let Some(&gun) = gun {
+                    self.weapon.select(gun);
+                }
+            }
+        }
+    }
+
+    /// Tints the view with a color fading out.
+    ///
+    /// # Arguments
+    /// `color` - The tint at the start of the flash
+    ///
+    /// `duration` - Seconds the flash lasts
+    fn start_flash(&mut self, color: Color, duration: f32) {
+        self.flash_color = color;
+        self.flash_duration = duration;
+        self.flash_time = duration;
+    }
+
+    /// Shoots along the view, off by up to the spread of the weapon, and hurts the first
+    /// actor hit.
+    fn fire_weapon(&mut self) {
+        // The last bullet still leaves the gun it was fired from
+        let stats = self.weapon.get_stats();
+        if self.weapon.get_current_weapon() != KNIFE {
+            self.player.use_ammo();
+            if self.player.get_ammo() == 0 {
+                self.weapon.select(KNIFE);
+            }
+        }
+        let angle = (self.random.next_f32() * 2. - 1.) * stats.spread;
+        let aim = self.r_engine.get_player_dir();
+        let direction = Vector2f::new(
+            aim.x * angle.cos() - aim.y * angle.sin(),
+            aim.x * angle.sin() + aim.y * angle.cos(),
+        );
+        let hit = cast_ray(
+            self.r_engine.get_map(),
+            &self.actors,
+            &self.r_engine.get_player_pos(),
+            &direction,
+            stats.range,
+        );
+        if let RayHit::Actor {
+            index, distance, ..
+        } = hit
+        {
+            let damage = stats.damage_at(distance, &mut self.random);
+            let actor = &mut self.actors[index];
+            actor.take_damage(damage);
+            if !actor.is_alive() {
+                self.player.add_score(GUARD_POINTS);
+            }
+        }
+    }
+
+    fn update_actors(&mut self, delta: f32) {
+        let player_position = self.r_engine.get_player_pos();
+        let mut damage = 0;
+        for actor in self.actors.iter_mut() {
+            damage += actor.update(
+                delta,
+                self.r_engine.get_map_mut(),
+                &player_position,
+                &mut self.random,
+            );
+        }
+        if damage > 0 && !self.player.is_dead() {
+            self.start_flash(Color::rgba(255, 0, 0, 160), DAMAGE_FLASH_TIME);
+            self.player.take_damage(damage);
+            if self.player.is_dead() && self.player.lose_life() {
+                self.respawn();
+            }
+        }
+    }
+
+    /// Puts the player back on the spawn of the level for the next life.
+    fn respawn(&mut self) {
+        self.r_engine.respawn();
+        self.weapon.select(PISTOL);
+    }
+
+    /// Draws the game in between the last two ticks.
+    ///
+    /// # Arguments
+    /// `render_window` - The window to draw onto
+    ///
+    /// `alpha` - How far the frame is from the previous tick (0) to the last one (1)
+    pub fn draw(&mut self, render_window: &mut RenderWindow, alpha: f32) {
+        self.render(alpha);
+        self.r_engine.draw(render_window);
         if self.mini_map.is_active() {
-            self.mini_map.draw(render_window, self.texture_loader);
+            self.mini_map
+                .draw(render_window, self.texture_loader, self.r_engine.get_map());
         }
         self.hud.draw(render_window);
         self.weapon.draw(render_window);
+        if self.flash_time > 0. {
+            let flash_alpha = self.flash_color.a as f32 * self.flash_time / self.flash_duration;
+            let mut color = self.flash_color;
+            color.a = flash_alpha as u8;
+            self.flash.set_fill_color(color);
+            render_window.draw(&self.flash);
+        }
         render_window.set_mouse_cursor_visible(false);
-        render_window.set_mouse_position(Vector2i::new(
-            (self.window_size.x / 2) as i32,
-            (self.window_size.y / 2) as i32,
-        ));
+    }
+
+    /// Renders the 3D view with its sprites in between the last two ticks.
+    fn render(&mut self, alpha: f32) -> &FrameBuffer {
+        let mut sprites = self.decorations.clone();
+        sprites.extend(self.items.iter().map(|(_, sprite)| *sprite));
+        sprites.extend(self.actors.iter().map(|actor| actor.get_sprite(alpha)));
+        self.r_engine.interpolate(alpha);
+        self.r_engine.render(&sprites, self.texture_loader)
     }
 }
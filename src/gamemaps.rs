@@ -0,0 +1,860 @@
+//! Module for importing original Wolfenstein 3D levels from MAPHEAD and GAMEMAPS files
+
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use rsfml::system::{Vector2f, Vector2i};
+
+use crate::{
+    item::{Item, ItemKind},
+    map::{ActorSpawn, Decoration, Map, Spawn},
+    player_state::KeyColor,
+    vswap::{FIRST_SPRITE, FIRST_WALL_BLOCK},
+};
+
+/// Number of level slots in a MAPHEAD file.
+pub const MAX_LEVELS: usize = 100;
+
+const NEAR_TAG: u8 = 0xA7;
+const FAR_TAG: u8 = 0xA8;
+const GAMEMAPS_SIGNATURE: &[u8] = b"TED5v1.0";
+const LEVEL_HEADER_SIZE: usize = 38;
+/// Plane 0 codes from this value onwards are floor area numbers, not walls.
+const FIRST_AREA_TILE: u16 = 106;
+/// Plane 0 codes of the sliding doors, in vertical and horizontal pairs: plain, gold, silver,
+/// two unused locks and elevator doors.
+const DOOR_TILES: std::ops::RangeInclusive<u16> = 90..=101;
+/// Plane 1 code marking the wall below it as a secret push-wall.
+const PUSH_WALL_OBJECT: u16 = 98;
+/// Plane 1 codes of the static objects (decorations and items), drawn with sprite pages
+/// 2 onwards in the same order.
+const STATIC_OBJECTS: std::ops::RangeInclusive<u16> = 23..=70;
+const FIRST_STATIC_SPRITE_PAGE: i32 = 2;
+/// First plane 1 code of the guards of each skill level: 4 standing guards facing east, north,
+/// west and south, then 4 patrolling guards in the same order.
+const GUARD_OBJECTS: [u16; 3] = [108, 144, 180];
+
+/// Error raised while reading or decoding Wolfenstein 3D map data.
+#[derive(Debug, Clone, PartialEq)]
+pub enum GameMapsError {
+    Io(String),
+    Corrupt(String),
+    MissingLevel(usize),
+    UnmappedWall { code: u16, position: Vector2i },
+    MissingSpawn,
+}
+
+/// Maps plane 0 wall codes to block IDs (texture IDs) of a [Map].
+#[derive(Clone, Debug, Default)]
+pub struct WallTable {
+    textures: HashMap<u16, i32>,
+}
+
+/// A non-empty plane 1 entry other than the player start (enemies, items, decorations...).
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct MapObject {
+    pub code: u16,
+    pub position: Vector2i,
+}
+
+/// A decoded Wolfenstein 3D level.
+pub struct WolfLevel {
+    pub name: String,
+    pub map: Map,
+    pub spawn: Spawn,
+    pub objects: Vec<MapObject>,
+}
+
+#[derive(Clone, Debug)]
+struct LevelHeader {
+    plane_starts: [u32; 3],
+    plane_lengths: [u16; 3],
+    width: u16,
+    height: u16,
+    name: String,
+}
+
+/// The level directory (MAPHEAD) and level data (GAMEMAPS) of a Wolfenstein 3D install.
+pub struct GameMaps {
+    rlew_tag: u16,
+    headers: Vec<Option<LevelHeader>>,
+    data: Vec<u8>,
+}
+
+impl fmt::Display for GameMapsError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GameMapsError::Io(message) => write!(f, "ERROR: {}", message),
+            GameMapsError::Corrupt(message) => write!(f, "ERROR: Corrupt map data ({})", message),
+            GameMapsError::MissingLevel(index) => {
+                write!(f, "ERROR: Level {} does not exist in GAMEMAPS", index)
+            }
+            GameMapsError::UnmappedWall { code, position } => write!(
+                f,
+                "ERROR: Wall code {} at ({}, {}) has no texture in the wall table",
+                code, position.x, position.y
+            ),
+            GameMapsError::MissingSpawn => write!(f, "ERROR: Level has no player start"),
+        }
+    }
+}
+
+impl std::error::Error for GameMapsError {}
+
+impl WallTable {
+    /// Creates an empty table, every wall code has to be added with [WallTable::set].
+    pub fn new() -> WallTable {
+        WallTable {
+            textures: HashMap::new(),
+        }
+    }
+
+    /// Spreads wall codes (and door codes 90 to 101) over the 5 built-in wall textures.
+    pub fn with_builtin_textures() -> WallTable {
+        let mut table = WallTable::new();
+        for code in (1..64).chain(90..102) {
+            table.set(code, (code as i32 - 1) % 5 + 1);
+        }
+        table
+    }
+
+    /// Uses the wall pages of a VSWAP file loaded with `TextureLoader::load_vswap`, door codes
+    /// keep the built-in textures.
+    pub fn with_vswap_textures() -> WallTable {
+        let mut table = WallTable::with_builtin_textures();
+        for code in 1..64 {
+            table.set(code, FIRST_WALL_BLOCK + code as i32 - 1);
+        }
+        table
+    }
+
+    pub fn set(&mut self, code: u16, texture_id: i32) {
+        self.textures.insert(code, texture_id);
+    }
+
+    pub fn get(&self, code: u16) -> Option<i32> {
+        self.textures.get(&code).copied()
+    }
+}
+
+impl GameMaps {
+    /// Reads a MAPHEAD and a GAMEMAPS file.
+    ///
+    /// # Arguments
+    /// `maphead_path` - The path of the MAPHEAD file (e.g. `MAPHEAD.WL6`)
+    ///
+    /// `gamemaps_path` - The path of the matching GAMEMAPS file (e.g. `GAMEMAPS.WL6`)
+    pub fn open<P: AsRef<Path>, Q: AsRef<Path>>(
+        maphead_path: P,
+        gamemaps_path: Q,
+    ) -> Result<GameMaps, GameMapsError> {
+        let read = |path: &Path| {
+            fs::read(path).map_err(|err| {
+                GameMapsError::Io(format!("Cannot read {} ({})", path.display(), err))
+            })
+        };
+        let maphead = read(maphead_path.as_ref())?;
+        let gamemaps = read(gamemaps_path.as_ref())?;
+        GameMaps::from_bytes(&maphead, gamemaps)
+    }
+
+    /// Looks for a MAPHEAD/GAMEMAPS pair in a Wolfenstein 3D data directory, trying the
+    /// extensions of the registered, shareware and Spear of Destiny releases.
+    pub fn open_dir<P: AsRef<Path>>(data_dir: P) -> Result<GameMaps, GameMapsError> {
+        let data_dir = data_dir.as_ref();
+        for extension in ["WL6", "WL1", "SOD", "SDM"].iter() {
+            for &(maphead, gamemaps) in [("MAPHEAD", "GAMEMAPS"), ("maphead", "gamemaps")].iter() {
+                let extension = if maphead == "maphead" {
+                    extension.to_lowercase()
+                } else {
+                    extension.to_string()
+                };
+                let maphead = data_dir.join(format!("{}.{}", maphead, extension));
+                let gamemaps = data_dir.join(format!("{}.{}", gamemaps, extension));
+                if maphead.is_file() && gamemaps.is_file() {
+                    return GameMaps::open(maphead, gamemaps);
+                }
+            }
+        }
+        Err(GameMapsError::Io(format!(
+            "No MAPHEAD/GAMEMAPS files found in {}",
+            data_dir.display()
+        )))
+    }
+
+    pub fn from_bytes(maphead: &[u8], gamemaps: Vec<u8>) -> Result<GameMaps, GameMapsError> {
+        let rlew_tag = read_u16(maphead, 0)
+            .ok_or_else(|| GameMapsError::Corrupt(String::from("MAPHEAD is too short")))?;
+        if !gamemaps.starts_with(GAMEMAPS_SIGNATURE) {
+            return Err(GameMapsError::Corrupt(String::from(
+                "GAMEMAPS is missing its TED5 signature",
+            )));
+        }
+
+        let mut headers = Vec::new();
+        for level in 0..MAX_LEVELS {
+            let offset = match read_u32(maphead, 2 + level * 4) {
+                Some(offset) => offset,
+                None => break,
+            };
+            if offset == 0 || offset == 0xFFFF_FFFF {
+                headers.push(None);
+            } else {
+                headers.push(Some(LevelHeader::parse(&gamemaps, offset as usize)?));
+            }
+        }
+
+        Ok(GameMaps {
+            rlew_tag,
+            headers,
+            data: gamemaps,
+        })
+    }
+
+    pub fn level_count(&self) -> usize {
+        self.headers.len()
+    }
+
+    pub fn level_name(&self, index: usize) -> Option<&str> {
+        match self.headers.get(index) {
+            Some(Some(header)) => Some(header.name.as_str()),
+            _ => None,
+        }
+    }
+
+    /// Decodes one plane of a level into `width * height` tile codes, stored row by row.
+    pub fn read_plane(&self, index: usize, plane: usize) -> Result<Vec<u16>, GameMapsError> {
+        let header = self.header(index)?;
+        if plane >= header.plane_starts.len() {
+            return Err(GameMapsError::Corrupt(format!(
+                "plane {} does not exist",
+                plane
+            )));
+        }
+        let start = header.plane_starts[plane] as usize;
+        let end = start + header.plane_lengths[plane] as usize;
+        let compressed = self.data.get(start..end).ok_or_else(|| {
+            GameMapsError::Corrupt(format!(
+                "plane {} of level {} is out of bounds",
+                plane, index
+            ))
+        })?;
+
+        let carmack_length = read_u16(compressed, 0)
+            .ok_or_else(|| GameMapsError::Corrupt(String::from("empty plane")))?;
+        let expanded = carmack_expand(&compressed[2..], carmack_length as usize)?;
+        let rlew_length = *expanded
+            .first()
+            .ok_or_else(|| GameMapsError::Corrupt(String::from("empty RLEW data")))?;
+        let tiles = rlew_expand(&expanded[1..], rlew_length as usize, self.rlew_tag)?;
+
+        let tile_count = header.width as usize * header.height as usize;
+        if tiles.len() != tile_count {
+            return Err(GameMapsError::Corrupt(format!(
+                "plane {} has {} tiles, expected {}",
+                plane,
+                tiles.len(),
+                tile_count
+            )));
+        }
+        Ok(tiles)
+    }
+
+    /// Builds a playable level from plane 0 (walls) and plane 1 (objects).
+    ///
+    /// # Arguments
+    /// `index` - The level slot in MAPHEAD (0 is E1M1)
+    ///
+    /// `walls` - Converts wall codes to block IDs
+    pub fn load_level(&self, index: usize, walls: &WallTable) -> Result<WolfLevel, GameMapsError> {
+        let header = self.header(index)?;
+        let width = header.width as usize;
+        let wall_plane = self.read_plane(index, 0)?;
+        let object_plane = self.read_plane(index, 1)?;
+
+        let mut blocks = Vec::with_capacity(wall_plane.len());
+        let mut doors = Vec::new();
+        for (tile_idx, &code) in wall_plane.iter().enumerate() {
+            if DOOR_TILES.contains(&code) {
+                doors.push((tile_position(tile_idx, width), door_lock(code)));
+            }
+            let block = if code == 0 || code >= FIRST_AREA_TILE {
+                0
+            } else {
+                walls.get(code).ok_or(GameMapsError::UnmappedWall {
+                    code,
+                    position: tile_position(tile_idx, width),
+                })?
+            };
+            blocks.push(block);
+        }
+
+        let mut spawn = None;
+        let mut objects = Vec::new();
+        let mut push_walls = Vec::new();
+        for (tile_idx, &code) in object_plane.iter().enumerate() {
+            let position = tile_position(tile_idx, width);
+            let direction = match code {
+                0 => continue,
+                PUSH_WALL_OBJECT => {
+                    push_walls.push(position);
+                    continue;
+                }
+                19 => Vector2f::new(0., -1.),
+                20 => Vector2f::new(1., 0.),
+                21 => Vector2f::new(0., 1.),
+                22 => Vector2f::new(-1., 0.),
+                _ => {
+                    objects.push(MapObject { code, position });
+                    continue;
+                }
+            };
+            spawn = Some(Spawn::new(
+                Vector2f::new(position.x as f32 + 0.5, position.y as f32 + 0.5),
+                direction,
+            ));
+        }
+        let spawn = spawn.ok_or(GameMapsError::MissingSpawn)?;
+
+        let mut map = Map::new(
+            blocks,
+            &Vector2f::new(header.width as f32, header.height as f32),
+        );
+        map.set_spawn(spawn);
+        // Episodes have ten floors each
+        map.set_floor((index % 10) as i32 + 1);
+        for (position, lock) in doors {
+            map.add_door(position, lock).map_err(|message| {
+                GameMapsError::Corrupt(format!("{} at ({}, {})", message, position.x, position.y))
+            })?;
+        }
+        for object in objects.iter() {
+            if let Some(kind) = ItemKind::from_object(object.code) {
+                map.add_item(Item {
+                    kind,
+                    position: object.position,
+                });
+            } else if STATIC_OBJECTS.contains(&object.code) {
+                map.add_decoration(Decoration {
+                    sprite: FIRST_SPRITE
+                        + FIRST_STATIC_SPRITE_PAGE
+                        + (object.code - STATIC_OBJECTS.start()) as i32,
+                    position: object.position,
+                });
+            }
+            if let Some(actor_spawn) = guard_spawn(object) {
+                map.add_actor_spawn(actor_spawn);
+            }
+        }
+        for position in push_walls {
+            map.add_push_wall(position).map_err(|message| {
+                GameMapsError::Corrupt(format!("{} at ({}, {})", message, position.x, position.y))
+            })?;
+        }
+        Ok(WolfLevel {
+            name: header.name.clone(),
+            map,
+            spawn,
+            objects,
+        })
+    }
+
+    fn header(&self, index: usize) -> Result<&LevelHeader, GameMapsError> {
+        match self.headers.get(index) {
+            Some(Some(header)) => Ok(header),
+            _ => Err(GameMapsError::MissingLevel(index)),
+        }
+    }
+}
+
+impl LevelHeader {
+    fn parse(data: &[u8], offset: usize) -> Result<LevelHeader, GameMapsError> {
+        let bytes = data
+            .get(offset..offset + LEVEL_HEADER_SIZE)
+            .ok_or_else(|| {
+                GameMapsError::Corrupt(format!("level header at {} is out of bounds", offset))
+            })?;
+        let name = bytes[22..38]
+            .iter()
+            .take_while(|&&c| c != 0)
+            .map(|&c| c as char)
+            .collect();
+        Ok(LevelHeader {
+            plane_starts: [
+                read_u32(bytes, 0).unwrap(),
+                read_u32(bytes, 4).unwrap(),
+                read_u32(bytes, 8).unwrap(),
+            ],
+            plane_lengths: [
+                read_u16(bytes, 12).unwrap(),
+                read_u16(bytes, 14).unwrap(),
+                read_u16(bytes, 16).unwrap(),
+            ],
+            width: read_u16(bytes, 18).unwrap(),
+            height: read_u16(bytes, 20).unwrap(),
+            name,
+        })
+    }
+}
+
+/// Expands Carmack-compressed data.
+///
+/// # Arguments
+/// `source` - The compressed bytes, without the leading length word
+///
+/// `expanded_length` - The size of the expanded data in bytes
+pub fn carmack_expand(source: &[u8], expanded_length: usize) -> Result<Vec<u16>, GameMapsError> {
+    let truncated = || GameMapsError::Corrupt(String::from("truncated Carmack data"));
+    let mut output: Vec<u16> = Vec::with_capacity(expanded_length / 2);
+    let mut remaining = expanded_length / 2;
+    let mut pos = 0;
+
+    while remaining > 0 {
+        let word = read_u16(source, pos).ok_or_else(truncated)?;
+        pos += 2;
+        let (count, tag) = ((word & 0xFF) as usize, (word >> 8) as u8);
+        if tag != NEAR_TAG && tag != FAR_TAG {
+            output.push(word);
+            remaining -= 1;
+        } else if count == 0 {
+            // A zero count escapes a literal word whose high byte happens to be a tag
+            let low = *source.get(pos).ok_or_else(truncated)?;
+            pos += 1;
+            output.push(word | low as u16);
+            remaining -= 1;
+        } else {
+            let copy_from = if tag == NEAR_TAG {
+                let offset = *source.get(pos).ok_or_else(truncated)? as usize;
+                pos += 1;
+                output.len().checked_sub(offset)
+            } else {
+                let offset = read_u16(source, pos).ok_or_else(truncated)? as usize;
+                pos += 2;
+                Some(offset)
+            };
+            let copy_from = match copy_from {
+                Some(start) if start < output.len() => start,
+                _ => {
+                    return Err(GameMapsError::Corrupt(String::from(
+                        "Carmack pointer outside of the expanded data",
+                    )))
+                }
+            };
+            if count > remaining {
+                return Err(GameMapsError::Corrupt(String::from(
+                    "Carmack run overflows the expanded length",
+                )));
+            }
+            // Runs may overlap the words they produce, so copy one word at a time
+            for i in 0..count {
+                let word = output[copy_from + i];
+                output.push(word);
+            }
+            remaining -= count;
+        }
+    }
+    Ok(output)
+}
+
+/// Expands RLEW-compressed words.
+///
+/// # Arguments
+/// `source` - The compressed words, without the leading length word
+///
+/// `expanded_length` - The size of the expanded data in bytes
+///
+/// `tag` - The RLEW marker word stored at the start of MAPHEAD
+pub fn rlew_expand(
+    source: &[u16],
+    expanded_length: usize,
+    tag: u16,
+) -> Result<Vec<u16>, GameMapsError> {
+    let truncated = || GameMapsError::Corrupt(String::from("truncated RLEW data"));
+    let expanded_words = expanded_length / 2;
+    let mut output = Vec::with_capacity(expanded_words);
+    let mut words = source.iter().copied();
+
+    while output.len() < expanded_words {
+        let word = words.next().ok_or_else(truncated)?;
+        if word == tag {
+            let count = words.next().ok_or_else(truncated)? as usize;
+            let value = words.next().ok_or_else(truncated)?;
+            if output.len() + count > expanded_words {
+                return Err(GameMapsError::Corrupt(String::from(
+                    "RLEW run overflows the expanded length",
+                )));
+            }
+            output.resize(output.len() + count, value);
+        } else {
+            output.push(word);
+        }
+    }
+    Ok(output)
+}
+
+fn door_lock(code: u16) -> Option<KeyColor> {
+    match (code - DOOR_TILES.start()) / 2 {
+        1 => Some(KeyColor::Gold),
+        2 => Some(KeyColor::Silver),
+        _ => None,
+    }
+}
+
+fn guard_spawn(object: &MapObject) -> Option<ActorSpawn> {
+    let first = GUARD_OBJECTS
+        .iter()
+        .find(|&&first| (first..first + 8).contains(&object.code))?;
+    let variant = object.code - first;
+    let direction = match variant % 4 {
+        0 => Vector2f::new(1., 0.),
+        1 => Vector2f::new(0., -1.),
+        2 => Vector2f::new(-1., 0.),
+        _ => Vector2f::new(0., 1.),
+    };
+    Some(ActorSpawn {
+        position: object.position,
+        direction,
+        patrol: variant >= 4,
+    })
+}
+
+fn tile_position(tile_idx: usize, width: usize) -> Vector2i {
+    Vector2i::new((tile_idx % width) as i32, (tile_idx / width) as i32)
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::map::DoorUse;
+
+    const TAG: u16 = 0xABCD;
+
+    /// Name, width, height, plane 0 and plane 1 of a level
+    type TestLevel<'a> = (&'a str, u16, u16, Vec<u16>, Vec<u16>);
+
+    /// RLEW-compresses words, collapsing runs of 3 or more and always escaping the tag.
+    fn rlew_compress(words: &[u16]) -> Vec<u16> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < words.len() {
+            let mut run = 1;
+            while i + run < words.len() && words[i + run] == words[i] {
+                run += 1;
+            }
+            if run >= 3 || words[i] == TAG {
+                output.extend_from_slice(&[TAG, run as u16, words[i]]);
+            } else {
+                output.extend_from_slice(&words[i..i + run]);
+            }
+            i += run;
+        }
+        output
+    }
+
+    /// Stores words in Carmack format without back references, escaping tag bytes.
+    fn carmack_store(words: &[u16]) -> Vec<u8> {
+        let mut output = Vec::new();
+        for &word in words {
+            let high = (word >> 8) as u8;
+            if high == NEAR_TAG || high == FAR_TAG {
+                output.extend_from_slice(&[0, high, word as u8]);
+            } else {
+                output.extend_from_slice(&word.to_le_bytes());
+            }
+        }
+        output
+    }
+
+    fn encode_plane(tiles: &[u16]) -> Vec<u8> {
+        let mut rlew = vec![(tiles.len() * 2) as u16];
+        rlew.extend(rlew_compress(tiles));
+        let mut plane = ((rlew.len() * 2) as u16).to_le_bytes().to_vec();
+        plane.extend(carmack_store(&rlew));
+        plane
+    }
+
+    /// Builds MAPHEAD and GAMEMAPS bytes holding the given levels at slots 0, 1...
+    fn build_files(levels: &[TestLevel]) -> (Vec<u8>, Vec<u8>) {
+        let mut maphead = TAG.to_le_bytes().to_vec();
+        let mut gamemaps = GAMEMAPS_SIGNATURE.to_vec();
+        for (name, width, height, walls, objects) in levels {
+            let planes = [
+                encode_plane(walls),
+                encode_plane(objects),
+                encode_plane(&[]),
+            ];
+            let mut starts = Vec::new();
+            for plane in planes.iter() {
+                starts.push(gamemaps.len() as u32);
+                gamemaps.extend(plane);
+            }
+            maphead.extend((gamemaps.len() as u32).to_le_bytes());
+            for start in starts.iter() {
+                gamemaps.extend(start.to_le_bytes());
+            }
+            for plane in planes.iter() {
+                gamemaps.extend((plane.len() as u16).to_le_bytes());
+            }
+            gamemaps.extend(width.to_le_bytes());
+            gamemaps.extend(height.to_le_bytes());
+            let mut name_bytes = name.as_bytes().to_vec();
+            name_bytes.resize(16, 0);
+            gamemaps.extend(name_bytes);
+        }
+        (maphead, gamemaps)
+    }
+
+    /// A 6x5 room of wall code 1 with a code 8 pillar, the player facing east and one object.
+    fn small_level() -> (Vec<u16>, Vec<u16>) {
+        #[rustfmt::skip]
+        let walls = vec![
+            1,   1,   1,   1,   1, 1,
+            1, 108, 108, 108, 108, 1,
+            1, 108,   8, 108, 108, 1,
+            1, 108, 108, 108, 108, 1,
+            1,   1,   1,   1,   1, 1,
+        ];
+        let mut objects = vec![0; 30];
+        objects[6 + 1] = 20;
+        objects[18 + 4] = 36;
+        (walls, objects)
+    }
+
+    #[test]
+    fn carmack_expands_near_and_far_pointers() {
+        let mut source = Vec::new();
+        source.extend(0x1111u16.to_le_bytes());
+        source.extend(0x2222u16.to_le_bytes());
+        // near pointer: copy 2 words starting 2 words back
+        source.extend([2, NEAR_TAG, 2]);
+        // far pointer: copy 3 words starting at word 1
+        source.extend([3, FAR_TAG, 1, 0]);
+        // escaped literal 0xA842
+        source.extend([0, FAR_TAG, 0x42]);
+
+        let expanded = carmack_expand(&source, 8 * 2).unwrap();
+        assert_eq!(
+            expanded,
+            vec![0x1111, 0x2222, 0x1111, 0x2222, 0x2222, 0x1111, 0x2222, 0xA842]
+        );
+    }
+
+    #[test]
+    fn carmack_rejects_pointers_before_the_start() {
+        let source = [1, NEAR_TAG, 4];
+        assert!(carmack_expand(&source, 2).is_err());
+    }
+
+    #[test]
+    fn rlew_round_trips() {
+        let words = vec![5, 5, 5, 5, 7, TAG, 9, 9, 0, 0, 0];
+        let compressed = rlew_compress(&words);
+        assert!(compressed.len() < words.len() + 3);
+        assert_eq!(
+            rlew_expand(&compressed, words.len() * 2, TAG).unwrap(),
+            words
+        );
+    }
+
+    #[test]
+    fn loads_a_synthetic_level() {
+        let (walls, objects) = small_level();
+        let (maphead, gamemaps) = build_files(&[("Wolf1 Map1", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+        assert_eq!(game_maps.level_name(0), Some("Wolf1 Map1"));
+
+        let mut table = WallTable::new();
+        table.set(1, 3);
+        table.set(8, 4);
+        let level = game_maps.load_level(0, &table).unwrap();
+
+        assert_eq!(*level.map.get_map_size(), Vector2i::new(6, 5));
+        assert_eq!(level.map.get_block(&Vector2i::new(0, 0)), Some(3));
+        assert_eq!(level.map.get_block(&Vector2i::new(1, 1)), Some(0));
+        assert_eq!(level.map.get_block(&Vector2i::new(2, 2)), Some(4));
+        assert_eq!(level.spawn.position, Vector2f::new(1.5, 1.5));
+        assert_eq!(level.spawn.direction, Vector2f::new(1., 0.));
+        assert_eq!(*level.map.get_spawn(), level.spawn);
+        assert_eq!(
+            level.objects,
+            vec![MapObject {
+                code: 36,
+                position: Vector2i::new(4, 3)
+            }]
+        );
+        assert_eq!(
+            level.map.get_decorations(),
+            &[Decoration {
+                sprite: FIRST_SPRITE + 15,
+                position: Vector2i::new(4, 3)
+            }]
+        );
+        assert!(level.map.get_items().is_empty());
+    }
+
+    #[test]
+    fn reports_unmapped_walls_and_missing_levels() {
+        let (walls, objects) = small_level();
+        let (maphead, gamemaps) = build_files(&[("Map", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+
+        let mut table = WallTable::new();
+        table.set(1, 1);
+        assert_eq!(
+            game_maps.load_level(0, &table).err(),
+            Some(GameMapsError::UnmappedWall {
+                code: 8,
+                position: Vector2i::new(2, 2)
+            })
+        );
+        assert_eq!(
+            game_maps.load_level(1, &table).err(),
+            Some(GameMapsError::MissingLevel(1))
+        );
+    }
+
+    #[test]
+    fn turns_door_codes_into_doors() {
+        let (mut walls, objects) = small_level();
+        walls[3] = 90;
+        walls[12 + 5] = 95;
+        let (maphead, gamemaps) = build_files(&[("Map", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+        let mut level = game_maps
+            .load_level(0, &WallTable::with_builtin_textures())
+            .unwrap();
+
+        let door = level.map.get_door(&Vector2i::new(3, 0)).unwrap();
+        assert!(!door.is_vertical());
+        assert_eq!(level.map.get_doors().len(), 2);
+        assert!(level.map.get_door(&Vector2i::new(2, 0)).is_none());
+
+        let locked = Vector2i::new(5, 2);
+        assert!(level.map.get_door(&locked).unwrap().is_vertical());
+        assert_eq!(
+            level.map.get_tile_info(&locked).unwrap().lock,
+            Some(KeyColor::Silver)
+        );
+        assert_eq!(
+            level
+                .map
+                .use_door(&locked, &[], |key| key == KeyColor::Gold),
+            DoorUse::Locked(KeyColor::Silver)
+        );
+        assert_eq!(
+            level
+                .map
+                .use_door(&locked, &[], |key| key == KeyColor::Silver),
+            DoorUse::Used
+        );
+        assert_eq!(
+            level.map.use_door(&Vector2i::new(1, 1), &[], |_| false),
+            DoorUse::NoDoor
+        );
+    }
+
+    #[test]
+    fn marks_push_walls_and_slides_them() {
+        let (walls, mut objects) = small_level();
+        objects[12 + 2] = PUSH_WALL_OBJECT;
+        let (maphead, gamemaps) = build_files(&[("Map", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+        let mut table = WallTable::new();
+        table.set(1, 1);
+        table.set(8, 4);
+        let mut level = game_maps.load_level(0, &table).unwrap();
+
+        let pillar = Vector2i::new(2, 2);
+        assert!(level.map.is_push_wall(&pillar));
+        assert_eq!(level.map.get_secret_count(), 1);
+        assert!(level
+            .objects
+            .iter()
+            .all(|object| object.code != PUSH_WALL_OBJECT));
+
+        // Only one free tile behind the pillar before the outer wall
+        assert!(level.map.push_wall(&pillar, Vector2i::new(0, 1), &[]));
+        assert_eq!(level.map.get_secrets_found(), 1);
+        assert_eq!(level.map.get_block(&pillar), Some(0));
+        assert!(!level.map.is_walkable(&Vector2f::new(2.5, 2.5)));
+        level.map.update_push_wall(10., &[]);
+        assert!(level.map.get_moving_wall().is_none());
+        assert_eq!(level.map.get_block(&Vector2i::new(2, 3)), Some(4));
+        assert!(level.map.is_walkable(&Vector2f::new(2.5, 2.5)));
+        assert!(!level
+            .map
+            .push_wall(&Vector2i::new(2, 3), Vector2i::new(0, 1), &[]));
+    }
+
+    #[test]
+    fn spawns_guards_of_every_skill_level() {
+        let (walls, mut objects) = small_level();
+        objects[6 + 3] = 108 + 3;
+        objects[6 + 4] = 144 + 5;
+        let (maphead, gamemaps) = build_files(&[("Map", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+        let level = game_maps
+            .load_level(0, &WallTable::with_builtin_textures())
+            .unwrap();
+
+        assert_eq!(
+            level.map.get_actor_spawns(),
+            &[
+                ActorSpawn {
+                    position: Vector2i::new(3, 1),
+                    direction: Vector2f::new(0., 1.),
+                    patrol: false,
+                },
+                ActorSpawn {
+                    position: Vector2i::new(4, 1),
+                    direction: Vector2f::new(0., -1.),
+                    patrol: true,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn turns_item_objects_into_items() {
+        let (walls, mut objects) = small_level();
+        objects[6 + 3] = 43;
+        objects[18 + 4] = 48;
+        let (maphead, gamemaps) = build_files(&[("Map", 6, 5, walls, objects)]);
+        let game_maps = GameMaps::from_bytes(&maphead, gamemaps).unwrap();
+        let level = game_maps
+            .load_level(0, &WallTable::with_builtin_textures())
+            .unwrap();
+
+        assert_eq!(
+            level.map.get_items(),
+            &[
+                Item {
+                    kind: ItemKind::GoldKey,
+                    position: Vector2i::new(3, 1),
+                },
+                Item {
+                    kind: ItemKind::FirstAid,
+                    position: Vector2i::new(4, 3),
+                },
+            ]
+        );
+        assert!(level.map.get_decorations().is_empty());
+        assert_eq!(ItemKind::FirstAid.get_sprite(), FIRST_SPRITE + 27);
+    }
+
+    #[test]
+    fn rejects_files_without_signature() {
+        let (maphead, mut gamemaps) = build_files(&[]);
+        gamemaps[0] = b'X';
+        assert!(GameMaps::from_bytes(&maphead, gamemaps).is_err());
+    }
+}
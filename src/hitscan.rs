@@ -0,0 +1,198 @@
+//! Module for instant-hit ray queries against walls and actors
+
+use rsfml::system::{Vector2f, Vector2i};
+
+use crate::{
+    actor::{Actor, ACTOR_RADIUS},
+    map::Map,
+};
+
+/// What a ray cast with [cast_ray] runs into first.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum RayHit {
+    Wall {
+        tile: Vector2i,
+        distance: f32,
+    },
+    /// `index` is the position of the actor in the slice given to [cast_ray]
+    Actor {
+        index: usize,
+        tile: Vector2i,
+        distance: f32,
+    },
+    Nothing,
+}
+
+/// Finds the first wall or living actor along a ray. Actors are circles of [ACTOR_RADIUS].
+///
+/// # Arguments
+/// `map` - The level the ray goes through
+///
+/// `actors` - The actors that can be hit
+///
+/// `origin` - Where the ray starts
+///
+/// `direction` - The direction of the ray, it does not need to be normalized
+///
+/// `max_distance` - How far the ray goes, in tiles
+pub fn cast_ray(
+    map: &Map,
+    actors: &[Actor],
+    origin: &Vector2f,
+    direction: &Vector2f,
+    max_distance: f32,
+) -> RayHit {
+    let length = (direction.x * direction.x + direction.y * direction.y).sqrt();
+    if length == 0. {
+        return RayHit::Nothing;
+    }
+    let ray = Vector2f::new(direction.x / length, direction.y / length);
+    let mut hit = match map.cast_ray(origin, &ray, max_distance) {
+        Some(wall) => RayHit::Wall {
+            tile: wall.tile,
+            distance: wall.distance,
+        },
+        None => RayHit::Nothing,
+    };
+    let mut nearest = match hit {
+        RayHit::Wall { distance, .. } => distance,
+        _ => max_distance,
+    };
+
+    for (index, actor) in actors.iter().enumerate() {
+        if !actor.is_alive() {
+            continue;
+        }
+        let position = actor.get_position();
+        let to_actor = Vector2f::new(position.x - origin.x, position.y - origin.y);
+        // Distance along the ray to the point closest to the actor
+        let along = to_actor.x * ray.x + to_actor.y * ray.y;
+        let across_squared = to_actor.x * to_actor.x + to_actor.y * to_actor.y - along * along;
+        if along < 0. || across_squared > ACTOR_RADIUS * ACTOR_RADIUS {
+            continue;
+        }
+        let distance = (along - (ACTOR_RADIUS * ACTOR_RADIUS - across_squared).sqrt()).max(0.);
+        if distance < nearest {
+            nearest = distance;
+            hit = RayHit::Actor {
+                index,
+                tile: actor.get_tile(),
+                distance,
+            };
+        }
+    }
+    hit
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::actor::{guard_textures, GUARD_HEALTH};
+
+    const LEVEL: &str = "\
+# = 1
+D = 1 door
+g = 4 guard
+map:
+#########
+#>..g...#
+#.......#
+####D####
+#.......#
+#########
+";
+
+    fn level() -> (Map, Vec<Actor>) {
+        let map = Map::parse_ascii(LEVEL).unwrap();
+        let texture_loader = guard_textures();
+        let actors = map
+            .get_actor_spawns()
+            .iter()
+            .map(|spawn| Actor::new(spawn, &texture_loader))
+            .collect();
+        (map, actors)
+    }
+
+    #[test]
+    fn rays_hit_the_nearest_actor_before_walls() {
+        let (map, actors) = level();
+        let origin = Vector2f::new(1.5, 1.5);
+        match cast_ray(&map, &actors, &origin, &Vector2f::new(2., 0.), 32.) {
+            RayHit::Actor {
+                index,
+                tile,
+                distance,
+            } => {
+                assert_eq!(index, 0);
+                assert_eq!(tile, Vector2i::new(4, 1));
+                assert!((distance - (3. - ACTOR_RADIUS)).abs() < 1e-4);
+            }
+            hit => panic!("{:?}", hit),
+        }
+        // Too short to reach the guard
+        assert_eq!(
+            cast_ray(&map, &actors, &origin, &Vector2f::new(1., 0.), 2.),
+            RayHit::Nothing
+        );
+        // Grazing past the guard
+        match cast_ray(&map, &actors, &origin, &Vector2f::new(1., 0.2), 32.) {
+            RayHit::Wall { tile, .. } => assert_eq!(tile.y, 2),
+            hit => panic!("{:?}", hit),
+        }
+    }
+
+    #[test]
+    fn rays_stop_at_walls_and_closed_doors_and_skip_the_dead() {
+        let (map, mut actors) = level();
+        let origin = Vector2f::new(4.5, 2.5);
+        match cast_ray(&map, &actors, &origin, &Vector2f::new(0., 1.), 32.) {
+            RayHit::Wall { tile, distance } => {
+                // The panel of a closed door is in the middle of its tile
+                assert_eq!(tile, Vector2i::new(4, 3));
+                assert!((distance - 1.).abs() < 1e-4, "{}", distance);
+            }
+            hit => panic!("{:?}", hit),
+        }
+
+        actors[0].take_damage(GUARD_HEALTH);
+        let origin = Vector2f::new(1.5, 1.5);
+        match cast_ray(&map, &actors, &origin, &Vector2f::new(1., 0.), 32.) {
+            RayHit::Wall { tile, distance } => {
+                assert_eq!(tile, Vector2i::new(8, 1));
+                assert!((distance - 6.5).abs() < 1e-4, "{}", distance);
+            }
+            hit => panic!("{:?}", hit),
+        }
+        assert_eq!(
+            cast_ray(&map, &actors, &origin, &Vector2f::new(0., 0.), 32.),
+            RayHit::Nothing
+        );
+    }
+
+    #[test]
+    fn slanted_rays_hit_door_panels_only_inside_the_door_tile() {
+        let (map, actors) = level();
+        // Crosses the panel in the middle of the door tile
+        let direction = Vector2f::new(1., 2.);
+        match cast_ray(&map, &actors, &Vector2f::new(3.8, 2.2), &direction, 32.) {
+            RayHit::Wall { tile, distance } => {
+                assert_eq!(tile, Vector2i::new(4, 3));
+                assert!((distance - 0.65 * 5f32.sqrt()).abs() < 1e-4, "{}", distance);
+            }
+            hit => panic!("{:?}", hit),
+        }
+        // Grazes the corner of the door tile and leaves it before the panel, into the wall
+        let direction = Vector2f::new(1.5, 1.);
+        match cast_ray(&map, &actors, &Vector2f::new(3.2, 2.2), &direction, 32.) {
+            RayHit::Wall { tile, distance } => {
+                assert_eq!(tile, Vector2i::new(5, 3));
+                assert!(
+                    (distance - 1.2 * 3.25f32.sqrt()).abs() < 1e-4,
+                    "{}",
+                    distance
+                );
+            }
+            hit => panic!("{:?}", hit),
+        }
+    }
+}
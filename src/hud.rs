@@ -2,13 +2,43 @@
 
 use rsfml::{
     graphics::{
-        Color, PrimitiveType, RectangleShape, RenderTarget, RenderWindow, Shape, Transformable,
-        Vertex, VertexArray,
+        Color, Font, PrimitiveType, RectangleShape, RenderTarget, RenderWindow, Shape, Text,
+        Transformable, Vertex, VertexArray,
     },
-    system::{Clock, Vector2f},
+    system::Vector2f,
 };
 
-use crate::{animation::*, texture_loader::TextureLoader};
+use crate::{
+    animation::*,
+    player_state::{KeyColor, PlayerState, MAX_HEALTH},
+    save::{SaveError, StateReader, StateWriter},
+    texture_loader::TextureLoader,
+};
+
+/// Number of face sets, from healthy to nearly dead. Set 0 is `face_1` to `face_3`, the others
+/// are the optional `face_hurt_<set>_<frame>` textures.
+const FACE_LEVELS: usize = 7;
+
+/// Health lost between two face sets, like the original status bar.
+const HEALTH_PER_FACE_LEVEL: i32 = 16;
+
+/// Seconds a message stays on screen.
+const MESSAGE_TIME: f32 = 2.5;
+
+/// Horizontal center of each status bar field, as a fraction of the window width.
+const FLOOR_X: f32 = 0.07;
+const SCORE_X: f32 = 0.22;
+const LIVES_X: f32 = 0.38;
+const HEALTH_X: f32 = 0.6;
+const AMMO_X: f32 = 0.72;
+const KEYS_X: f32 = 0.81;
+
+/// A labelled number of the status bar.
+struct StatusField<'s> {
+    label: Text<'s>,
+    value: Text<'s>,
+    center: f32,
+}
 
 type Line<const N: usize> = [f32; N];
 
@@ -20,49 +50,226 @@ pub struct HUD<'s> {
     background: RectangleShape<'s>,
     hud_vertex_array: VertexArray,
     face: RectangleShape<'s>,
-    face_animation: Animation,
+    // Face animation of each health level, healthiest first
+    face_animations: Vec<Animation>,
+    // Whether each level has its own textures, the others tint the healthy face
+    hurt_faces: Vec<bool>,
+    dead_face: Option<i32>,
+    // Texture of the face, set on the shape when drawn
+    face_texture_id: i32,
     texture_loader: &'s TextureLoader,
-    face_clock: Clock,
+    // Seconds since the face animations last played
+    face_time: f32,
+    fields: Vec<StatusField<'s>>,
+    // Key icons, with whether the player holds each key
+    keys: Vec<(KeyColor, RectangleShape<'s>, bool)>,
+    message: Text<'s>,
+    message_time: f32,
+}
+
+impl<'s> StatusField<'s> {
+    fn new(label: &str, center: f32, window_size: &Vector2f, font: &'s Font) -> StatusField<'s> {
+        let mut label = Text::new(label, font, 11);
+        label.set_fill_color(Color::rgb(170, 170, 220));
+        let bounds = label.local_bounds();
+        label.set_position(Vector2f::new(
+            (center - bounds.width / 2.).round(),
+            window_size.y - 68.,
+        ));
+        let mut value = Text::new("0", font, 28);
+        value.set_fill_color(Color::WHITE);
+        StatusField {
+            label,
+            value,
+            center,
+        }
+    }
+
+    /// Shows a new value, centered under the label.
+    fn set_value(&mut self, value: i32, window_size: &Vector2f) {
+        self.value.set_string(&value.to_string());
+        let bounds = self.value.local_bounds();
+        self.value.set_position(Vector2f::new(
+            (self.center - bounds.width / 2. - bounds.left).round(),
+            window_size.y - 55.,
+        ));
+    }
 }
 
 impl<'s> HUD<'s> {
-    pub fn new(window_size: &Vector2f, texture_loader: &'s TextureLoader) -> HUD<'s> {
+    /// Creates the status bar.
+    ///
+    /// # Arguments
+    /// `window_size` - The size of the window, the status bar spans its bottom
+    ///
+    /// `texture_loader` - A [TextureLoader] holding the face textures
+    ///
+    /// `font` - The font of the status bar numbers
+    pub fn new(
+        window_size: &Vector2f,
+        texture_loader: &'s TextureLoader,
+        font: &'s Font,
+    ) -> HUD<'s> {
         let mut array = VertexArray::default();
         array.set_primitive_type(PrimitiveType::LineStrip);
         let mut tmp_face = RectangleShape::with_size(Vector2f::new(43., 58.));
         tmp_face.set_position(Vector2f::new(window_size.x / 2. - 21., window_size.y - 71.));
+        let (face_animations, hurt_faces) = HUD::create_face_animations(texture_loader);
+        let face_texture_id = face_animations[0].get_current_texture_id();
+        let fields = [
+            ("FLOOR", FLOOR_X),
+            ("SCORE", SCORE_X),
+            ("LIVES", LIVES_X),
+            ("HEALTH", HEALTH_X),
+            ("AMMO", AMMO_X),
+        ]
+        .iter()
+        .map(|&(label, center)| StatusField::new(label, window_size.x * center, window_size, font))
+        .collect();
+        let keys = [
+            (
+                KeyColor::Gold,
+                Color::rgb(232, 184, 24),
+                window_size.y - 64.,
+            ),
+            (
+                KeyColor::Silver,
+                Color::rgb(184, 184, 200),
+                window_size.y - 40.,
+            ),
+        ]
+        .iter()
+        .map(|&(key, color, y)| {
+            let mut shape = RectangleShape::with_size(Vector2f::new(10., 20.));
+            shape.set_fill_color(color);
+            shape.set_position(Vector2f::new(window_size.x * KEYS_X - 5., y));
+            (key, shape, false)
+        })
+        .collect();
+        let mut message = Text::new("", font, 18);
+        message.set_fill_color(Color::WHITE);
+        message.set_position(Vector2f::new(20., window_size.y - 112.));
         HUD {
             window_size: window_size.clone(),
             background: RectangleShape::new(),
             hud_vertex_array: array,
             face: tmp_face,
-            face_animation: Animation::new(
-                vec![40, 41, 42],
+            face_animations,
+            hurt_faces,
+            dead_face: texture_loader.get_id("face_dead"),
+            face_texture_id,
+            texture_loader,
+            face_time: 0.,
+            fields,
+            keys,
+            message,
+            message_time: 0.,
+        }
+    }
+
+    /// Creates the face animation of each health level. Levels without their own
+    /// `face_hurt_<level>_<frame>` textures reuse the healthy face.
+    fn create_face_animations(texture_loader: &TextureLoader) -> (Vec<Animation>, Vec<bool>) {
+        let healthy: Vec<i32> = (1..4)
+            .map(|frame| texture_loader.require_id(&format!("face_{}", frame)))
+            .collect();
+        let mut animations = Vec::with_capacity(FACE_LEVELS);
+        let mut hurt_faces = Vec::with_capacity(FACE_LEVELS);
+        for level in 0..FACE_LEVELS {
+            let hurt: Option<Vec<i32>> = (1..4)
+                .map(|frame| texture_loader.get_id(&format!("face_hurt_{}_{}", level, frame)))
+                .collect();
+            let frames = match hurt {
+                Some(frames) if level > 0 => frames,
+                _ => healthy.clone(),
+            };
+            hurt_faces.push(level == 0 || frames != healthy);
+            animations.push(Animation::new(
+                frames,
                 AnimationState::Play,
                 AnimationPlayMode::Once,
                 1.,
                 0,
-            ),
-            texture_loader,
-            face_clock: Clock::start(),
+            ));
+        }
+        (animations, hurt_faces)
+    }
+
+    /// Shows a message above the status bar for a few seconds.
+    pub fn show_message(&mut self, message: &str) {
+        self.message.set_string(message);
+        self.message_time = MESSAGE_TIME;
+    }
+
+    /// Writes the face animations to a save. The rest of the status bar follows the player
+    /// state.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_f32(self.face_time);
+        for animation in self.face_animations.iter() {
+            animation.save_state(writer);
+        }
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.face_time = reader.read_f32()?;
+        for animation in self.face_animations.iter_mut() {
+            animation.load_state(reader)?;
         }
+        Ok(())
     }
 
-    pub fn update(&mut self) {
+    /// Updates the status bar from the player state.
+    ///
+    /// # Arguments
+    /// `player` - The state shown in the status bar
+    ///
+    /// `delta` - Seconds elapsed since the last update
+    pub fn update(&mut self, player: &PlayerState, delta: f32) {
+        self.message_time = (self.message_time - delta).max(0.);
         self.background
             .set_size(Vector2f::new(self.window_size.x - 21., 59.));
         self.background.set_fill_color(Color::rgb(6, 1, 162));
         self.background
             .set_position(Vector2f::new(10., self.window_size.y - 70.));
-        self.face_animation.update();
-        self.face.set_texture(
-            self.texture_loader
-                .get_texture(self.face_animation.get_current_texture_id()),
-            false,
-        );
-        if self.face_clock.elapsed_time().as_seconds() >= 7. {
-            self.face_animation.set_state(AnimationState::Play);
-            self.face_clock.restart();
+
+        let values = [
+            player.get_floor(),
+            player.get_score(),
+            player.get_lives(),
+            player.get_health(),
+            player.get_ammo(),
+        ];
+        for (field, value) in self.fields.iter_mut().zip(values.iter()) {
+            field.set_value(*value, &self.window_size);
+        }
+        for (key, _, held) in self.keys.iter_mut() {
+            *held = player.has_key(*key);
+        }
+        self.update_face(player, delta);
+    }
+
+    fn update_face(&mut self, player: &PlayerState, delta: f32) {
+        let level = (((MAX_HEALTH - player.get_health()) / HEALTH_PER_FACE_LEVEL) as usize)
+            .min(FACE_LEVELS - 1);
+        let animation = &mut self.face_animations[level];
+        animation.update(delta);
+        self.face_texture_id = match self.dead_face {
+            Some(dead_face) if player.is_dead() => dead_face,
+            _ => animation.get_current_texture_id(),
+        };
+        // Without art for this level, the healthy face turns redder as health drops
+        if self.hurt_faces[level] {
+            self.face.set_fill_color(Color::WHITE);
+        } else {
+            let shade = 255 - (level as i32 * 120 / (FACE_LEVELS as i32 - 1)) as u8;
+            self.face.set_fill_color(Color::rgb(255, shade, shade));
+        }
+        self.face_time += delta;
+        if self.face_time >= 7. {
+            for animation in self.face_animations.iter_mut() {
+                animation.set_state(AnimationState::Play);
+            }
+            self.face_time = 0.;
         }
     }
 
@@ -158,6 +365,20 @@ impl<'s> HUD<'s> {
             &Color::rgba(255, 255, 255, 75),
             render_window,
         );
+        self.face
+            .set_texture(self.texture_loader.get_texture(self.face_texture_id), false);
         render_window.draw(&self.face);
+        for field in self.fields.iter() {
+            render_window.draw(&field.label);
+            render_window.draw(&field.value);
+        }
+        for (_, shape, held) in self.keys.iter() {
+            if *held {
+                render_window.draw(shape);
+            }
+        }
+        if self.message_time > 0. {
+            render_window.draw(&self.message);
+        }
     }
 }
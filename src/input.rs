@@ -0,0 +1,852 @@
+//! Module for the actions of the player and the keys, mouse buttons and wheel moves bound to them
+
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use rsfml::window::{
+    joystick::{self, Axis},
+    mouse::Button as MouseButton,
+    Event, Key,
+};
+
+/// Something the player can do, whatever input it is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Action {
+    MoveForward,
+    MoveBackward,
+    StrafeLeft,
+    StrafeRight,
+    TurnLeft,
+    TurnRight,
+    Run,
+    Fire,
+    Use,
+    Reload,
+    NextWeapon,
+    PreviousWeapon,
+    Weapon1,
+    Weapon2,
+    Weapon3,
+    Weapon4,
+    ToggleMap,
+    QuickSave,
+    QuickLoad,
+    Rebind,
+    Quit,
+}
+
+/// Every action, in the order of the controls file.
+pub const ACTIONS: [Action; 21] = [
+    Action::MoveForward,
+    Action::MoveBackward,
+    Action::StrafeLeft,
+    Action::StrafeRight,
+    Action::TurnLeft,
+    Action::TurnRight,
+    Action::Run,
+    Action::Fire,
+    Action::Use,
+    Action::Reload,
+    Action::NextWeapon,
+    Action::PreviousWeapon,
+    Action::Weapon1,
+    Action::Weapon2,
+    Action::Weapon3,
+    Action::Weapon4,
+    Action::ToggleMap,
+    Action::QuickSave,
+    Action::QuickLoad,
+    Action::Rebind,
+    Action::Quit,
+];
+
+/// The actions selecting each weapon slot.
+pub const WEAPON_ACTIONS: [Action; 4] = [
+    Action::Weapon1,
+    Action::Weapon2,
+    Action::Weapon3,
+    Action::Weapon4,
+];
+
+/// Names of the keys in the controls file.
+const KEY_NAMES: [(&str, Key); 76] = [
+    ("A", Key::A),
+    ("B", Key::B),
+    ("C", Key::C),
+    ("D", Key::D),
+    ("E", Key::E),
+    ("F", Key::F),
+    ("G", Key::G),
+    ("H", Key::H),
+    ("I", Key::I),
+    ("J", Key::J),
+    ("K", Key::K),
+    ("L", Key::L),
+    ("M", Key::M),
+    ("N", Key::N),
+    ("O", Key::O),
+    ("P", Key::P),
+    ("Q", Key::Q),
+    ("R", Key::R),
+    ("S", Key::S),
+    ("T", Key::T),
+    ("U", Key::U),
+    ("V", Key::V),
+    ("W", Key::W),
+    ("X", Key::X),
+    ("Y", Key::Y),
+    ("Z", Key::Z),
+    ("Num0", Key::Num0),
+    ("Num1", Key::Num1),
+    ("Num2", Key::Num2),
+    ("Num3", Key::Num3),
+    ("Num4", Key::Num4),
+    ("Num5", Key::Num5),
+    ("Num6", Key::Num6),
+    ("Num7", Key::Num7),
+    ("Num8", Key::Num8),
+    ("Num9", Key::Num9),
+    ("Numpad0", Key::Numpad0),
+    ("Numpad1", Key::Numpad1),
+    ("Numpad2", Key::Numpad2),
+    ("Numpad3", Key::Numpad3),
+    ("Numpad4", Key::Numpad4),
+    ("Numpad5", Key::Numpad5),
+    ("Numpad6", Key::Numpad6),
+    ("Numpad7", Key::Numpad7),
+    ("Numpad8", Key::Numpad8),
+    ("Numpad9", Key::Numpad9),
+    ("F1", Key::F1),
+    ("F2", Key::F2),
+    ("F3", Key::F3),
+    ("F4", Key::F4),
+    ("F5", Key::F5),
+    ("F6", Key::F6),
+    ("F7", Key::F7),
+    ("F8", Key::F8),
+    ("F9", Key::F9),
+    ("F10", Key::F10),
+    ("F11", Key::F11),
+    ("F12", Key::F12),
+    ("Left", Key::Left),
+    ("Right", Key::Right),
+    ("Up", Key::Up),
+    ("Down", Key::Down),
+    ("Space", Key::Space),
+    ("Tab", Key::Tab),
+    ("Escape", Key::Escape),
+    ("LShift", Key::LShift),
+    ("RShift", Key::RShift),
+    ("LControl", Key::LControl),
+    ("RControl", Key::RControl),
+    ("LAlt", Key::LAlt),
+    ("RAlt", Key::RAlt),
+    ("PageUp", Key::PageUp),
+    ("PageDown", Key::PageDown),
+    ("Home", Key::Home),
+    ("End", Key::End),
+    ("Insert", Key::Insert),
+];
+
+/// Names of the mouse buttons in the controls file.
+const MOUSE_BUTTON_NAMES: [(&str, MouseButton); 5] = [
+    ("MouseLeft", MouseButton::Left),
+    ("MouseRight", MouseButton::Right),
+    ("MouseMiddle", MouseButton::Middle),
+    ("MouseX1", MouseButton::XButton1),
+    ("MouseX2", MouseButton::XButton2),
+];
+
+/// Names of the gamepad axes in the controls file, after `Pad`.
+const AXIS_NAMES: [(&str, Axis); 8] = [
+    ("X", Axis::X),
+    ("Y", Axis::Y),
+    ("Z", Axis::Z),
+    ("R", Axis::R),
+    ("U", Axis::U),
+    ("V", Axis::V),
+    ("PovX", Axis::PovX),
+    ("PovY", Axis::PovY),
+];
+
+/// First lines of a written controls file.
+const CONTROLS_HEADER: &str = "\
+# Controls: <action> = <binding>, <binding>...
+#
+# Bindings are key names (A to Z, Num0 to Num9, Numpad0 to Numpad9, F1 to F12, Left, Right,
+# Up, Down, Space, Tab, Escape, LShift, RShift, LControl, RControl, LAlt, RAlt, PageUp,
+# PageDown, Home, End, Insert), mouse buttons (MouseLeft, MouseRight, MouseMiddle, MouseX1,
+# MouseX2), wheel moves (WheelUp, WheelDown), gamepad buttons (PadButton0 to PadButton31) and
+# gamepad axes pushed one way (PadX+, PadY-, ... for the axes X, Y, Z, R, U, V, PovX, PovY).
+# Actions left out keep their default bindings. The game rewrites this file when the controls
+# are rebound in game.
+#
+# Axes bound to moving and turning move and turn as far as they are pushed. Past the deadzone
+# (0 to 1), how far an axis is pushed is raised to the power of the curve, then scaled by the
+# sensitivity. Axes and their directions differ between gamepads and systems, the defaults
+# follow an Xbox gamepad on Linux.
+";
+
+impl Action {
+    /// Returns the name of the action in the controls file.
+    pub fn get_name(self) -> &'static str {
+        match self {
+            Action::MoveForward => "move_forward",
+            Action::MoveBackward => "move_backward",
+            Action::StrafeLeft => "strafe_left",
+            Action::StrafeRight => "strafe_right",
+            Action::TurnLeft => "turn_left",
+            Action::TurnRight => "turn_right",
+            Action::Run => "run",
+            Action::Fire => "fire",
+            Action::Use => "use",
+            Action::Reload => "reload",
+            Action::NextWeapon => "next_weapon",
+            Action::PreviousWeapon => "previous_weapon",
+            Action::Weapon1 => "weapon_1",
+            Action::Weapon2 => "weapon_2",
+            Action::Weapon3 => "weapon_3",
+            Action::Weapon4 => "weapon_4",
+            Action::ToggleMap => "toggle_map",
+            Action::QuickSave => "quick_save",
+            Action::QuickLoad => "quick_load",
+            Action::Rebind => "rebind",
+            Action::Quit => "quit",
+        }
+    }
+
+    /// Returns the action with a name of the controls file.
+    pub fn from_name(name: &str) -> Option<Action> {
+        ACTIONS
+            .iter()
+            .copied()
+            .find(|action| action.get_name() == name)
+    }
+
+    /// Returns the text shown to the player for the action.
+    pub fn get_description(self) -> &'static str {
+        match self {
+            Action::MoveForward => "Move forward",
+            Action::MoveBackward => "Move backward",
+            Action::StrafeLeft => "Strafe left",
+            Action::StrafeRight => "Strafe right",
+            Action::TurnLeft => "Turn left",
+            Action::TurnRight => "Turn right",
+            Action::Run => "Run",
+            Action::Fire => "Fire",
+            Action::Use => "Open doors",
+            Action::Reload => "Reload",
+            Action::NextWeapon => "Next weapon",
+            Action::PreviousWeapon => "Previous weapon",
+            Action::Weapon1 => "Pistol",
+            Action::Weapon2 => "Machine gun",
+            Action::Weapon3 => "Chaingun",
+            Action::Weapon4 => "Knife",
+            Action::ToggleMap => "Mini-map",
+            Action::QuickSave => "Quicksave",
+            Action::QuickLoad => "Quickload",
+            Action::Rebind => "Rebind controls",
+            Action::Quit => "Quit",
+        }
+    }
+}
+
+/// A key, mouse button, wheel move or gamepad input an action is bound to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Binding {
+    Key(Key),
+    Mouse(MouseButton),
+    WheelUp,
+    WheelDown,
+    GamepadButton(u32),
+    /// An axis pushed towards its positive or negative end
+    GamepadAxis {
+        axis: Axis,
+        positive: bool,
+    },
+}
+
+/// How the position of the gamepad axes bound to moving or turning turns into speed.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct AxisResponse {
+    /// How far an axis can be pushed without any effect, from 0 to 1
+    pub deadzone: f32,
+    /// Power the pushed distance past the deadzone is raised to, higher values give finer
+    /// control of small moves
+    pub curve: f32,
+    /// Factor of the speed of an axis pushed all the way
+    pub sensitivity: f32,
+}
+
+impl AxisResponse {
+    /// Returns the speed, from 0 to the sensitivity, for an axis pushed from 0 to 1.
+    pub fn apply(&self, pushed: f32) -> f32 {
+        if pushed <= self.deadzone {
+            return 0.;
+        }
+        let past_deadzone = ((pushed - self.deadzone) / (1. - self.deadzone)).min(1.);
+        past_deadzone.powf(self.curve) * self.sensitivity
+    }
+}
+
+impl Binding {
+    /// Returns the binding with a name of the controls file.
+    pub fn from_name(name: &str) -> Option<Binding> {
+        if let Some(button) = name.strip_prefix("PadButton") {
+            return match button.parse() {
+                Ok(button) if button < joystick::BUTTON_COUNT => {
+                    Some(Binding::GamepadButton(button))
+                }
+                _ => None,
+            };
+        }
+        if let Some(axis) = name.strip_prefix("Pad") {
+            let positive = match axis.chars().last() {
+                Some('+') => true,
+                Some('-') => false,
+                _ => return None,
+            };
+            let axis_name = &axis[..axis.len() - 1];
+            return AXIS_NAMES
+                .iter()
+                .find(|(named, _)| *named == axis_name)
+                .map(|(_, axis)| Binding::GamepadAxis {
+                    axis: *axis,
+                    positive,
+                });
+        }
+        match name {
+            "WheelUp" => Some(Binding::WheelUp),
+            "WheelDown" => Some(Binding::WheelDown),
+            _ => KEY_NAMES
+                .iter()
+                .find(|(key_name, _)| *key_name == name)
+                .map(|(_, key)| Binding::Key(*key))
+                .or_else(|| {
+                    MOUSE_BUTTON_NAMES
+                        .iter()
+                        .find(|(button_name, _)| *button_name == name)
+                        .map(|(_, button)| Binding::Mouse(*button))
+                }),
+        }
+    }
+
+    /// Returns the name of the binding in the controls file, or None for keys the file cannot
+    /// name.
+    pub fn get_name(&self) -> Option<String> {
+        match self {
+            Binding::Key(key) => KEY_NAMES
+                .iter()
+                .find(|(_, named)| named == key)
+                .map(|(name, _)| name.to_string()),
+            Binding::Mouse(button) => MOUSE_BUTTON_NAMES
+                .iter()
+                .find(|(_, named)| named == button)
+                .map(|(name, _)| name.to_string()),
+            Binding::WheelUp => Some(String::from("WheelUp")),
+            Binding::WheelDown => Some(String::from("WheelDown")),
+            Binding::GamepadButton(button) => Some(format!("PadButton{}", button)),
+            Binding::GamepadAxis { axis, positive } => AXIS_NAMES
+                .iter()
+                .find(|(_, named)| named == axis)
+                .map(|(name, _)| format!("Pad{}{}", name, if *positive { '+' } else { '-' })),
+        }
+    }
+
+    /// Whether the binding is on a gamepad rather than the keyboard or mouse.
+    pub fn is_gamepad(&self) -> bool {
+        matches!(
+            self,
+            Binding::GamepadButton(_) | Binding::GamepadAxis { .. }
+        )
+    }
+
+    /// Returns the binding an event presses, if any.
+    pub fn from_pressed_event(event: &Event) -> Option<Binding> {
+        match *event {
+            Event::KeyPressed { code, .. } => Some(Binding::Key(code)),
+            Event::MouseButtonPressed { button, .. } => Some(Binding::Mouse(button)),
+            Event::MouseWheelScrolled { delta, .. } if delta > 0. => Some(Binding::WheelUp),
+            Event::MouseWheelScrolled { delta, .. } if delta < 0. => Some(Binding::WheelDown),
+            Event::JoystickButtonPressed { button, .. } => Some(Binding::GamepadButton(button)),
+            _ => None,
+        }
+    }
+
+    /// Returns the binding an event releases, if any. Wheel moves are never released. Axis
+    /// moves are neither pressed nor released, see
+    /// [EventHandler](crate::event_handler::EventHandler) for when they count as held.
+    pub fn from_released_event(event: &Event) -> Option<Binding> {
+        match *event {
+            Event::KeyReleased { code, .. } => Some(Binding::Key(code)),
+            Event::MouseButtonReleased { button, .. } => Some(Binding::Mouse(button)),
+            Event::JoystickButtonReleased { button, .. } => Some(Binding::GamepadButton(button)),
+            _ => None,
+        }
+    }
+}
+
+/// The bindings of every action, read from a controls file.
+#[derive(Clone, Debug)]
+pub struct InputMap {
+    bindings: HashMap<Action, Vec<Binding>>,
+    // Response of the axes bound to moving, and to turning
+    move_response: AxisResponse,
+    look_response: AxisResponse,
+    // File the bindings are saved to after rebinding
+    path: Option<PathBuf>,
+}
+
+impl Default for InputMap {
+    fn default() -> InputMap {
+        let key = Binding::Key;
+        let pad = Binding::GamepadButton;
+        let axis = |axis, positive| Binding::GamepadAxis { axis, positive };
+        let defaults = [
+            (Action::MoveForward, vec![key(Key::W), axis(Axis::Y, false)]),
+            (Action::MoveBackward, vec![key(Key::S), axis(Axis::Y, true)]),
+            (Action::StrafeLeft, vec![key(Key::A), axis(Axis::X, false)]),
+            (Action::StrafeRight, vec![key(Key::D), axis(Axis::X, true)]),
+            (Action::TurnLeft, vec![key(Key::Left), axis(Axis::U, false)]),
+            (
+                Action::TurnRight,
+                vec![key(Key::Right), axis(Axis::U, true)],
+            ),
+            (
+                Action::Run,
+                vec![key(Key::LShift), key(Key::RShift), pad(9)],
+            ),
+            (
+                Action::Fire,
+                vec![Binding::Mouse(MouseButton::Left), axis(Axis::R, true)],
+            ),
+            (Action::Use, vec![key(Key::Space), pad(0)]),
+            (Action::Reload, vec![key(Key::E), pad(2)]),
+            (Action::NextWeapon, vec![Binding::WheelUp, pad(5)]),
+            (Action::PreviousWeapon, vec![Binding::WheelDown, pad(4)]),
+            (Action::Weapon1, vec![key(Key::Num1)]),
+            (Action::Weapon2, vec![key(Key::Num2)]),
+            (Action::Weapon3, vec![key(Key::Num3)]),
+            (Action::Weapon4, vec![key(Key::Num4)]),
+            (Action::ToggleMap, vec![key(Key::M), pad(6)]),
+            (Action::QuickSave, vec![key(Key::F5)]),
+            (Action::QuickLoad, vec![key(Key::F9)]),
+            (Action::Rebind, vec![key(Key::F10)]),
+            (Action::Quit, vec![key(Key::Escape)]),
+        ];
+        InputMap {
+            bindings: defaults.iter().cloned().collect(),
+            move_response: AxisResponse {
+                deadzone: 0.25,
+                curve: 1.,
+                sensitivity: 1.,
+            },
+            look_response: AxisResponse {
+                deadzone: 0.2,
+                curve: 2.,
+                sensitivity: 1.5,
+            },
+            path: None,
+        }
+    }
+}
+
+impl InputMap {
+    /// Reads the bindings from a controls file, see [InputMap::parse] for the format. The
+    /// bindings are saved back to the same file.
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<InputMap, String> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| {
+            format!(
+                "ERROR: Cannot read controls file {} ({})",
+                path.display(),
+                err
+            )
+        })?;
+        let mut input_map = InputMap::parse(&contents, &path.display().to_string())?;
+        input_map.path = Some(path.to_path_buf());
+        Ok(input_map)
+    }
+
+    /// Reads the bindings from the contents of a controls file.
+    ///
+    /// Each line is `<action> = <binding>, <binding>...`, and replaces the default bindings of
+    /// the action, or `<move|look>_<deadzone|curve|sensitivity> = <value>` for the response of
+    /// the gamepad axes. Blank lines and lines starting with `#` are ignored.
+    ///
+    /// # Arguments
+    /// `contents` - The text of the file
+    ///
+    /// `source` - Name of the file in error messages
+    pub fn parse(contents: &str, source: &str) -> Result<InputMap, String> {
+        let mut input_map = InputMap::default();
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, bindings) = match line.find('=') {
+                Some(separator) => (line[..separator].trim(), line[separator + 1..].trim()),
+                None => {
+                    return Err(format!(
+                        "ERROR: {}:{}: expected `<action> = <binding>, <binding>...`",
+                        source,
+                        line_idx + 1
+                    ))
+                }
+            };
+            if let Some(setting) = input_map.get_response_setting(name) {
+                *setting = match bindings.parse::<f32>() {
+                    Ok(value) if value.is_finite() && value >= 0. => value,
+                    _ => {
+                        return Err(format!(
+                            "ERROR: {}:{}: expected a number of 0 or more for {}",
+                            source,
+                            line_idx + 1,
+                            name
+                        ))
+                    }
+                };
+                continue;
+            }
+            let action = Action::from_name(name).ok_or_else(|| {
+                format!(
+                    "ERROR: {}:{}: unknown action {}",
+                    source,
+                    line_idx + 1,
+                    name
+                )
+            })?;
+            let bindings = bindings
+                .split(',')
+                .map(str::trim)
+                .filter(|binding| !binding.is_empty())
+                .map(|binding| {
+                    Binding::from_name(binding).ok_or_else(|| {
+                        format!(
+                            "ERROR: {}:{}: unknown binding {}",
+                            source,
+                            line_idx + 1,
+                            binding
+                        )
+                    })
+                })
+                .collect::<Result<Vec<Binding>, String>>()?;
+            input_map.bindings.insert(action, bindings);
+        }
+        for response in [input_map.move_response, input_map.look_response].iter() {
+            if response.deadzone >= 1. || response.curve <= 0. {
+                return Err(format!(
+                    "ERROR: {}: deadzones must be below 1 and curves above 0",
+                    source
+                ));
+            }
+        }
+        Ok(input_map)
+    }
+
+    /// Returns the axis response setting with a name of the controls file.
+    fn get_response_setting(&mut self, name: &str) -> Option<&mut f32> {
+        let (group, setting) = name.split_at(name.find('_')?);
+        let response = match group {
+            "move" => &mut self.move_response,
+            "look" => &mut self.look_response,
+            _ => return None,
+        };
+        match setting {
+            "_deadzone" => Some(&mut response.deadzone),
+            "_curve" => Some(&mut response.curve),
+            "_sensitivity" => Some(&mut response.sensitivity),
+            _ => None,
+        }
+    }
+
+    /// Returns the response of the gamepad axes bound to an action, None for actions that are
+    /// only on or off.
+    pub fn get_axis_response(&self, action: Action) -> Option<&AxisResponse> {
+        match action {
+            Action::MoveForward
+            | Action::MoveBackward
+            | Action::StrafeLeft
+            | Action::StrafeRight => Some(&self.move_response),
+            Action::TurnLeft | Action::TurnRight => Some(&self.look_response),
+            _ => None,
+        }
+    }
+
+    /// Returns the bindings as the text of a controls file.
+    pub fn to_config(&self) -> String {
+        let mut config = String::from(CONTROLS_HEADER);
+        config.push('\n');
+        for action in ACTIONS.iter() {
+            let names: Vec<String> = self
+                .get_bindings(*action)
+                .iter()
+                .filter_map(Binding::get_name)
+                .collect();
+            config.push_str(&format!("{} = {}\n", action.get_name(), names.join(", ")));
+        }
+        config.push('\n');
+        for (group, response) in
+            [("move", &self.move_response), ("look", &self.look_response)].iter()
+        {
+            config.push_str(&format!("{}_deadzone = {}\n", group, response.deadzone));
+            config.push_str(&format!("{}_curve = {}\n", group, response.curve));
+            config.push_str(&format!(
+                "{}_sensitivity = {}\n",
+                group, response.sensitivity
+            ));
+        }
+        config
+    }
+
+    /// Writes the bindings to the controls file they were read from.
+    pub fn save(&self) -> Result<(), String> {
+        let path = self
+            .path
+            .as_ref()
+            .ok_or_else(|| String::from("ERROR: The controls have no file to be saved to"))?;
+        fs::write(path, self.to_config()).map_err(|err| {
+            format!(
+                "ERROR: Cannot write controls file {} ({})",
+                path.display(),
+                err
+            )
+        })
+    }
+
+    /// Sets the controls file the bindings are saved to.
+    pub fn set_path<P: AsRef<Path>>(&mut self, path: P) {
+        self.path = Some(path.as_ref().to_path_buf());
+    }
+
+    pub fn get_bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Binds an action to a single key, mouse button or wheel move, or a single gamepad input,
+    /// which other actions lose. The action keeps its bindings on the other device, so rebinding
+    /// the keyboard leaves the gamepad as it was.
+    ///
+    /// # Arguments
+    /// `action` - The action to rebind
+    ///
+    /// `binding` - Its new and only binding on its device
+    pub fn rebind(&mut self, action: Action, binding: Binding) {
+        for bindings in self.bindings.values_mut() {
+            bindings.retain(|bound| *bound != binding);
+        }
+        let bindings = self.bindings.entry(action).or_default();
+        bindings.retain(|bound| bound.is_gamepad() != binding.is_gamepad());
+        bindings.push(binding);
+    }
+
+    /// Returns every binding of every action, once each.
+    pub fn get_all_bindings(&self) -> Vec<Binding> {
+        let mut all_bindings: Vec<Binding> = Vec::new();
+        for binding in self.bindings.values().flatten() {
+            if !all_bindings.contains(binding) {
+                all_bindings.push(*binding);
+            }
+        }
+        all_bindings
+    }
+
+    /// Whether a binding of the action is in a list, like the bindings held down.
+    pub fn is_bound_in(&self, action: Action, bindings: &[Binding]) -> bool {
+        self.get_bindings(action)
+            .iter()
+            .any(|binding| bindings.contains(binding))
+    }
+
+    /// Whether one of the events presses a binding of the action.
+    pub fn is_pressed(&self, action: Action, events: &[Event]) -> bool {
+        events
+            .iter()
+            .any(|event| match Binding::from_pressed_event(event) {
+                Some(binding) => self.get_bindings(action).contains(&binding),
+                None => false,
+            })
+    }
+
+    /// Whether one of the events releases a binding of the action.
+    pub fn is_released(&self, action: Action, events: &[Event]) -> bool {
+        events
+            .iter()
+            .any(|event| match Binding::from_released_event(event) {
+                Some(binding) => self.get_bindings(action).contains(&binding),
+                None => false,
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key_pressed(code: Key) -> Event {
+        Event::KeyPressed {
+            code,
+            alt: false,
+            ctrl: false,
+            shift: false,
+            system: false,
+        }
+    }
+
+    #[test]
+    fn defaults_survive_the_controls_file() {
+        let defaults = InputMap::default();
+        let parsed = InputMap::parse(&defaults.to_config(), "controls").unwrap();
+        for action in ACTIONS.iter() {
+            assert_eq!(parsed.get_bindings(*action), defaults.get_bindings(*action));
+        }
+    }
+
+    #[test]
+    fn shipped_controls_are_the_defaults() {
+        let path = concat!(env!("CARGO_MANIFEST_DIR"), "/resources/controls.cfg");
+        let shipped = InputMap::from_file(path).unwrap();
+        let defaults = InputMap::default();
+        for action in ACTIONS.iter() {
+            assert_eq!(
+                shipped.get_bindings(*action),
+                defaults.get_bindings(*action)
+            );
+            assert_eq!(
+                shipped.get_axis_response(*action),
+                defaults.get_axis_response(*action)
+            );
+        }
+    }
+
+    #[test]
+    fn listed_actions_replace_their_defaults() {
+        let input_map = InputMap::parse(
+            "# Arrows to walk\nmove_forward = Up, W\n\nfire = LControl,MouseLeft\n",
+            "controls",
+        )
+        .unwrap();
+        assert_eq!(
+            input_map.get_bindings(Action::MoveForward),
+            &[Binding::Key(Key::Up), Binding::Key(Key::W)]
+        );
+        assert_eq!(
+            input_map.get_bindings(Action::Fire),
+            &[
+                Binding::Key(Key::LControl),
+                Binding::Mouse(MouseButton::Left)
+            ]
+        );
+        assert_eq!(
+            input_map.get_bindings(Action::Use),
+            &[Binding::Key(Key::Space), Binding::GamepadButton(0)]
+        );
+    }
+
+    #[test]
+    fn errors_give_the_line() {
+        let error = InputMap::parse("run = LShift\njump = Space\n", "controls").unwrap_err();
+        assert_eq!(error, "ERROR: controls:2: unknown action jump");
+        let error = InputMap::parse("run = Shift\n", "controls").unwrap_err();
+        assert_eq!(error, "ERROR: controls:1: unknown binding Shift");
+        let error = InputMap::parse("\nrun LShift\n", "controls").unwrap_err();
+        assert!(error.starts_with("ERROR: controls:2: expected"));
+    }
+
+    #[test]
+    fn rebinding_takes_the_binding_from_other_actions() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(Action::Fire, Binding::Key(Key::Space));
+        let trigger = Binding::GamepadAxis {
+            axis: Axis::R,
+            positive: true,
+        };
+        assert_eq!(
+            input_map.get_bindings(Action::Fire),
+            &[trigger, Binding::Key(Key::Space)]
+        );
+        assert_eq!(
+            input_map.get_bindings(Action::Use),
+            &[Binding::GamepadButton(0)]
+        );
+        input_map.rebind(Action::Run, Binding::Key(Key::LShift));
+        assert_eq!(
+            input_map.get_bindings(Action::Run),
+            &[Binding::GamepadButton(9), Binding::Key(Key::LShift)]
+        );
+        // Rebinding the gamepad keeps the keys
+        input_map.rebind(Action::Fire, Binding::GamepadButton(9));
+        assert_eq!(
+            input_map.get_bindings(Action::Fire),
+            &[Binding::Key(Key::Space), Binding::GamepadButton(9)]
+        );
+        assert_eq!(
+            input_map.get_bindings(Action::Run),
+            &[Binding::Key(Key::LShift)]
+        );
+    }
+
+    #[test]
+    fn gamepad_bindings_have_names() {
+        for name in ["PadButton0", "PadButton31", "PadX+", "PadPovY-", "PadR+"].iter() {
+            let binding = Binding::from_name(name).unwrap();
+            assert!(binding.is_gamepad());
+            assert_eq!(binding.get_name().unwrap(), *name);
+        }
+        for name in ["PadButton32", "PadButton", "PadX", "PadW+", "Pad+"].iter() {
+            assert_eq!(Binding::from_name(name), None, "{}", name);
+        }
+    }
+
+    #[test]
+    fn axis_responses_are_read_and_applied() {
+        let input_map = InputMap::parse(
+            "move_deadzone = 0.5\nlook_curve = 3\nlook_sensitivity = 2\n",
+            "controls",
+        )
+        .unwrap();
+        let walk = input_map.get_axis_response(Action::StrafeLeft).unwrap();
+        assert_eq!(walk.apply(0.4), 0.);
+        assert!((walk.apply(0.75) - 0.5).abs() < 1e-6);
+        assert!((walk.apply(1.) - 1.).abs() < 1e-6);
+        let look = input_map.get_axis_response(Action::TurnRight).unwrap();
+        assert!((look.apply(1.) - 2.).abs() < 1e-6);
+        // Small pushes turn much slower with a steep curve
+        assert!(look.apply(0.5) < 0.5);
+        assert!(input_map.get_axis_response(Action::Fire).is_none());
+
+        let error = InputMap::parse("\nlook_curve = steep\n", "controls").unwrap_err();
+        assert_eq!(
+            error,
+            "ERROR: controls:2: expected a number of 0 or more for look_curve"
+        );
+        assert!(InputMap::parse("move_deadzone = 1\n", "controls").is_err());
+    }
+
+    #[test]
+    fn events_trigger_the_bound_actions() {
+        let mut input_map = InputMap::default();
+        input_map.rebind(Action::Use, Binding::Mouse(MouseButton::Middle));
+        let events = [
+            key_pressed(Key::M),
+            Event::MouseButtonPressed {
+                button: MouseButton::Right,
+                x: 0,
+                y: 0,
+            },
+            Event::MouseButtonReleased {
+                button: MouseButton::Left,
+                x: 0,
+                y: 0,
+            },
+        ];
+        assert!(input_map.is_pressed(Action::ToggleMap, &events));
+        assert!(!input_map.is_pressed(Action::Use, &events));
+        assert!(input_map.is_released(Action::Fire, &events));
+        assert!(!input_map.is_pressed(Action::Fire, &events));
+    }
+}
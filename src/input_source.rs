@@ -0,0 +1,320 @@
+//! Module for the sources of input events: the window, or code driving the game without a
+//! keyboard
+
+use rsfml::{
+    graphics::RenderWindow,
+    window::{
+        joystick::{self, Axis},
+        mouse::{Button as MouseButton, Wheel},
+        Event, Key,
+    },
+};
+
+use crate::input::Binding;
+
+/// Where the [EventHandler](crate::event_handler::EventHandler) reads input from.
+pub trait InputSource {
+    /// Returns the events that happened since the last call.
+    fn poll_events(&mut self) -> Vec<Event>;
+
+    /// Whether a key, mouse button or gamepad button is held down. Wheel moves are never held,
+    /// and axes are read with [InputSource::get_axis_position].
+    fn is_held(&self, binding: &Binding) -> bool;
+
+    /// Returns the position of an axis of the gamepad in use, from -100 to 100, or 0 without
+    /// a gamepad.
+    fn get_axis_position(&self, _axis: Axis) -> f32 {
+        0.
+    }
+}
+
+/// Reads the events of a window, the real keyboard and mouse, and the first gamepad connected.
+pub struct WindowInput<'w> {
+    render_window: &'w mut RenderWindow,
+    gamepad: Option<u32>,
+}
+
+impl<'w> WindowInput<'w> {
+    pub fn new(render_window: &'w mut RenderWindow) -> WindowInput<'w> {
+        WindowInput {
+            render_window,
+            gamepad: None,
+        }
+    }
+}
+
+impl<'w> InputSource for WindowInput<'w> {
+    fn poll_events(&mut self) -> Vec<Event> {
+        let mut events = Vec::new();
+        while let Some(event) = self.render_window.poll_event() {
+            events.push(event);
+        }
+        // Polling updated the state of the gamepads
+        self.gamepad = (0..joystick::COUNT).find(|id| joystick::is_connected(*id));
+        events
+    }
+
+    fn is_held(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::Key(key) => Key::is_pressed(*key),
+            Binding::Mouse(button) => MouseButton::is_pressed(*button),
+            Binding::GamepadButton(button) => {
+                matches!(self.gamepad, Some(id) if joystick::is_button_pressed(id, *button))
+            }
+            Binding::WheelUp | Binding::WheelDown | Binding::GamepadAxis { .. } => false,
+        }
+    }
+
+    fn get_axis_position(&self, axis: Axis) -> f32 {
+        self.gamepad
+            .map_or(0., |id| joystick::axis_position(id, axis))
+    }
+}
+
+/// Input driven by code, e.g. by tests. A key or button stays held from [VirtualInput::press]
+/// to [VirtualInput::release], and both queue the event a window would send. The virtual
+/// gamepad only counts between [VirtualInput::connect_gamepad] and
+/// [VirtualInput::disconnect_gamepad].
+#[derive(Clone, Debug, Default)]
+pub struct VirtualInput {
+    held: Vec<Binding>,
+    axes: Vec<(Axis, f32)>,
+    gamepad_connected: bool,
+    events: Vec<Event>,
+}
+
+impl VirtualInput {
+    pub fn new() -> VirtualInput {
+        VirtualInput::default()
+    }
+
+    /// Presses a key or button, moves the wheel by one notch or pushes a gamepad axis all the
+    /// way.
+    pub fn press(&mut self, binding: Binding) {
+        if let Binding::GamepadAxis { axis, positive } = binding {
+            self.move_axis(axis, if positive { 100. } else { -100. });
+            return;
+        }
+        self.events.push(match binding {
+            Binding::Key(code) => Event::KeyPressed {
+                code,
+                alt: false,
+                ctrl: false,
+                shift: false,
+                system: false,
+            },
+            Binding::Mouse(button) => Event::MouseButtonPressed { button, x: 0, y: 0 },
+            Binding::WheelUp | Binding::WheelDown => Event::MouseWheelScrolled {
+                wheel: Wheel::VerticalWheel,
+                delta: if binding == Binding::WheelUp { 1. } else { -1. },
+                x: 0,
+                y: 0,
+            },
+            Binding::GamepadButton(button) => Event::JoystickButtonPressed {
+                joystickid: 0,
+                button,
+            },
+            Binding::GamepadAxis { .. } => unreachable!(),
+        });
+        if !matches!(binding, Binding::WheelUp | Binding::WheelDown) && !self.is_held(&binding) {
+            self.held.push(binding);
+        }
+    }
+
+    /// Releases a key or button, or lets a gamepad axis go back to the centre.
+    pub fn release(&mut self, binding: Binding) {
+        match binding {
+            Binding::Key(code) => self.events.push(Event::KeyReleased {
+                code,
+                alt: false,
+                ctrl: false,
+                shift: false,
+                system: false,
+            }),
+            Binding::Mouse(button) => {
+                self.events
+                    .push(Event::MouseButtonReleased { button, x: 0, y: 0 })
+            }
+            Binding::GamepadButton(button) => self.events.push(Event::JoystickButtonReleased {
+                joystickid: 0,
+                button,
+            }),
+            Binding::GamepadAxis { axis, .. } => return self.move_axis(axis, 0.),
+            Binding::WheelUp | Binding::WheelDown => return,
+        }
+        self.held.retain(|held| *held != binding);
+    }
+
+    /// Moves a gamepad axis to a position from -100 to 100.
+    pub fn move_axis(&mut self, axis: Axis, position: f32) {
+        self.axes.retain(|(moved, _)| *moved != axis);
+        self.axes.push((axis, position));
+        self.events.push(Event::JoystickMoved {
+            joystickid: 0,
+            axis,
+            position,
+        });
+    }
+
+    /// Plugs the virtual gamepad in.
+    pub fn connect_gamepad(&mut self) {
+        self.gamepad_connected = true;
+        self.events.push(Event::JoystickConnected { joystickid: 0 });
+    }
+
+    /// Unplugs the virtual gamepad, which lets go of its buttons and axes.
+    pub fn disconnect_gamepad(&mut self) {
+        self.gamepad_connected = false;
+        self.held
+            .retain(|held| !matches!(held, Binding::GamepadButton(_)));
+        self.axes.clear();
+        self.events
+            .push(Event::JoystickDisconnected { joystickid: 0 });
+    }
+
+    /// Presses and releases a key or button before the next poll.
+    pub fn tap(&mut self, binding: Binding) {
+        self.press(binding);
+        self.release(binding);
+    }
+
+    /// Queues any other event, like a mouse move.
+    pub fn push_event(&mut self, event: Event) {
+        self.events.push(event);
+    }
+}
+
+impl InputSource for VirtualInput {
+    fn poll_events(&mut self) -> Vec<Event> {
+        std::mem::take(&mut self.events)
+    }
+
+    fn is_held(&self, binding: &Binding) -> bool {
+        match binding {
+            Binding::GamepadButton(_) if !self.gamepad_connected => false,
+            _ => self.held.contains(binding),
+        }
+    }
+
+    fn get_axis_position(&self, axis: Axis) -> f32 {
+        match self.axes.iter().find(|(moved, _)| *moved == axis) {
+            Some((_, position)) if self.gamepad_connected => *position,
+            _ => 0.,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{event_handler::EventHandler, input::Action};
+
+    #[test]
+    fn held_keys_press_once_and_release_once() {
+        let mut input = VirtualInput::new();
+        let mut event_handler = EventHandler::new();
+        input.press(Binding::Key(Key::W));
+        event_handler.update_events(&mut input);
+        assert!(event_handler.is_action_held(Action::MoveForward));
+        assert!(event_handler.has_action_pressed_event(Action::MoveForward));
+
+        event_handler.update_events(&mut input);
+        assert!(event_handler.is_action_held(Action::MoveForward));
+        assert!(!event_handler.has_action_pressed_event(Action::MoveForward));
+
+        input.release(Binding::Key(Key::W));
+        event_handler.update_events(&mut input);
+        assert!(!event_handler.is_action_held(Action::MoveForward));
+        assert!(event_handler.has_action_released_event(Action::MoveForward));
+        event_handler.update_events(&mut input);
+        assert!(!event_handler.has_action_released_event(Action::MoveForward));
+    }
+
+    #[test]
+    fn taps_shorter_than_a_tick_are_not_lost() {
+        let mut input = VirtualInput::new();
+        let mut event_handler = EventHandler::new();
+        input.tap(Binding::Key(Key::M));
+        input.press(Binding::WheelDown);
+        event_handler.update_events(&mut input);
+        assert!(event_handler.has_action_pressed_event(Action::ToggleMap));
+        assert!(event_handler.has_action_released_event(Action::ToggleMap));
+        assert!(!event_handler.is_action_held(Action::ToggleMap));
+        assert!(event_handler.has_action_pressed_event(Action::PreviousWeapon));
+        assert!(!event_handler.is_action_held(Action::PreviousWeapon));
+    }
+
+    #[test]
+    fn held_state_changes_without_events_count_as_presses() {
+        // A key held down while the window got the focus sends no event
+        struct HeldKey;
+        impl InputSource for HeldKey {
+            fn poll_events(&mut self) -> Vec<Event> {
+                Vec::new()
+            }
+            fn is_held(&self, binding: &Binding) -> bool {
+                *binding == Binding::Key(Key::Space)
+            }
+        }
+        let mut event_handler = EventHandler::new();
+        event_handler.update_events(&mut HeldKey);
+        assert!(event_handler.has_action_pressed_event(Action::Use));
+        // Ticks without reading events keep the state but do not repeat the press
+        event_handler.clear_events();
+        assert!(event_handler.is_action_held(Action::Use));
+        assert!(!event_handler.has_action_pressed_event(Action::Use));
+    }
+
+    #[test]
+    fn gamepad_sticks_move_as_far_as_they_are_pushed() {
+        let mut input = VirtualInput::new();
+        let mut event_handler = EventHandler::new();
+        input.connect_gamepad();
+        // Inside the deadzone
+        input.move_axis(Axis::Y, -20.);
+        event_handler.update_events(&mut input);
+        assert!(event_handler.has_gamepad_connected_event());
+        assert_eq!(event_handler.get_action_value(Action::MoveForward), 0.);
+
+        input.move_axis(Axis::Y, -62.5);
+        event_handler.update_events(&mut input);
+        assert!((event_handler.get_action_value(Action::MoveForward) - 0.5).abs() < 1e-6);
+        assert_eq!(event_handler.get_action_value(Action::MoveBackward), 0.);
+        assert!(event_handler.is_action_held(Action::MoveForward));
+        assert!(event_handler.has_action_pressed_event(Action::MoveForward));
+
+        // Keys still count whole
+        input.press(Binding::Key(Key::W));
+        event_handler.update_events(&mut input);
+        assert_eq!(event_handler.get_action_value(Action::MoveForward), 1.);
+    }
+
+    #[test]
+    fn disconnected_gamepads_let_go() {
+        let mut input = VirtualInput::new();
+        let mut event_handler = EventHandler::new();
+        let trigger = Binding::GamepadAxis {
+            axis: Axis::R,
+            positive: true,
+        };
+        // Nothing counts before the gamepad is connected
+        input.press(Binding::GamepadButton(0));
+        event_handler.update_events(&mut input);
+        assert!(!event_handler.is_action_held(Action::Use));
+        input.release(Binding::GamepadButton(0));
+
+        input.connect_gamepad();
+        input.press(trigger);
+        input.press(Binding::GamepadButton(0));
+        event_handler.update_events(&mut input);
+        assert!(event_handler.is_action_held(Action::Fire));
+        assert!(event_handler.has_action_pressed_event(Action::Use));
+
+        input.disconnect_gamepad();
+        event_handler.update_events(&mut input);
+        assert!(event_handler.has_gamepad_disconnected_event());
+        assert!(!event_handler.is_action_held(Action::Fire));
+        assert!(event_handler.has_action_released_event(Action::Fire));
+        assert!(!event_handler.is_action_held(Action::Use));
+    }
+}
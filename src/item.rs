@@ -0,0 +1,188 @@
+//! Module for the items the player picks up
+
+use rsfml::system::Vector2i;
+
+use crate::{
+    player_state::{KeyColor, PlayerState, CHAINGUN, MACHINE_GUN},
+    vswap::FIRST_SPRITE,
+};
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum ItemKind {
+    GoldKey,
+    SilverKey,
+    DogFood,
+    Food,
+    FirstAid,
+    BloodyBones,
+    Gibs,
+    Clip,
+    MachineGun,
+    Chaingun,
+    Cross,
+    Chalice,
+    Chest,
+    Crown,
+    ExtraLife,
+}
+
+/// An item lying in the middle of a tile.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Item {
+    pub kind: ItemKind,
+    pub position: Vector2i,
+}
+
+impl ItemKind {
+    /// Returns the item placed by an object code of the original levels.
+    pub fn from_object(code: u16) -> Option<ItemKind> {
+        match code {
+            29 => Some(ItemKind::DogFood),
+            43 => Some(ItemKind::GoldKey),
+            44 => Some(ItemKind::SilverKey),
+            47 => Some(ItemKind::Food),
+            48 => Some(ItemKind::FirstAid),
+            49 => Some(ItemKind::Clip),
+            50 => Some(ItemKind::MachineGun),
+            51 => Some(ItemKind::Chaingun),
+            52 => Some(ItemKind::Cross),
+            53 => Some(ItemKind::Chalice),
+            54 => Some(ItemKind::Chest),
+            55 => Some(ItemKind::Crown),
+            56 => Some(ItemKind::ExtraLife),
+            57 => Some(ItemKind::BloodyBones),
+            61 => Some(ItemKind::Gibs),
+            _ => None,
+        }
+    }
+
+    /// Returns the sprite of the item, numbered like the sprite pages of the original VSWAP
+    /// files.
+    pub fn get_sprite(&self) -> i32 {
+        let page = match self {
+            ItemKind::DogFood => 8,
+            ItemKind::GoldKey => 22,
+            ItemKind::SilverKey => 23,
+            ItemKind::Food => 26,
+            ItemKind::FirstAid => 27,
+            ItemKind::Clip => 28,
+            ItemKind::MachineGun => 29,
+            ItemKind::Chaingun => 30,
+            ItemKind::Cross => 31,
+            ItemKind::Chalice => 32,
+            ItemKind::Chest => 33,
+            ItemKind::Crown => 34,
+            ItemKind::ExtraLife => 35,
+            ItemKind::BloodyBones => 36,
+            ItemKind::Gibs => 40,
+        };
+        FIRST_SPRITE + page
+    }
+
+    /// Returns the weapon slot the item gives, if any.
+    pub fn get_weapon(&self) -> Option<usize> {
+        match self {
+            ItemKind::MachineGun => Some(MACHINE_GUN),
+            ItemKind::Chaingun => Some(CHAINGUN),
+            _ => None,
+        }
+    }
+
+    /// Returns the message shown when the item is picked up.
+    pub fn get_message(&self) -> &'static str {
+        match self {
+            ItemKind::GoldKey => "Picked up the gold key",
+            ItemKind::SilverKey => "Picked up the silver key",
+            ItemKind::DogFood => "Ate some dog food",
+            ItemKind::Food => "Ate a meal",
+            ItemKind::FirstAid => "Picked up a first aid kit",
+            ItemKind::BloodyBones | ItemKind::Gibs => "Slurp...",
+            ItemKind::Clip => "Picked up an ammo clip",
+            ItemKind::MachineGun => "Picked up a machine gun",
+            ItemKind::Chaingun => "Picked up a chaingun",
+            ItemKind::Cross => "Picked up a cross",
+            ItemKind::Chalice => "Picked up a chalice",
+            ItemKind::Chest => "Picked up a chest of jewels",
+            ItemKind::Crown => "Picked up a crown",
+            ItemKind::ExtraLife => "Extra life!",
+        }
+    }
+
+    /// Applies the effect of the item, with the amounts of the original game. Returns false,
+    /// leaving the player unchanged, when the item is of no use right now.
+    ///
+    /// # Arguments
+    /// `player` - The player picking up the item
+    pub fn pick_up(&self, player: &mut PlayerState) -> bool {
+        match self {
+            ItemKind::GoldKey => player.give_key(KeyColor::Gold),
+            ItemKind::SilverKey => player.give_key(KeyColor::Silver),
+            ItemKind::DogFood => player.heal(4),
+            ItemKind::Food => player.heal(10),
+            ItemKind::FirstAid => player.heal(25),
+            // Only worth it when nearly dead
+            ItemKind::BloodyBones | ItemKind::Gibs => player.get_health() <= 10 && player.heal(1),
+            ItemKind::Clip => player.add_ammo(8),
+            ItemKind::MachineGun | ItemKind::Chaingun => {
+                player.give_weapon(self.get_weapon().unwrap());
+                player.add_ammo(6);
+                true
+            }
+            ItemKind::Cross => {
+                player.add_score(100);
+                true
+            }
+            ItemKind::Chalice => {
+                player.add_score(500);
+                true
+            }
+            ItemKind::Chest => {
+                player.add_score(1000);
+                true
+            }
+            ItemKind::Crown => {
+                player.add_score(5000);
+                true
+            }
+            ItemKind::ExtraLife => {
+                player.heal(100);
+                player.add_ammo(25);
+                player.add_life();
+                true
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn object_codes_use_the_sprites_of_the_original_game() {
+        let table = [
+            (29, ItemKind::DogFood, 8),
+            (43, ItemKind::GoldKey, 22),
+            (44, ItemKind::SilverKey, 23),
+            (47, ItemKind::Food, 26),
+            (48, ItemKind::FirstAid, 27),
+            (49, ItemKind::Clip, 28),
+            (50, ItemKind::MachineGun, 29),
+            (51, ItemKind::Chaingun, 30),
+            (52, ItemKind::Cross, 31),
+            (53, ItemKind::Chalice, 32),
+            (54, ItemKind::Chest, 33),
+            (55, ItemKind::Crown, 34),
+            (56, ItemKind::ExtraLife, 35),
+            (57, ItemKind::BloodyBones, 36),
+            (61, ItemKind::Gibs, 40),
+        ];
+        for &(code, kind, page) in table.iter() {
+            assert_eq!(ItemKind::from_object(code), Some(kind), "object {}", code);
+            assert_eq!(kind.get_sprite(), FIRST_SPRITE + page, "object {}", code);
+        }
+        for code in [0, 23, 28, 58, 60, 62, 124].iter() {
+            assert_eq!(ItemKind::from_object(*code), None, "object {}", code);
+        }
+    }
+}
@@ -1,34 +1,114 @@
 #![allow(non_snake_case)]
 
+pub mod actor;
 pub mod animation;
+pub mod collision;
+pub mod demo;
+pub mod door;
 pub mod event_handler;
 pub mod fps;
+pub mod framebuffer;
 pub mod game;
 pub mod game_mode;
+pub mod gamemaps;
+pub mod hitscan;
 pub mod hud;
+pub mod input;
+pub mod input_source;
+pub mod item;
 pub mod map;
 pub mod mini_map;
+pub mod player_state;
+pub mod push_wall;
+pub mod random;
 pub mod raycasting_engine;
+pub mod save;
+pub mod screenshot;
+pub mod sprite;
 pub mod texture_loader;
+pub mod timestep;
+pub mod vswap;
 pub mod weapon;
 
-use std::error::Error;
-use std::fs;
-use std::path::Path;
-
-use texture_loader::TextureLoader;
+use actor::guard_texture_names;
+use demo::Demo;
+use gamemaps::{GameMaps, WallTable};
+use input::InputMap;
+use map::{Map, Spawn};
+use rsfml::system::Vector2f;
+use save::SaveGame;
+use texture_loader::{ManifestReport, TextureLoader, VswapTextures};
+use vswap::{Palette, Vswap};
+use weapon::{WEAPON_FRAMES, WEAPON_TEXTURE_PREFIXES};
 
 pub const RESOURCES_BASE_PATH: &'static str = "resources";
 
+pub enum MapSource {
+    Ascii(String),
+    GameMaps { data_dir: String, level: usize },
+}
+
+impl MapSource {
+    /// Returns a one line description of the source, which [MapSource::from_name] reads back,
+    /// e.g. to tell demos which level they were recorded on.
+    pub fn get_name(&self) -> String {
+        match self {
+            MapSource::Ascii(path) => format!("map {}", path),
+            MapSource::GameMaps { data_dir, level } => format!("gamemaps {} {}", level, data_dir),
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<MapSource> {
+        let mut parts = name.splitn(2, ' ');
+        match (parts.next()?, parts.next()?) {
+            ("map", path) => Some(MapSource::Ascii(String::from(path))),
+            ("gamemaps", rest) => {
+                let mut parts = rest.splitn(2, ' ');
+                let level = parts.next()?.parse().ok()?;
+                Some(MapSource::GameMaps {
+                    data_dir: String::from(parts.next()?),
+                    level,
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
 pub struct Arguments {
     pub window_dimensions: (u32, u32),
     pub no_ground: bool,
     pub framerate_limit: u32,
+    pub map_source: MapSource,
+    pub vswap_path: Option<String>,
+    pub palette_path: String,
+    pub controls_path: Option<String>,
+    /// Demo file the run is recorded to
+    pub record_path: Option<String>,
+    /// Demo files played instead of the game
+    pub demo_paths: Vec<String>,
+    /// Directory of demos looped in attract mode before the game starts
+    pub attract_dir: Option<String>,
+    /// Save the game starts from, also written and read by the quicksave and quickload keys
+    pub load_path: Option<String>,
+}
+
+/// Arguments of the `render` command, which writes the view from a camera pose to an image.
+pub struct RenderArguments {
+    pub map_source: MapSource,
+    pub vswap_path: Option<String>,
+    pub palette_path: String,
+    pub position: Option<Vector2f>,
+    pub direction: Option<Vector2f>,
+    pub size: (u32, u32),
+    pub output_path: String,
+    pub no_ground: bool,
 }
 
 pub enum ParsedResult {
     Success,
     Parsed(Arguments),
+    Render(RenderArguments),
     Failure(String),
 }
 
@@ -36,100 +116,403 @@ fn display_help() -> () {
     println!("Arguments available for Rustenstein3D:");
     println!("\t-w [window_width] [window_height] : Specify a new size for the window.");
     println!("\t-f, --framerate [framerate_value] : Set the framerate of the game.");
+    println!("\t-m, --map [map_file]\t\t  : Load the level from an ASCII map file.");
+    println!("\t--gamemaps [data_dir] [level]\t  : Load a level from Wolfenstein 3D MAPHEAD/GAMEMAPS files.");
+    println!("\t--vswap [vswap_file]\t\t  : Load wall and sprite graphics from a Wolfenstein 3D VSWAP file.");
+    println!(
+        "\t--palette [palette_file]\t  : Palette used for VSWAP graphics (raw RGB or 8-bit BMP)."
+    );
     println!("\t--noground\t\t\t  : Disable the ground texturing (improve performance).");
+    println!("\t--controls [controls_file]\t  : Read the key bindings from a file (resources/controls.cfg by default).");
+    println!("\t--record [demo_file]\t\t  : Record the input of the run to a demo file.");
+    println!(
+        "\t--playdemo [demo_file]\t\t  : Play a demo back, then quit. Repeat to play several."
+    );
+    println!("\t--attract [demo_dir]\t\t  : Loop the demos of a directory until a key is pressed.");
+    println!("\t--load [save_file]\t\t  : Continue a saved game, quicksaves then go to this file.");
     println!("\t--help\t\t\t\t  : Display this help.");
+    println!();
+    println!("Render a single frame to an image, without opening a window:");
+    println!("\trender [options] -o [image_file]\t  : --map, --gamemaps, --vswap, --palette and --noground work as above.");
+    println!("\t--pos [x],[y]\t\t\t  : Camera position, the map spawn by default.");
+    println!("\t--dir [x],[y]\t\t\t  : Camera direction, the map spawn by default.");
+    println!("\t--size [width]x[height]\t\t  : Size of the image (320x200 by default).");
+}
+
+/// Names of the textures the game cannot run without.
+fn required_textures() -> Vec<String> {
+    let mut names: Vec<String> = ["ground", "sky", "wall_1", "face_1", "face_2", "face_3"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    for weapon in WEAPON_TEXTURE_PREFIXES.iter() {
+        names.extend(WEAPON_FRAMES.map(|frame| format!("{}_{}", weapon, frame)));
+        names.push(format!("{}_shadow", weapon));
+    }
+    names
+}
+
+/// Loads the textures listed in `resources/textures.manifest`.
+pub fn load_texture() -> Result<TextureLoader, ManifestReport> {
+    let texture_loader =
+        TextureLoader::from_manifest(format!("{}/textures.manifest", RESOURCES_BASE_PATH))?;
+    texture_loader.check_names(&required_textures())?;
+    Ok(texture_loader)
+}
+
+/// Loads the textures listed in `resources/textures.manifest` in memory only, for rendering
+/// without a window.
+pub fn load_texture_headless() -> Result<TextureLoader, ManifestReport> {
+    let texture_loader = TextureLoader::from_manifest_headless(format!(
+        "{}/textures.manifest",
+        RESOURCES_BASE_PATH
+    ))?;
+    texture_loader.check_names(&required_textures())?;
+    Ok(texture_loader)
+}
+
+/// Checks that the textures of the enemies and items placed on the map are loaded.
+pub fn check_sprite_textures(
+    map: &Map,
+    texture_loader: &TextureLoader,
+) -> Result<(), ManifestReport> {
+    let mut names: Vec<String> = map
+        .get_items()
+        .iter()
+        .map(|item| format!("sprite_{}", item.kind.get_sprite()))
+        .collect();
+    if !map.get_actor_spawns().is_empty() {
+        names.extend(guard_texture_names());
+    }
+    names.sort();
+    names.dedup();
+    texture_loader.check_names(&names)
+}
+
+/// Loads the pages of a VSWAP file into the texture loader.
+pub fn load_vswap(
+    texture_loader: &mut TextureLoader,
+    vswap_path: &str,
+    palette_path: &str,
+) -> Result<VswapTextures, String> {
+    let palette = Palette::from_file(palette_path).map_err(|err| err.to_string())?;
+    let vswap = Vswap::open(vswap_path).map_err(|err| err.to_string())?;
+    texture_loader.load_vswap(&vswap, &palette)
+}
+
+/// Loads the key bindings from a controls file. Without a file given, the bindings come from
+/// `resources/controls.cfg`, or are the defaults when it does not exist yet.
+pub fn load_controls(controls_path: Option<&str>) -> Result<InputMap, String> {
+    if let Some(path) = controls_path {
+        return InputMap::from_file(path);
+    }
+    let default_path = format!("{}/controls.cfg", RESOURCES_BASE_PATH);
+    if std::path::Path::new(&default_path).is_file() {
+        return InputMap::from_file(default_path);
+    }
+    let mut input_map = InputMap::default();
+    input_map.set_path(default_path);
+    Ok(input_map)
+}
+
+/// Loads demo files and the levels they were recorded on.
+///
+/// # Arguments
+/// `demo_paths` - The demo files
+///
+/// `vswap_walls` - Whether original levels use the walls of a loaded VSWAP file
+pub fn load_demos(demo_paths: &[String], vswap_walls: bool) -> Result<Vec<(Demo, Map)>, String> {
+    let mut demos = Vec::new();
+    for path in demo_paths.iter() {
+        let demo = Demo::from_file(path).map_err(|err| err.to_string())?;
+        let map_source = MapSource::from_name(demo.get_map_name()).ok_or_else(|| {
+            format!(
+                "ERROR: Unknown level {} in demo {}",
+                demo.get_map_name(),
+                path
+            )
+        })?;
+        let map = load_map(&map_source, vswap_walls)?;
+        if !demo.is_recorded_on(&map) {
+            return Err(format!(
+                "ERROR: The level of demo {} changed since it was recorded (or --vswap differs)",
+                path
+            ));
+        }
+        demos.push((demo, map));
+    }
+    Ok(demos)
+}
+
+/// Reads a save file and tells where its level comes from.
+pub fn load_save(save_path: &str) -> Result<(SaveGame, MapSource), String> {
+    let save = SaveGame::from_file(save_path).map_err(|err| err.to_string())?;
+    let map_source = MapSource::from_name(save.get_map_name()).ok_or_else(|| {
+        format!(
+            "ERROR: Unknown level {} in save {}",
+            save.get_map_name(),
+            save_path
+        )
+    })?;
+    Ok((save, map_source))
+}
+
+/// Returns the `.demo` files of a directory, sorted by name.
+pub fn list_demos(demo_dir: &str) -> Result<Vec<String>, String> {
+    let entries = std::fs::read_dir(demo_dir)
+        .map_err(|err| format!("ERROR: Cannot read demo directory {} ({})", demo_dir, err))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().and_then(|extension| extension.to_str()) == Some("demo"))
+        .map(|path| path.to_string_lossy().into_owned())
+        .collect();
+    paths.sort();
+    if paths.is_empty() {
+        return Err(format!("ERROR: No .demo file in {}", demo_dir));
+    }
+    Ok(paths)
+}
+
+/// Loads the level to play.
+///
+/// # Arguments
+/// `map_source` - Where to read the level from
+///
+/// `vswap_walls` - Whether original levels use the walls of a loaded VSWAP file
+pub fn load_map(map_source: &MapSource, vswap_walls: bool) -> Result<Map, String> {
+    match map_source {
+        MapSource::Ascii(path) => Map::from_ascii(path).map_err(|err| err.to_string()),
+        MapSource::GameMaps { data_dir, level } => {
+            let game_maps = GameMaps::open_dir(data_dir).map_err(|err| err.to_string())?;
+            let wall_table = if vswap_walls {
+                WallTable::with_vswap_textures()
+            } else {
+                WallTable::with_builtin_textures()
+            };
+            let level = game_maps
+                .load_level(*level, &wall_table)
+                .map_err(|err| err.to_string())?;
+            Ok(level.map)
+        }
+    }
 }
 
-// TODO: Use this over the giant texture loading block
-fn get_resources_list<P: AsRef<Path>>(path: P) -> Result<Vec<String>, Box<dyn Error>> {
-    let paths = fs::read_dir(path)?;
+/// Renders the frame described by the arguments of the `render` command and writes it to the
+/// output image.
+pub fn render_to_file(arguments: RenderArguments) -> Result<(), String> {
+    let mut map = load_map(&arguments.map_source, arguments.vswap_path.is_some())?;
+    let spawn = *map.get_spawn();
+    let position = arguments.position.unwrap_or(spawn.position);
+    if !map.is_walkable(&position) {
+        return Err(format!(
+            "ERROR: The camera position ({}, {}) is not on an open tile",
+            position.x, position.y
+        ));
+    }
+    let direction = arguments.direction.unwrap_or(spawn.direction);
+    let length = (direction.x * direction.x + direction.y * direction.y).sqrt();
+    if length == 0. {
+        return Err(String::from("ERROR: The camera direction cannot be 0,0"));
+    }
+    map.set_spawn(Spawn::new(position, direction / length));
+
+    let mut texture_loader = load_texture_headless().map_err(|report| {
+        eprint!("{}", report);
+        format!("ERROR: {} texture(s) failed to load", report.errors.len())
+    })?;
+    if let Some(vswap_path) = &arguments.vswap_path {
+        load_vswap(&mut texture_loader, vswap_path, &arguments.palette_path)?;
+    }
+    check_sprite_textures(&map, &texture_loader).map_err(|report| {
+        eprint!("{}", report);
+        String::from(
+            "ERROR: The map has enemies or items but their sprites are not loaded (see --vswap)",
+        )
+    })?;
 
-    let mut resource_list = Vec::new();
+    let frame = screenshot::render_view(map, &texture_loader, arguments.size, arguments.no_ground);
+    screenshot::save_frame(&frame, &arguments.output_path)
+}
 
-    for path in paths {
-        let path_name = path?.path();
-        if path_name.is_dir() {
-            let mut sub_resources = get_resources_list(path_name)?;
-            resource_list.append(&mut sub_resources);
-        } else {
-            let extension = path_name
-                .extension()
-                .expect("ERROR: Cannot get file extension.");
-            if extension != "wav" && extension != "ttf" {
-                resource_list.push(path_name.display().to_string());
+/// Parses two values separated by `separator`, like `22,12` or `320x200`.
+fn parse_pair<T: std::str::FromStr>(value: &str, separator: char) -> Option<(T, T)> {
+    let mut parts = value.splitn(2, separator);
+    let first = parts.next()?.trim().parse().ok()?;
+    let second = parts.next()?.trim().parse().ok()?;
+    Some((first, second))
+}
+
+/// Parses the options choosing the level and its graphics, shared by the game and the
+/// `render` command. Returns how many values following the option were consumed, or None
+/// when `args[i_args]` is not one of these options.
+fn parse_level_option(
+    args: &[String],
+    i_args: usize,
+    map_source: &mut MapSource,
+    vswap_path: &mut Option<String>,
+    palette_path: &mut String,
+) -> Result<Option<usize>, String> {
+    let arg = &args[i_args];
+    let arg_length = args.len();
+    match arg.as_str() {
+        "-m" | "--map" => {
+            if i_args + 1 >= arg_length {
+                return Err(String::from("ERROR: Missing argument for --map option."));
+            }
+            *map_source = MapSource::Ascii(args[i_args + 1].clone());
+            Ok(Some(1))
+        }
+        "--gamemaps" => {
+            if i_args + 2 >= arg_length {
+                return Err(String::from(
+                    "ERROR: Missing arguments for --gamemaps option.",
+                ));
             }
+            let level = args[i_args + 2].parse().map_err(|_| {
+                format!(
+                    "ERROR: Unable to parse level number for --gamemaps ({})",
+                    args[i_args + 2]
+                )
+            })?;
+            *map_source = MapSource::GameMaps {
+                data_dir: args[i_args + 1].clone(),
+                level,
+            };
+            Ok(Some(2))
         }
+        "--vswap" | "--palette" => {
+            if i_args + 1 >= arg_length {
+                return Err(format!("ERROR: Missing argument for {} option.", arg));
+            }
+            if arg == "--vswap" {
+                *vswap_path = Some(args[i_args + 1].clone());
+            } else {
+                *palette_path = args[i_args + 1].clone();
+            }
+            Ok(Some(1))
+        }
+        _ => Ok(None),
     }
+}
 
-    Ok(resource_list)
-}
-
-pub fn load_texture() -> Result<TextureLoader, Box<dyn Error>> {
-    let mut texture_loader = TextureLoader::new();
-    if texture_loader.load_texture("resources/ground.tga").is_err() || // 0
-       texture_loader.load_texture("resources/1.tga").is_err() || // 1
-       texture_loader.load_texture("resources/2.tga").is_err() || // 2
-       texture_loader.load_texture("resources/3.tga").is_err() || // 3
-       texture_loader.load_texture("resources/4.tga").is_err() || // 4
-       texture_loader.load_texture("resources/5.tga").is_err() || // 5
-       texture_loader.load_texture("resources/6.tga").is_err() || // 6
-       texture_loader.load_texture("resources/7.tga").is_err() || // 7
-       texture_loader.load_texture("resources/8.tga").is_err() || // 8
-       texture_loader.load_texture("resources/9.tga").is_err() || // 9
-       texture_loader.load_texture("resources/10.tga").is_err() || // 10
-       texture_loader.load_texture("resources/sky.tga").is_err() || // 11
-       texture_loader.load_texture("resources/weapons/gun_1.png").is_err() || // 12
-       texture_loader.load_texture("resources/weapons/gun_2.png").is_err() || // 13
-       texture_loader.load_texture("resources/weapons/gun_3.png").is_err() || // 14
-       texture_loader.load_texture("resources/weapons/gun_4.png").is_err() || // 15
-       texture_loader.load_texture("resources/weapons/gun_5.png").is_err() || // 16
-       texture_loader.load_texture("resources/weapons/gun_6.png").is_err() || // 17
-       texture_loader.load_texture("resources/weapons/gun_shadow.png").is_err() || // 18
-       texture_loader.load_texture("resources/weapons/gun2_1.png").is_err() || // 19
-       texture_loader.load_texture("resources/weapons/gun2_2.png").is_err() || // 20
-       texture_loader.load_texture("resources/weapons/gun2_3.png").is_err() || // 21
-       texture_loader.load_texture("resources/weapons/gun2_4.png").is_err() || // 22
-       texture_loader.load_texture("resources/weapons/gun2_5.png").is_err() || // 23
-       texture_loader.load_texture("resources/weapons/gun2_6.png").is_err() || // 24
-       texture_loader.load_texture("resources/weapons/gun2_shadow.png").is_err() || // 25
-       texture_loader.load_texture("resources/weapons/gun3_1.png").is_err() || // 26
-       texture_loader.load_texture("resources/weapons/gun3_2.png").is_err() || // 27
-       texture_loader.load_texture("resources/weapons/gun3_3.png").is_err() || // 28
-       texture_loader.load_texture("resources/weapons/gun3_4.png").is_err() || // 29
-       texture_loader.load_texture("resources/weapons/gun3_2.png").is_err() || // 27
-       texture_loader.load_texture("resources/weapons/gun3_3.png").is_err() || // 28
-       texture_loader.load_texture("resources/weapons/gun3_4.png").is_err() || // 29
-       texture_loader.load_texture("resources/weapons/gun3_5.png").is_err() || // 30
-       texture_loader.load_texture("resources/weapons/gun3_6.png").is_err() || // 31
-       texture_loader.load_texture("resources/weapons/gun3_shadow.png").is_err() || // 32
-       texture_loader.load_texture("resources/weapons/cut_1.png").is_err() || // 33
-       texture_loader.load_texture("resources/weapons/cut_2.png").is_err() || // 34
-       texture_loader.load_texture("resources/weapons/cut_3.png").is_err() || // 35
-       texture_loader.load_texture("resources/weapons/cut_4.png").is_err() || // 36
-       texture_loader.load_texture("resources/weapons/cut_5.png").is_err() || // 37
-       texture_loader.load_texture("resources/weapons/cut_6.png").is_err() || //38
-       texture_loader.load_texture("resources/weapons/cut_shadow.png").is_err() || // 39
-       texture_loader.load_texture("resources/face1.png").is_err() || //40
-       texture_loader.load_texture("resources/face2.png").is_err() || //41
-       texture_loader.load_texture("resources/face3.png").is_err()
-    {
-        panic!("Failed to load textures");
+/// Parses the arguments following `render`.
+fn parse_render_arguments(args: &[String]) -> ParsedResult {
+    let arg_length = args.len();
+    let mut arguments = RenderArguments {
+        map_source: MapSource::Ascii(format!("{}/maps/level1.map", RESOURCES_BASE_PATH)),
+        vswap_path: None,
+        palette_path: format!("{}/SPR_CHAINATK3.bmp", RESOURCES_BASE_PATH),
+        position: None,
+        direction: None,
+        size: (320, 200),
+        output_path: String::from("render.png"),
+        no_ground: false,
+    };
+
+    let mut i_args = 0;
+    while i_args < arg_length {
+        match parse_level_option(
+            args,
+            i_args,
+            &mut arguments.map_source,
+            &mut arguments.vswap_path,
+            &mut arguments.palette_path,
+        ) {
+            Ok(Some(consumed)) => {
+                i_args += consumed + 1;
+                continue;
+            }
+            Ok(None) => (),
+            Err(err) => return ParsedResult::Failure(err),
+        }
+        let arg = &args[i_args];
+        if arg == "--noground" {
+            arguments.no_ground = true;
+            i_args += 1;
+            continue;
+        }
+        let value = match args.get(i_args + 1) {
+            Some(value) => value,
+            None => {
+                return ParsedResult::Failure(format!(
+                    "ERROR: Missing argument for {} option.",
+                    arg
+                ))
+            }
+        };
+        match arg.as_str() {
+            "--pos" | "--dir" => match parse_pair::<f32>(value, ',') {
+                Some((x, y)) if arg == "--pos" => arguments.position = Some(Vector2f::new(x, y)),
+                Some((x, y)) => arguments.direction = Some(Vector2f::new(x, y)),
+                None => {
+                    return ParsedResult::Failure(format!(
+                        "ERROR: Unable to parse value for {} ({}), expected x,y",
+                        arg, value
+                    ))
+                }
+            },
+            "--size" => match parse_pair::<u32>(value, 'x') {
+                Some((width, height)) if width > 0 && height > 0 => {
+                    arguments.size = (width, height)
+                }
+                _ => {
+                    return ParsedResult::Failure(format!(
+                        "ERROR: Unable to parse value for --size ({}), expected widthxheight",
+                        value
+                    ))
+                }
+            },
+            "-o" | "--output" => arguments.output_path = value.clone(),
+            _ => {
+                return ParsedResult::Failure(format!(
+                    "ERROR: Unknown argument for render ({}).",
+                    arg
+                ))
+            }
+        }
+        i_args += 2;
     }
-    Ok(texture_loader)
+    ParsedResult::Render(arguments)
 }
 
 pub fn parse_arguments() -> ParsedResult {
     let args = std::env::args().collect::<Vec<String>>();
+    if args.get(1).map(String::as_str) == Some("render") {
+        return parse_render_arguments(&args[2..]);
+    }
     let arg_length = args.len();
 
     let mut arguments = Arguments {
         window_dimensions: (768, 480),
         no_ground: false,
         framerate_limit: 30,
+        map_source: MapSource::Ascii(format!("{}/maps/level1.map", RESOURCES_BASE_PATH)),
+        vswap_path: None,
+        palette_path: format!("{}/SPR_CHAINATK3.bmp", RESOURCES_BASE_PATH),
+        controls_path: None,
+        record_path: None,
+        demo_paths: Vec::new(),
+        attract_dir: None,
+        load_path: None,
     };
 
     let mut i_args = 1;
     while i_args < arg_length {
+        match parse_level_option(
+            &args,
+            i_args,
+            &mut arguments.map_source,
+            &mut arguments.vswap_path,
+            &mut arguments.palette_path,
+        ) {
+            Ok(Some(consumed)) => {
+                i_args += consumed + 1;
+                continue;
+            }
+            Ok(None) => (),
+            Err(err) => return ParsedResult::Failure(err),
+        }
         let arg = &args[i_args];
         match arg.as_str() {
             "--help" => {
@@ -137,6 +520,31 @@ pub fn parse_arguments() -> ParsedResult {
                 return ParsedResult::Success;
             }
             "--noground" => arguments.no_ground = true,
+            "--controls" => {
+                if i_args + 1 >= arg_length {
+                    return ParsedResult::Failure(String::from(
+                        "ERROR: Missing argument for --controls option.",
+                    ));
+                }
+                arguments.controls_path = Some(args[i_args + 1].clone());
+                i_args += 1;
+            }
+            "--record" | "--playdemo" | "--attract" | "--load" => {
+                if i_args + 1 >= arg_length {
+                    return ParsedResult::Failure(format!(
+                        "ERROR: Missing argument for {} option.",
+                        arg
+                    ));
+                }
+                let value = args[i_args + 1].clone();
+                match arg.as_str() {
+                    "--record" => arguments.record_path = Some(value),
+                    "--playdemo" => arguments.demo_paths.push(value),
+                    "--load" => arguments.load_path = Some(value),
+                    _ => arguments.attract_dir = Some(value),
+                }
+                i_args += 1;
+            }
             "-f" | "--framerate" => {
                 if i_args + 1 >= arg_length {
                     panic!("ERROR: Missing argument for --framerate option.");
@@ -6,7 +6,11 @@ use rsfml::{
     window::{ContextSettings, Style, VideoMode},
 };
 use rustenstein3D::game::GameLoop;
-use rustenstein3D::{load_texture, parse_arguments, Arguments, ParsedResult, RESOURCES_BASE_PATH};
+use rustenstein3D::{
+    check_sprite_textures, list_demos, load_controls, load_demos, load_map, load_save,
+    load_texture, load_vswap, parse_arguments, render_to_file, Arguments, ParsedResult,
+    RESOURCES_BASE_PATH,
+};
 
 #[cfg(target_os = "macos")]
 #[start]
@@ -19,12 +23,51 @@ fn main() -> Result<(), String> {
         window_dimensions: (width, height),
         framerate_limit,
         no_ground,
+        map_source,
+        vswap_path,
+        palette_path,
+        controls_path,
+        record_path,
+        mut demo_paths,
+        attract_dir,
+        load_path,
     } = match parse_arguments() {
         ParsedResult::Success => return Ok(()),
         ParsedResult::Failure(err) => return Err(err),
+        ParsedResult::Render(arguments) => return render_to_file(arguments),
         ParsedResult::Parsed(value) => value,
     };
 
+    if load_path.is_some()
+        && (record_path.is_some() || !demo_paths.is_empty() || attract_dir.is_some())
+    {
+        return Err(String::from(
+            "ERROR: --load cannot be used with --record, --playdemo or --attract",
+        ));
+    }
+    // A save is played on the level it was saved on
+    let (save, map_source) = match &load_path {
+        Some(load_path) => {
+            let (save, map_source) = load_save(load_path)?;
+            (Some(save), map_source)
+        }
+        None => (None, map_source),
+    };
+
+    // Load the level before opening the window so map errors are reported straight away.
+    let map = load_map(&map_source, vswap_path.is_some())?;
+    let input_map = load_controls(controls_path.as_deref())?;
+    if record_path.is_some() && !demo_paths.is_empty() {
+        return Err(String::from(
+            "ERROR: --record cannot be used while playing demos with --playdemo",
+        ));
+    }
+    let attract = attract_dir.is_some();
+    if let Some(attract_dir) = attract_dir {
+        demo_paths.extend(list_demos(&attract_dir)?);
+    }
+    let demos = load_demos(&demo_paths, vswap_path.is_some())?;
+
     // Create the render_window.
     let settings = ContextSettings::default();
     let video_mode = VideoMode::new(width, height, 32);
@@ -39,19 +82,46 @@ fn main() -> Result<(), String> {
     // set the mouse positon on the center of the window
     render_window.set_mouse_position(Vector2i::new(width as i32 / 2, height as i32 / 2));
 
-    // Create the font for the FPS_handler.
+    // Create the font for the status bar and the FPS_handler.
     let font = Font::from_file(&format!("{}/sansation.ttf", RESOURCES_BASE_PATH))
         .ok_or("ERROR: Cannot load font! Font (resources/sansation.ttf) does not exist!")?;
 
     // Create the texture loader and load textures
-    let texture_loader = match load_texture() {
-        Ok(tl) => tl,
-        Err(err) => return Err(err.to_string()),
-    };
+    let mut texture_loader = load_texture().map_err(|report| {
+        eprint!("{}", report);
+        format!("ERROR: {} texture(s) failed to load", report.errors.len())
+    })?;
+    if let Some(vswap_path) = vswap_path {
+        load_vswap(&mut texture_loader, &vswap_path, &palette_path)?;
+    }
+    for map in std::iter::once(&map).chain(demos.iter().map(|(_, map)| map)) {
+        check_sprite_textures(map, &texture_loader).map_err(|report| {
+            eprint!("{}", report);
+            String::from(
+                "ERROR: The map has enemies or items but their sprites are not loaded (see --vswap)",
+            )
+        })?;
+    }
 
     // Create the game_loop and activate the fps handler.
-    let mut game_loop = GameLoop::new(render_window, &texture_loader, no_ground);
+    let mut game_loop = GameLoop::new(
+        render_window,
+        &texture_loader,
+        &font,
+        map,
+        &map_source.get_name(),
+        no_ground,
+        input_map,
+    );
     game_loop.activate_FPS(&font);
+    if let Some(record_path) = record_path {
+        game_loop.record_demo(&record_path);
+    }
+    if let (Some(save), Some(load_path)) = (save, load_path) {
+        game_loop.load_game(&save).map_err(|err| err.to_string())?;
+        game_loop.set_save_path(&load_path);
+    }
+    game_loop.play_demos(demos, attract);
 
     game_loop.run();
     Ok(())
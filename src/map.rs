@@ -1,10 +1,106 @@
 //! Module for the world map data
-use rsfml::system::{Vector2f, Vector2i};
+use std::{collections::HashMap, fmt, fs, path::Path};
+
+use rsfml::{
+    graphics::FloatRect,
+    system::{Vector2f, Vector2i},
+};
+
+use crate::{
+    door::Door,
+    item::{Item, ItemKind},
+    player_state::KeyColor,
+    push_wall::{PushWall, PUSH_WALL_DISTANCE},
+    raycasting_engine,
+    save::{SaveError, StateReader, StateWriter},
+};
 
 #[derive(Clone)]
 pub struct Map {
     map: Vec<i32>,
     map_size: Vector2i,
+    tile_info: Vec<TileInfo>,
+    spawn: Spawn,
+    doors: Vec<Door>,
+    // Index in `doors` of the door standing on each tile
+    door_ids: Vec<Option<usize>>,
+    // Secret walls that have not been pushed yet
+    push_walls: Vec<bool>,
+    moving_wall: Option<PushWall>,
+    secret_count: u32,
+    secrets_found: u32,
+    decorations: Vec<Decoration>,
+    actor_spawns: Vec<ActorSpawn>,
+    items: Vec<Item>,
+    floor: i32,
+}
+
+/// What the map knows about a tile besides its block ID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct TileInfo {
+    /// The key needed to open the door on the tile
+    pub lock: Option<KeyColor>,
+}
+
+/// The outcome of [Map::use_door].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DoorUse {
+    NoDoor,
+    Used,
+    /// The door stays shut without this key
+    Locked(KeyColor),
+}
+
+/// A sprite standing in the middle of a tile, drawn with the texture named `sprite_<sprite>`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Decoration {
+    pub sprite: i32,
+    pub position: Vector2i,
+}
+
+/// The solid tile stopping a ray cast with [Map::cast_ray].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WallHit {
+    pub tile: Vector2i,
+    pub distance: f32,
+}
+
+/// Where an enemy guard starts, standing still or patrolling.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ActorSpawn {
+    pub position: Vector2i,
+    pub direction: Vector2f,
+    pub patrol: bool,
+}
+
+/// Starting position and facing direction of the player.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Spawn {
+    pub position: Vector2f,
+    pub direction: Vector2f,
+}
+
+/// Error raised while loading a map file.
+///
+/// `line` and `column` are 1-based and point at the offending character, or are 0 when the
+/// error is not tied to a location in the file (e.g. the file could not be read).
+#[derive(Debug, Clone, PartialEq)]
+pub struct MapError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+/// What a legend character stands for, besides its block ID.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum LegendKind {
+    Wall,
+    Door(Option<KeyColor>),
+    PushWall,
+    Sprite,
+    Guard,
+    Patrol,
+    Item,
 }
 
 pub enum Orientation {
@@ -18,17 +114,345 @@ pub enum Orientation {
     BottomRight,
 }
 
+impl Spawn {
+    pub fn new(position: Vector2f, direction: Vector2f) -> Spawn {
+        Spawn {
+            position,
+            direction,
+        }
+    }
+}
+
+impl MapError {
+    fn new(line: usize, column: usize, message: String) -> MapError {
+        MapError {
+            line,
+            column,
+            message,
+        }
+    }
+}
+
+impl fmt::Display for MapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "ERROR: {}", self.message)
+        } else {
+            write!(
+                f,
+                "ERROR: {} (line {}, column {})",
+                self.message, self.line, self.column
+            )
+        }
+    }
+}
+
+impl std::error::Error for MapError {}
+
 impl Map {
     pub fn new(map: Vec<i32>, map_size: &Vector2f) -> Map {
+        let tile_count = map.len();
         Map {
             map,
             map_size: Vector2i {
                 x: map_size.x as i32,
                 y: map_size.y as i32,
             },
+            tile_info: vec![TileInfo::default(); tile_count],
+            spawn: Spawn::new(Vector2f::new(22., 12.), Vector2f::new(-1., 0.)),
+            doors: Vec::new(),
+            door_ids: vec![None; tile_count],
+            push_walls: vec![false; tile_count],
+            moving_wall: None,
+            secret_count: 0,
+            secrets_found: 0,
+            decorations: Vec::new(),
+            actor_spawns: Vec::new(),
+            items: Vec::new(),
+            floor: 1,
         }
     }
 
+    /// Loads a map from a plain-text ASCII file.
+    ///
+    /// The file starts with a legend header of `<char> = <block id>` lines, followed by a
+    /// `map:` line and the grid itself, one character per tile. `.` and spaces are empty
+    /// tiles, and one of `<`, `>`, `^` or `v` marks the player spawn and the direction it faces.
+    /// Lines starting with `;` in the header are comments.
+    ///
+    /// A legend entry ending with `door` (`D = 3 door`) places sliding doors drawn with the
+    /// block's texture. Doors must sit between two walls. `door gold` and `door silver` make
+    /// doors locked with that key. Entries ending with `pushwall`
+    /// (`S = 1 pushwall`) are secret walls that slide away when pushed, and entries ending with
+    /// `sprite` (`o = 1 sprite`) place a decoration using that sprite number on an empty tile.
+    /// Entries ending with `guard` or `patrol` place a standing or patrolling enemy guard facing
+    /// north (1), east (2), south (3) or west (4). Entries ending with `item` (`k = 43 item`)
+    /// place the item of that original object code, see [ItemKind::from_object].
+    ///
+    /// # Arguments
+    /// `path` - The path of the map file to load
+    pub fn from_ascii<P: AsRef<Path>>(path: P) -> Result<Map, MapError> {
+        let path = path.as_ref();
+        let contents = fs::read_to_string(path).map_err(|err| {
+            MapError::new(
+                0,
+                0,
+                format!("Cannot read map file {} ({})", path.display(), err),
+            )
+        })?;
+        Map::parse_ascii(&contents)
+    }
+
+    /// Parses the contents of an ASCII map file. See [Map::from_ascii] for the format.
+    pub fn parse_ascii(contents: &str) -> Result<Map, MapError> {
+        let mut legend: HashMap<char, (i32, LegendKind)> = HashMap::new();
+        let mut lines = contents.lines().enumerate();
+
+        let mut found_grid = false;
+        for (line_idx, line) in lines.by_ref() {
+            let line_number = line_idx + 1;
+            let trimmed = line.trim();
+            if trimmed.is_empty() || trimmed.starts_with(';') {
+                continue;
+            }
+            if trimmed == "map:" {
+                found_grid = true;
+                break;
+            }
+            let (tile, entry) = Map::parse_legend_entry(line, line_number)?;
+            legend.insert(tile, entry);
+        }
+        if !found_grid {
+            return Err(MapError::new(
+                0,
+                0,
+                String::from("Missing `map:` line before the tile grid"),
+            ));
+        }
+
+        let mut map = Vec::new();
+        let mut width = 0;
+        let mut height = 0;
+        let mut spawn = None;
+        let mut first_row = 0;
+        let mut special_tiles = Vec::new();
+        for (line_idx, line) in lines {
+            let line_number = line_idx + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if height == 0 {
+                first_row = line_number;
+                width = line.chars().count();
+            } else if line.chars().count() != width {
+                return Err(MapError::new(
+                    line_number,
+                    line.chars().count().min(width) + 1,
+                    format!(
+                        "Row is {} tiles wide, expected {}",
+                        line.chars().count(),
+                        width
+                    ),
+                ));
+            }
+            for (column_idx, tile) in line.chars().enumerate() {
+                let direction = match tile {
+                    '<' => Some(Vector2f::new(-1., 0.)),
+                    '>' => Some(Vector2f::new(1., 0.)),
+                    '^' => Some(Vector2f::new(0., -1.)),
+                    'v' => Some(Vector2f::new(0., 1.)),
+                    _ => None,
+                };
+                let block = match (tile, direction) {
+                    (_, Some(direction)) => {
+                        if spawn.is_some() {
+                            return Err(MapError::new(
+                                line_number,
+                                column_idx + 1,
+                                String::from("Duplicate player spawn"),
+                            ));
+                        }
+                        spawn = Some(Spawn::new(
+                            Vector2f::new(column_idx as f32 + 0.5, height as f32 + 0.5),
+                            direction,
+                        ));
+                        0
+                    }
+                    ('.', _) | (' ', _) => 0,
+                    _ => {
+                        let (block, kind) = *legend.get(&tile).ok_or_else(|| {
+                            MapError::new(
+                                line_number,
+                                column_idx + 1,
+                                format!("Unknown tile '{}' (missing from legend)", tile),
+                            )
+                        })?;
+                        if kind != LegendKind::Wall {
+                            special_tiles.push((
+                                Vector2i::new(column_idx as i32, height as i32),
+                                line_number,
+                                block,
+                                kind,
+                            ));
+                        }
+                        match kind {
+                            LegendKind::Wall | LegendKind::Door(_) | LegendKind::PushWall => block,
+                            LegendKind::Sprite
+                            | LegendKind::Guard
+                            | LegendKind::Patrol
+                            | LegendKind::Item => 0,
+                        }
+                    }
+                };
+                map.push(block);
+            }
+            height += 1;
+        }
+
+        if height == 0 || width == 0 {
+            return Err(MapError::new(0, 0, String::from("Map grid is empty")));
+        }
+        let spawn = spawn.ok_or_else(|| {
+            MapError::new(
+                0,
+                0,
+                String::from("Missing player spawn ('<', '>', '^' or 'v')"),
+            )
+        })?;
+
+        // Rays and movement assume the level is closed, so every border tile must be a wall.
+        for y in 0..height {
+            for x in 0..width {
+                let on_border = x == 0 || y == 0 || x == width - 1 || y == height - 1;
+                if on_border && map[y * width + x] == 0 {
+                    return Err(MapError::new(
+                        first_row + y,
+                        x + 1,
+                        String::from("Map border must be a wall"),
+                    ));
+                }
+            }
+        }
+
+        let mut result = Map::new(map, &Vector2f::new(width as f32, height as f32));
+        result.set_spawn(spawn);
+        for (position, line_number, block, kind) in special_tiles {
+            match kind {
+                LegendKind::Door(lock) => result.add_door(position, lock),
+                LegendKind::PushWall => result.add_push_wall(position),
+                LegendKind::Sprite => {
+                    result.add_decoration(Decoration {
+                        sprite: block,
+                        position,
+                    });
+                    Ok(())
+                }
+                LegendKind::Guard | LegendKind::Patrol => {
+                    let direction = match block {
+                        1 => Vector2f::new(0., -1.),
+                        2 => Vector2f::new(1., 0.),
+                        3 => Vector2f::new(0., 1.),
+                        4 => Vector2f::new(-1., 0.),
+                        _ => {
+                            return Err(MapError::new(
+                                line_number,
+                                position.x as usize + 1,
+                                format!("Invalid guard direction {} (1 to 4)", block),
+                            ))
+                        }
+                    };
+                    result.add_actor_spawn(ActorSpawn {
+                        position,
+                        direction,
+                        patrol: kind == LegendKind::Patrol,
+                    });
+                    Ok(())
+                }
+                LegendKind::Item => match ItemKind::from_object(block as u16) {
+                    Some(kind) => {
+                        result.add_item(Item { kind, position });
+                        Ok(())
+                    }
+                    None => Err(format!("Unknown item object code {}", block)),
+                },
+                LegendKind::Wall => Ok(()),
+            }
+            .map_err(|message| MapError::new(line_number, position.x as usize + 1, message))?;
+        }
+        Ok(result)
+    }
+
+    fn parse_legend_entry(
+        line: &str,
+        line_number: usize,
+    ) -> Result<(char, (i32, LegendKind)), MapError> {
+        let separator = line.find('=').ok_or_else(|| {
+            MapError::new(
+                line_number,
+                1,
+                String::from("Expected a legend entry `<char> = <block id>`"),
+            )
+        })?;
+        let (tile_part, block_part) = (&line[..separator], &line[separator + 1..]);
+
+        let mut tile_chars = tile_part.trim().chars();
+        let tile = match (tile_chars.next(), tile_chars.next()) {
+            (Some(tile), None) => tile,
+            _ => {
+                return Err(MapError::new(
+                    line_number,
+                    1,
+                    String::from("Legend key must be a single character"),
+                ))
+            }
+        };
+        if matches!(tile, '.' | '<' | '>' | '^' | 'v') {
+            return Err(MapError::new(
+                line_number,
+                1,
+                format!("Tile '{}' is reserved and cannot be redefined", tile),
+            ));
+        }
+
+        let block_column = separator + 2 + (block_part.len() - block_part.trim_start().len());
+        let mut words = block_part.split_whitespace();
+        let block_word = words.next().unwrap_or("");
+        let block = match block_word.parse::<i32>() {
+            Ok(block) if block > 0 => block,
+            _ => {
+                return Err(MapError::new(
+                    line_number,
+                    block_column,
+                    format!("Invalid block ID '{}' for tile '{}'", block_word, tile),
+                ))
+            }
+        };
+        let kind_words: Vec<&str> = words.collect();
+        let kind = match kind_words.as_slice() {
+            [] => LegendKind::Wall,
+            ["door"] => LegendKind::Door(None),
+            ["door", "gold"] => LegendKind::Door(Some(KeyColor::Gold)),
+            ["door", "silver"] => LegendKind::Door(Some(KeyColor::Silver)),
+            ["pushwall"] => LegendKind::PushWall,
+            ["sprite"] => LegendKind::Sprite,
+            ["guard"] => LegendKind::Guard,
+            ["patrol"] => LegendKind::Patrol,
+            ["item"] => LegendKind::Item,
+            _ => {
+                return Err(MapError::new(
+                    line_number,
+                    block_column,
+                    format!(
+                        "Unknown tile kind '{}' for tile '{}'",
+                        kind_words.join(" "),
+                        tile
+                    ),
+                ))
+            }
+        };
+        Ok((tile, (block, kind)))
+    }
+
     pub fn get_block_with_orientation(
         &self,
         block_orientation: Orientation,
@@ -48,9 +472,9 @@ impl Map {
 
     pub fn get_block(&self, position: &Vector2i) -> Option<i32> {
         if position.x >= 0
-            && position.x <= self.map_size.x
+            && position.x < self.map_size.x
             && position.y >= 0
-            && position.y <= self.map_size.y
+            && position.y < self.map_size.y
         {
             Some(self.map[(position.y * self.map_size.x + position.x) as usize])
         } else {
@@ -62,7 +486,528 @@ impl Map {
         &self.map_size
     }
 
+    pub fn get_spawn(&self) -> &Spawn {
+        &self.spawn
+    }
+
+    pub fn set_spawn(&mut self, spawn: Spawn) {
+        self.spawn = spawn;
+    }
+
+    /// Turns a block of the map into a closed door. The door slides along the two walls it sits
+    /// between.
+    ///
+    /// # Arguments
+    /// `position` - The tile of the door
+    ///
+    /// `lock` - The key the player needs to open the door, if any
+    pub fn add_door(&mut self, position: Vector2i, lock: Option<KeyColor>) -> Result<(), String> {
+        let is_wall = |offset: Vector2i| {
+            let neighbour = position + offset;
+            matches!(self.get_block(&neighbour), Some(block) if block != 0)
+                && self.get_door(&neighbour).is_none()
+        };
+        let vertical = if is_wall(Vector2i::new(0, -1)) && is_wall(Vector2i::new(0, 1)) {
+            true
+        } else if is_wall(Vector2i::new(-1, 0)) && is_wall(Vector2i::new(1, 0)) {
+            false
+        } else {
+            return Err(String::from("Door must sit between two walls"));
+        };
+        let tile_idx = self
+            .tile_index(&position)
+            .ok_or_else(|| String::from("Door is outside of the map"))?;
+        self.door_ids[tile_idx] = Some(self.doors.len());
+        self.doors.push(Door::new(position, vertical));
+        self.tile_info[tile_idx].lock = lock;
+        Ok(())
+    }
+
+    pub fn get_tile_info(&self, position: &Vector2i) -> Option<&TileInfo> {
+        Some(&self.tile_info[self.tile_index(position)?])
+    }
+
+    pub fn get_door(&self, position: &Vector2i) -> Option<&Door> {
+        let door_id = self.door_ids[self.tile_index(position)?]?;
+        Some(&self.doors[door_id])
+    }
+
+    pub fn get_doors(&self) -> &[Door] {
+        &self.doors
+    }
+
+    /// Opens or closes the door on a tile, if the player holds its key.
+    ///
+    /// # Arguments
+    /// `position` - The tile of the door
+    ///
+    /// `occupied` - Tiles where something stands, a door does not close on them
+    ///
+    /// `has_key` - Whether the player holds a key
+    pub fn use_door<F: Fn(KeyColor) -> bool>(
+        &mut self,
+        position: &Vector2i,
+        occupied: &[Vector2i],
+        has_key: F,
+    ) -> DoorUse {
+        let tile_idx = match self.tile_index(position) {
+            Some(tile_idx) => tile_idx,
+            None => return DoorUse::NoDoor,
+        };
+        let door_id = match self.door_ids[tile_idx] {
+            Some(door_id) => door_id,
+            None => return DoorUse::NoDoor,
+        };
+        match self.tile_info[tile_idx].lock {
+            Some(key) if !has_key(key) => DoorUse::Locked(key),
+            _ => {
+                self.doors[door_id].activate(occupied.contains(position));
+                DoorUse::Used
+            }
+        }
+    }
+
+    /// Starts opening the door on a tile, a door already opening or open is left alone. Actors
+    /// open locked doors too. Returns false when there is no door there.
+    pub fn open_door(&mut self, position: &Vector2i) -> bool {
+        match self.tile_index(position).and_then(|idx| self.door_ids[idx]) {
+            Some(door_id) => {
+                self.doors[door_id].open();
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Moves every door.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `occupied` - Tiles where something stands, a door does not close on them
+    pub fn update_doors(&mut self, delta: f32, occupied: &[Vector2i]) {
+        for door in self.doors.iter_mut() {
+            let blocked = occupied.contains(&door.get_position());
+            door.update(delta, blocked);
+        }
+    }
+
+    /// Marks a wall block as a secret push-wall.
+    pub fn add_push_wall(&mut self, position: Vector2i) -> Result<(), String> {
+        match self.get_block(&position) {
+            Some(block) if block != 0 && self.get_door(&position).is_none() => {}
+            _ => return Err(String::from("Push-wall must be placed on a wall block")),
+        }
+        let tile_idx = self.tile_index(&position).unwrap();
+        if !self.push_walls[tile_idx] {
+            self.push_walls[tile_idx] = true;
+            self.secret_count += 1;
+        }
+        Ok(())
+    }
+
+    pub fn is_push_wall(&self, position: &Vector2i) -> bool {
+        match self.tile_index(position) {
+            Some(tile_idx) => self.push_walls[tile_idx],
+            None => false,
+        }
+    }
+
+    /// Starts sliding a push-wall up to two tiles, stopping early in front of anything solid
+    /// or anyone standing in the way. Returns false when there is no push-wall on the tile,
+    /// another one is still moving or there is no room behind it.
+    ///
+    /// # Arguments
+    /// `position` - The tile of the push-wall
+    ///
+    /// `direction` - The direction the wall is pushed, along one axis
+    ///
+    /// `occupied` - Tiles where the player or actors stand
+    pub fn push_wall(
+        &mut self,
+        position: &Vector2i,
+        direction: Vector2i,
+        occupied: &[Vector2i],
+    ) -> bool {
+        if !self.is_push_wall(position) || self.moving_wall.is_some() {
+            return false;
+        }
+        let mut length = 0;
+        while length < PUSH_WALL_DISTANCE {
+            let next = *position + direction * (length + 1);
+            if self.get_block(&next) != Some(0) || occupied.contains(&next) {
+                break;
+            }
+            length += 1;
+        }
+        if length == 0 {
+            return false;
+        }
+
+        let tile_idx = self.tile_index(position).unwrap();
+        self.push_walls[tile_idx] = false;
+        self.moving_wall = Some(PushWall::new(
+            self.map[tile_idx],
+            *position,
+            direction,
+            length,
+        ));
+        self.map[tile_idx] = 0;
+        self.secrets_found += 1;
+        true
+    }
+
+    /// Moves the push-wall in motion, which becomes a normal wall once it stops. The wall waits
+    /// while someone stands in the next tile it slides into.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `occupied` - Tiles where the player or actors stand
+    pub fn update_push_wall(&mut self, delta: f32, occupied: &[Vector2i]) {
+        let finished = match self.moving_wall.as_mut() {
+            Some(moving_wall) => moving_wall.update(delta, |tile| occupied.contains(tile)),
+            None => false,
+        };
+        if finished {
+            let moving_wall = self.moving_wall.take().unwrap();
+            let tile_idx = self.tile_index(&moving_wall.get_destination()).unwrap();
+            self.map[tile_idx] = moving_wall.get_block();
+        }
+    }
+
+    pub fn get_moving_wall(&self) -> Option<&PushWall> {
+        self.moving_wall.as_ref()
+    }
+
+    /// The block drawn on a tile, which is the moving push-wall when it slides over the tile.
+    pub fn get_drawn_block(&self, position: &Vector2i) -> Option<i32> {
+        match &self.moving_wall {
+            Some(moving_wall) if moving_wall.covers(position) => Some(moving_wall.get_block()),
+            _ => self.get_block(position),
+        }
+    }
+
+    /// Writes what changes while the level is played to a save: the blocks moved by push-walls,
+    /// the doors and the secrets found.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.map.len() as u32);
+        for (block, push_wall) in self.map.iter().zip(self.push_walls.iter()) {
+            writer.write_i32(*block);
+            writer.write_bool(*push_wall);
+        }
+        writer.write_u32(self.doors.len() as u32);
+        for door in self.doors.iter() {
+            door.save_state(writer);
+        }
+        writer.write_bool(self.moving_wall.is_some());
+        if let Some(moving_wall) = &self.moving_wall {
+            moving_wall.save_state(writer);
+        }
+        writer.write_u32(self.secrets_found);
+    }
+
+    /// Reads the state written by [Map::save_state] into the same level as it started.
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        reader.read_count(self.map.len(), "tiles")?;
+        for (block, push_wall) in self.map.iter_mut().zip(self.push_walls.iter_mut()) {
+            *block = reader.read_i32()?;
+            *push_wall = reader.read_bool()?;
+        }
+        reader.read_count(self.doors.len(), "doors")?;
+        for door in self.doors.iter_mut() {
+            door.load_state(reader)?;
+        }
+        self.moving_wall = if reader.read_bool()? {
+            let moving_wall = PushWall::from_state(reader)?;
+            if self.tile_index(&moving_wall.get_destination()).is_none() {
+                return Err(SaveError::Corrupt(String::from(
+                    "push-wall moving out of the level",
+                )));
+            }
+            Some(moving_wall)
+        } else {
+            None
+        };
+        self.secrets_found = reader.read_u32()?;
+        Ok(())
+    }
+
+    pub fn get_secret_count(&self) -> u32 {
+        self.secret_count
+    }
+
+    pub fn get_secrets_found(&self) -> u32 {
+        self.secrets_found
+    }
+
+    /// Returns the floor number shown in the status bar.
+    pub fn get_floor(&self) -> i32 {
+        self.floor
+    }
+
+    pub fn set_floor(&mut self, floor: i32) {
+        self.floor = floor;
+    }
+
+    pub fn add_decoration(&mut self, decoration: Decoration) {
+        self.decorations.push(decoration);
+    }
+
+    pub fn get_decorations(&self) -> &[Decoration] {
+        &self.decorations
+    }
+
+    pub fn add_actor_spawn(&mut self, actor_spawn: ActorSpawn) {
+        self.actor_spawns.push(actor_spawn);
+    }
+
+    pub fn get_actor_spawns(&self) -> &[ActorSpawn] {
+        &self.actor_spawns
+    }
+
+    pub fn add_item(&mut self, item: Item) {
+        self.items.push(item);
+    }
+
+    pub fn get_items(&self) -> &[Item] {
+        &self.items
+    }
+
+    /// Whether nothing blocks the straight line between two points. Doors only let sight
+    /// through their open part.
+    pub fn has_line_of_sight(&self, from: &Vector2f, to: &Vector2f) -> bool {
+        let ray = Vector2f::new(to.x - from.x, to.y - from.y);
+        let distance = (ray.x * ray.x + ray.y * ray.y).sqrt();
+        distance == 0. || self.cast_ray(from, &ray, distance).is_none()
+    }
+
+    /// Finds the first solid tile along a ray, walking the grid with the renderer's
+    /// [raycasting_engine::cast_ray]. Doors stop the ray at their panel, unless it goes through
+    /// their open part.
+    ///
+    /// # Arguments
+    /// `origin` - Where the ray starts
+    ///
+    /// `direction` - The direction of the ray, it does not need to be normalized
+    ///
+    /// `max_distance` - How far the ray goes, in tiles
+    pub fn cast_ray(
+        &self,
+        origin: &Vector2f,
+        direction: &Vector2f,
+        max_distance: f32,
+    ) -> Option<WallHit> {
+        let length = (direction.x * direction.x + direction.y * direction.y).sqrt();
+        if length == 0. {
+            return None;
+        }
+        let ray = Vector2f::new(direction.x / length, direction.y / length);
+        let mut distance = None;
+        let hit = raycasting_engine::cast_ray(origin, &ray, |tile, side| {
+            // Distance to the tile border the ray crossed to enter the tile
+            let entry = if side == 0 {
+                let border = tile.x as f32 + if ray.x < 0. { 1. } else { 0. };
+                (border - origin.x) / ray.x
+            } else {
+                let border = tile.y as f32 + if ray.y < 0. { 1. } else { 0. };
+                (border - origin.y) / ray.y
+            };
+            if entry > max_distance {
+                return true;
+            }
+            distance = self.blocking_distance(tile, side, origin, &ray, entry);
+            distance.is_some()
+        });
+        match distance {
+            Some(distance) if distance <= max_distance => Some(WallHit {
+                tile: hit.tile,
+                distance,
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns where a ray entering a tile from `side` is stopped, or None when it goes through
+    /// the tile. Doors are hit like the renderer draws them, see [Door::hit_by_ray].
+    fn blocking_distance(
+        &self,
+        tile: &Vector2i,
+        side: i32,
+        origin: &Vector2f,
+        ray: &Vector2f,
+        entry: f32,
+    ) -> Option<f32> {
+        if let Some(moving_wall) = &self.moving_wall {
+            if moving_wall.covers(tile) {
+                return Some(entry);
+            }
+        }
+        match (self.get_block(tile), self.get_door(tile)) {
+            (Some(0), _) => None,
+            (Some(_), Some(door)) => door.hit_by_ray(side, origin, ray),
+            _ => Some(entry),
+        }
+    }
+
+    /// Whether a point is on an empty tile, or in the open part of a doorway, and not inside a
+    /// moving push-wall.
+    pub fn is_walkable(&self, point: &Vector2f) -> bool {
+        if let Some(moving_wall) = &self.moving_wall {
+            if moving_wall.contains(point) {
+                return false;
+            }
+        }
+        let position = Vector2i::new(point.x.floor() as i32, point.y.floor() as i32);
+        match (self.get_block(&position), self.get_door(&position)) {
+            (Some(0), _) => true,
+            (Some(_), Some(door)) => door.is_passable_at(point),
+            _ => false,
+        }
+    }
+
+    /// Returns the part of a tile blocking movement, or None when nothing on it blocks. Tiles
+    /// outside of the map are solid. The moving push-wall is left out, it is not on the grid.
+    pub fn get_blocking_box(&self, tile: &Vector2i) -> Option<FloatRect> {
+        match (self.get_block(tile), self.get_door(tile)) {
+            (Some(0), _) => None,
+            (Some(_), Some(door)) => door.get_blocking_box(),
+            _ => Some(FloatRect::new(tile.x as f32, tile.y as f32, 1., 1.)),
+        }
+    }
+
+    fn tile_index(&self, position: &Vector2i) -> Option<usize> {
+        self.get_block(position)?;
+        Some((position.y * self.map_size.x + position.x) as usize)
+    }
+
     fn handle_orientation(&self, position: Vector2i, offset: Vector2i) -> Option<i32> {
         self.get_block(&(position + offset))
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse_error(contents: &str) -> String {
+        Map::parse_ascii(contents).err().unwrap().to_string()
+    }
+
+    #[test]
+    fn grid_errors_point_at_the_tile() {
+        assert_eq!(
+            parse_error("# = 1\nmap:\n####\n#>x#\n####\n"),
+            "ERROR: Unknown tile 'x' (missing from legend) (line 4, column 3)"
+        );
+        assert_eq!(
+            parse_error("# = 1\nmap:\n####\n#>.#\n###\n"),
+            "ERROR: Row is 3 tiles wide, expected 4 (line 5, column 4)"
+        );
+        assert_eq!(
+            parse_error("# = 1\nmap:\n#####\n#>.<#\n#####\n"),
+            "ERROR: Duplicate player spawn (line 4, column 4)"
+        );
+        assert_eq!(
+            parse_error("# = 1\n\nmap:\n####\n#>..\n####\n"),
+            "ERROR: Map border must be a wall (line 5, column 4)"
+        );
+        assert_eq!(
+            parse_error("# = 1\nmap:\n####\n#..#\n####\n"),
+            "ERROR: Missing player spawn ('<', '>', '^' or 'v')"
+        );
+        assert_eq!(
+            parse_error("# = 1\n"),
+            "ERROR: Missing `map:` line before the tile grid"
+        );
+    }
+
+    #[test]
+    fn legend_errors_point_at_the_entry() {
+        let grid = "map:\n###\n#>#\n###\n";
+        let error = |legend: &str| parse_error(&format!("{}\n{}", legend, grid));
+        assert_eq!(
+            error("; walls\n# 1"),
+            "ERROR: Expected a legend entry `<char> = <block id>` (line 2, column 1)"
+        );
+        assert_eq!(
+            error("## = 1"),
+            "ERROR: Legend key must be a single character (line 1, column 1)"
+        );
+        assert_eq!(
+            error(". = 1"),
+            "ERROR: Tile '.' is reserved and cannot be redefined (line 1, column 1)"
+        );
+        assert_eq!(
+            error("# = wall"),
+            "ERROR: Invalid block ID 'wall' for tile '#' (line 1, column 5)"
+        );
+        assert_eq!(
+            error("# = 0"),
+            "ERROR: Invalid block ID '0' for tile '#' (line 1, column 5)"
+        );
+        assert_eq!(
+            error("# = 1 window"),
+            "ERROR: Unknown tile kind 'window' for tile '#' (line 1, column 5)"
+        );
+    }
+
+    #[test]
+    fn push_walls_stop_short_of_anyone_in_the_way() {
+        let mut map = Map::parse_ascii(
+            "\
+# = 1
+S = 1 pushwall
+map:
+#####
+#.>.#
+#.S.#
+#...#
+#...#
+#####
+",
+        )
+        .unwrap();
+        let wall = Vector2i::new(2, 2);
+        let down = Vector2i::new(0, 1);
+        // Nowhere to go with someone right behind the wall
+        assert!(!map.push_wall(&wall, down, &[Vector2i::new(2, 3)]));
+        assert!(map.is_push_wall(&wall));
+
+        // Someone two tiles behind shortens the slide to one tile
+        assert!(map.push_wall(&wall, down, &[Vector2i::new(2, 4)]));
+        map.update_push_wall(10., &[]);
+        assert_eq!(map.get_block(&Vector2i::new(2, 3)), Some(1));
+        assert_eq!(map.get_block(&Vector2i::new(2, 4)), Some(0));
+    }
+
+    #[test]
+    fn moving_push_walls_wait_for_the_way_to_clear() {
+        let mut map = Map::parse_ascii(
+            "\
+# = 1
+S = 1 pushwall
+map:
+#####
+#.>.#
+#.S.#
+#...#
+#...#
+#####
+",
+        )
+        .unwrap();
+        assert!(map.push_wall(&Vector2i::new(2, 2), Vector2i::new(0, 1), &[]));
+        // Someone walked into the second tile while the wall slides
+        let in_the_way = [Vector2i::new(2, 4)];
+        for _ in 0..100 {
+            map.update_push_wall(0.07, &in_the_way);
+        }
+        let moving_wall = map.get_moving_wall().unwrap();
+        assert_eq!(moving_wall.get_position(), Vector2f::new(2., 3.));
+        assert!(map.is_walkable(&Vector2f::new(2.5, 4.)));
+        map.update_push_wall(10., &[]);
+        assert!(map.get_moving_wall().is_none());
+        assert_eq!(map.get_block(&Vector2i::new(2, 4)), Some(1));
+    }
+}
@@ -10,26 +10,22 @@ use rsfml::{
 use crate::{map::*, texture_loader::TextureLoader};
 
 pub struct MiniMap {
-    map: Map,
     active: bool,
     mini_map_view: SfBox<View>,
     player_pos: Vector2f,
-    rotation: f32,
 }
 
 impl MiniMap {
-    pub fn new(map: Map, window_size: &Vector2u) -> MiniMap {
+    pub fn new(map: &Map, window_size: &Vector2u) -> MiniMap {
         let mut tmp_view = View::new(Vector2f::default(), Vector2f::default());
         let borrowed_view = &mut (*tmp_view);
         borrowed_view.set_size(Vector2f::new(window_size.x as f32, window_size.y as f32));
         borrowed_view.set_viewport(&FloatRect::new(0.70, 0.05, 0.25, 0.25));
-        borrowed_view.set_rotation(-90.);
+        borrowed_view.set_rotation(MiniMap::rotation_of(&map.get_spawn().direction));
         MiniMap {
-            map,
             active: true,
             mini_map_view: tmp_view,
             player_pos: Vector2f { x: 0., y: 0. },
-            rotation: 0.,
         }
     }
 
@@ -42,35 +38,53 @@ impl MiniMap {
         self.active
     }
 
-    pub fn update(&mut self, player_position: Vector2f, new_rotation: f32) -> () {
+    pub fn set_active(&mut self, active: bool) {
+        self.active = active;
+    }
+
+    /// Returns the rotation of the view making a direction point up, in degrees.
+    fn rotation_of(direction: &Vector2f) -> f32 {
+        direction.y.atan2(direction.x).to_degrees() + 90.
+    }
+
+    /// Centers the mini-map on the player, with the direction the player faces pointing up.
+    ///
+    /// # Arguments
+    /// `player_position` - Where the player stands
+    ///
+    /// `player_direction` - The direction the player faces
+    pub fn update(&mut self, player_position: Vector2f, player_direction: Vector2f) -> () {
         self.player_pos = player_position;
         let borrowed_mini_map_view = &mut (*self.mini_map_view);
-        borrowed_mini_map_view.rotate(new_rotation);
+        borrowed_mini_map_view.set_rotation(MiniMap::rotation_of(&player_direction));
         borrowed_mini_map_view.set_center(Vector2f::new(
             self.player_pos.x * 80.,
             self.player_pos.y * 80.,
         ));
-        self.rotation += new_rotation;
     }
 
-    pub fn draw(&mut self, render_window: &mut RenderWindow, texture_loader: &TextureLoader) -> () {
+    pub fn draw(
+        &mut self,
+        render_window: &mut RenderWindow,
+        texture_loader: &TextureLoader,
+        map: &Map,
+    ) -> () {
         let mut block: i32;
-        let map_size = self.map.get_map_size();
+        let map_size = map.get_map_size();
         let mut pos: Vector2i = Vector2i::new(0, 0);
         let mut rect = RectangleShape::with_size(Vector2f::new(80., 80.));
         rect.set_fill_color(Color::rgba(255, 255, 255, 175));
         render_window.set_view(&self.mini_map_view);
         while pos.x < map_size.x {
             while pos.y < map_size.y {
-                block = self
-                    .map
+                block = map
                     .get_block(&pos)
                     .expect("ERROR: Cannot get block in minimap.");
                 if block == 0 {
-                    rect.set_texture(texture_loader.get_texture(block), false);
+                    rect.set_texture(texture_loader.get_wall_texture(block, false), false);
                     rect.set_position(Vector2f::new(pos.x as f32 * 80., pos.y as f32 * 80.));
                 } else {
-                    rect.set_texture(texture_loader.get_texture(block), false);
+                    rect.set_texture(texture_loader.get_wall_texture(block, false), false);
                     rect.set_position(Vector2f::new(pos.x as f32 * 80., pos.y as f32 * 80.));
                 }
                 render_window.draw(&mut rect);
@@ -79,6 +93,15 @@ impl MiniMap {
             pos.x += 1;
             pos.y = 0;
         }
+        if let Some(moving_wall) = map.get_moving_wall() {
+            let position = moving_wall.get_position();
+            rect.set_texture(
+                texture_loader.get_wall_texture(moving_wall.get_block(), false),
+                false,
+            );
+            rect.set_position(Vector2f::new(position.x * 80., position.y * 80.));
+            render_window.draw(&mut rect);
+        }
         rect.set_fill_color(Color::rgba(255, 0, 0, 125));
         rect.set_origin(Vector2f::new(40., 40.));
         rect.set_position(Vector2f::new(
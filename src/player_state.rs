@@ -0,0 +1,272 @@
+//! Module for the player's health, ammunition and inventory
+
+use crate::save::{SaveError, StateReader, StateWriter};
+
+/// Health of the player at the start of each life.
+pub const MAX_HEALTH: i32 = 100;
+
+/// Most ammunition the player can carry.
+pub const MAX_AMMO: i32 = 99;
+
+/// Highest score, the most the six digits of the status bar show.
+pub const MAX_SCORE: i32 = 999_999;
+
+/// Ammunition at the start of each life.
+const START_AMMO: i32 = 8;
+
+/// Lives at the start of the game.
+const START_LIVES: i32 = 3;
+
+/// Weapon slots, in the order of the weapon textures.
+pub const PISTOL: usize = 0;
+pub const MACHINE_GUN: usize = 1;
+pub const CHAINGUN: usize = 2;
+pub const KNIFE: usize = 3;
+
+#[derive(PartialEq, Eq, Clone, Copy, Debug)]
+pub enum KeyColor {
+    Gold,
+    Silver,
+}
+
+/// Everything the status bar shows about the player.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PlayerState {
+    health: i32,
+    ammo: i32,
+    lives: i32,
+    score: i32,
+    floor: i32,
+    keys: [bool; 2],
+    weapons: [bool; 4],
+}
+
+impl PlayerState {
+    /// Creates the state of a new game, with the knife and the pistol.
+    ///
+    /// # Arguments
+    /// `floor` - The floor number of the level
+    pub fn new(floor: i32) -> PlayerState {
+        let mut weapons = [false; 4];
+        weapons[PISTOL] = true;
+        weapons[KNIFE] = true;
+        PlayerState {
+            health: MAX_HEALTH,
+            ammo: START_AMMO,
+            lives: START_LIVES,
+            score: 0,
+            floor,
+            keys: [false; 2],
+            weapons,
+        }
+    }
+
+    pub fn get_health(&self) -> i32 {
+        self.health
+    }
+
+    pub fn get_ammo(&self) -> i32 {
+        self.ammo
+    }
+
+    pub fn get_lives(&self) -> i32 {
+        self.lives
+    }
+
+    pub fn get_score(&self) -> i32 {
+        self.score
+    }
+
+    pub fn get_floor(&self) -> i32 {
+        self.floor
+    }
+
+    pub fn is_dead(&self) -> bool {
+        self.health <= 0
+    }
+
+    /// Removes health, down to 0.
+    pub fn take_damage(&mut self, damage: i32) {
+        self.health = (self.health - damage).max(0);
+    }
+
+    /// Adds health, up to [MAX_HEALTH]. Returns false when the player is already healthy.
+    pub fn heal(&mut self, amount: i32) -> bool {
+        if self.health >= MAX_HEALTH {
+            return false;
+        }
+        self.health = (self.health + amount).min(MAX_HEALTH);
+        true
+    }
+
+    /// Adds ammunition, up to [MAX_AMMO]. Returns false when the player cannot carry more.
+    pub fn add_ammo(&mut self, amount: i32) -> bool {
+        if self.ammo >= MAX_AMMO {
+            return false;
+        }
+        self.ammo = (self.ammo + amount).min(MAX_AMMO);
+        true
+    }
+
+    /// Uses one bullet. Returns false when there is none left.
+    pub fn use_ammo(&mut self) -> bool {
+        if self.ammo <= 0 {
+            return false;
+        }
+        self.ammo -= 1;
+        true
+    }
+
+    /// Adds points, up to [MAX_SCORE].
+    pub fn add_score(&mut self, points: i32) {
+        self.score = self.score.saturating_add(points).min(MAX_SCORE);
+    }
+
+    pub fn add_life(&mut self) {
+        self.lives += 1;
+    }
+
+    /// Starts the next life with full health and the starting ammunition, keeping the score.
+    /// Returns false when no life is left.
+    pub fn lose_life(&mut self) -> bool {
+        if self.lives <= 0 {
+            return false;
+        }
+        self.lives -= 1;
+        self.health = MAX_HEALTH;
+        self.ammo = START_AMMO;
+        self.keys = [false; 2];
+        true
+    }
+
+    pub fn has_key(&self, key: KeyColor) -> bool {
+        self.keys[key as usize]
+    }
+
+    /// Gives a key. Returns false when the player already holds it.
+    pub fn give_key(&mut self, key: KeyColor) -> bool {
+        let had_key = self.keys[key as usize];
+        self.keys[key as usize] = true;
+        !had_key
+    }
+
+    pub fn has_weapon(&self, slot: usize) -> bool {
+        self.weapons[slot]
+    }
+
+    /// Gives a weapon. Returns false when the player already owns it.
+    pub fn give_weapon(&mut self, slot: usize) -> bool {
+        let had_weapon = self.weapons[slot];
+        self.weapons[slot] = true;
+        !had_weapon
+    }
+
+    /// Whether a weapon can be used: it is owned and, apart from the knife, has ammunition.
+    pub fn can_use_weapon(&self, slot: usize) -> bool {
+        self.weapons[slot] && (slot == KNIFE || self.ammo > 0)
+    }
+
+    /// Writes the stats and inventory to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        for value in [self.health, self.ammo, self.lives, self.score, self.floor].iter() {
+            writer.write_i32(*value);
+        }
+        for owned in self.keys.iter().chain(self.weapons.iter()) {
+            writer.write_bool(*owned);
+        }
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        let mut read_stat = |name: &str, max: i32| {
+            let value = reader.read_i32()?;
+            if value < 0 || value > max {
+                return Err(SaveError::Corrupt(format!("{} {}", name, value)));
+            }
+            Ok(value)
+        };
+        self.health = read_stat("health", MAX_HEALTH)?;
+        self.ammo = read_stat("ammo", MAX_AMMO)?;
+        self.lives = read_stat("lives", i32::MAX)?;
+        self.score = read_stat("score", MAX_SCORE)?;
+        self.floor = reader.read_i32()?;
+        for owned in self.keys.iter_mut().chain(self.weapons.iter_mut()) {
+            *owned = reader.read_bool()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn health_stays_between_zero_and_max() {
+        let mut player = PlayerState::new(1);
+        player.take_damage(30);
+        assert_eq!(player.get_health(), MAX_HEALTH - 30);
+        assert!(!player.is_dead());
+
+        assert!(player.heal(50));
+        assert_eq!(player.get_health(), MAX_HEALTH);
+        assert!(!player.heal(1));
+
+        player.take_damage(MAX_HEALTH + 20);
+        assert_eq!(player.get_health(), 0);
+        assert!(player.is_dead());
+    }
+
+    #[test]
+    fn ammo_runs_out_and_fills_up_to_max() {
+        let mut player = PlayerState::new(1);
+        for _ in 0..START_AMMO {
+            assert!(player.use_ammo());
+        }
+        assert_eq!(player.get_ammo(), 0);
+        assert!(!player.use_ammo());
+        assert_eq!(player.get_ammo(), 0);
+        assert!(!player.can_use_weapon(PISTOL));
+        assert!(player.can_use_weapon(KNIFE));
+
+        assert!(player.add_ammo(MAX_AMMO + 10));
+        assert_eq!(player.get_ammo(), MAX_AMMO);
+        assert!(!player.add_ammo(1));
+    }
+
+    #[test]
+    fn score_stops_at_max() {
+        let mut player = PlayerState::new(1);
+        player.add_score(5000);
+        assert_eq!(player.get_score(), 5000);
+        player.add_score(MAX_SCORE);
+        assert_eq!(player.get_score(), MAX_SCORE);
+        player.add_score(i32::MAX);
+        assert_eq!(player.get_score(), MAX_SCORE);
+    }
+
+    #[test]
+    fn losing_a_life_restarts_it_but_keeps_the_score() {
+        let mut player = PlayerState::new(2);
+        player.take_damage(MAX_HEALTH);
+        player.use_ammo();
+        player.add_score(700);
+        player.give_key(KeyColor::Gold);
+        player.give_weapon(CHAINGUN);
+
+        assert!(player.lose_life());
+        assert_eq!(player.get_lives(), START_LIVES - 1);
+        assert_eq!(player.get_health(), MAX_HEALTH);
+        assert_eq!(player.get_ammo(), START_AMMO);
+        assert_eq!(player.get_score(), 700);
+        assert_eq!(player.get_floor(), 2);
+        assert!(!player.has_key(KeyColor::Gold));
+        assert!(player.has_weapon(CHAINGUN));
+
+        while player.get_lives() > 0 {
+            assert!(player.lose_life());
+        }
+        player.take_damage(MAX_HEALTH);
+        assert!(!player.lose_life());
+        assert!(player.is_dead());
+    }
+}
@@ -0,0 +1,121 @@
+//! Module for secret push-walls
+
+use rsfml::system::{Vector2f, Vector2i};
+
+use crate::save::{SaveError, StateReader, StateWriter};
+
+/// Tiles a push-wall moves at most when pushed.
+pub const PUSH_WALL_DISTANCE: i32 = 2;
+
+/// Speed of a moving push-wall, in tiles per second.
+pub const PUSH_WALL_SPEED: f32 = 1.;
+
+/// A wall block sliding away from the player after being pushed.
+#[derive(Clone, Debug)]
+pub struct PushWall {
+    block: i32,
+    origin: Vector2i,
+    direction: Vector2i,
+    length: i32,
+    distance: f32,
+}
+
+impl PushWall {
+    /// Starts moving a wall block.
+    ///
+    /// # Arguments
+    /// `block` - The block ID of the wall
+    ///
+    /// `origin` - The tile the wall starts from
+    ///
+    /// `direction` - A unit vector along one axis
+    ///
+    /// `length` - How many tiles the wall travels
+    pub fn new(block: i32, origin: Vector2i, direction: Vector2i, length: i32) -> PushWall {
+        PushWall {
+            block,
+            origin,
+            direction,
+            length,
+            distance: 0.,
+        }
+    }
+
+    pub fn get_block(&self) -> i32 {
+        self.block
+    }
+
+    /// The tile the wall stops on.
+    pub fn get_destination(&self) -> Vector2i {
+        self.origin + self.direction * self.length
+    }
+
+    /// The top left corner of the block, between two tiles while it moves.
+    pub fn get_position(&self) -> Vector2f {
+        Vector2f::new(
+            self.origin.x as f32 + self.direction.x as f32 * self.distance,
+            self.origin.y as f32 + self.direction.y as f32 * self.distance,
+        )
+    }
+
+    /// Moves the wall, waiting at the border of a tile it cannot enter yet. Returns true once it
+    /// reached its destination.
+    ///
+    /// # Arguments
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `is_blocked` - Whether someone stands in a tile the wall would slide into
+    pub fn update<F: Fn(&Vector2i) -> bool>(&mut self, delta: f32, is_blocked: F) -> bool {
+        let mut distance = (self.distance + delta * PUSH_WALL_SPEED).min(self.length as f32);
+        // Tiles are entered once the wall passes their near border
+        let mut tile = self.distance.ceil() as i32 + 1;
+        while tile <= self.length && ((tile - 1) as f32) < distance {
+            if is_blocked(&(self.origin + self.direction * tile)) {
+                distance = (tile - 1) as f32;
+                break;
+            }
+            tile += 1;
+        }
+        self.distance = distance;
+        self.distance >= self.length as f32
+    }
+
+    /// Whether any part of the block is over a tile.
+    pub fn covers(&self, tile: &Vector2i) -> bool {
+        let position = self.get_position();
+        (tile.x as f32) < position.x + 1.
+            && position.x < (tile.x + 1) as f32
+            && (tile.y as f32) < position.y + 1.
+            && position.y < (tile.y + 1) as f32
+    }
+
+    /// Writes the wall and how far it went to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_i32(self.block);
+        writer.write_vector2i(&self.origin);
+        writer.write_vector2i(&self.direction);
+        writer.write_i32(self.length);
+        writer.write_f32(self.distance);
+    }
+
+    /// Reads a moving wall written by [PushWall::save_state].
+    pub fn from_state(reader: &mut StateReader) -> Result<PushWall, SaveError> {
+        let mut push_wall = PushWall::new(
+            reader.read_i32()?,
+            reader.read_vector2i()?,
+            reader.read_vector2i()?,
+            reader.read_i32()?,
+        );
+        push_wall.distance = reader.read_f32()?;
+        Ok(push_wall)
+    }
+
+    /// Whether a point is inside the block.
+    pub fn contains(&self, point: &Vector2f) -> bool {
+        let position = self.get_position();
+        point.x >= position.x
+            && point.x < position.x + 1.
+            && point.y >= position.y
+            && point.y < position.y + 1.
+    }
+}
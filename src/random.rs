@@ -0,0 +1,47 @@
+//! Module for the pseudo-random numbers used by the game logic
+
+use crate::save::{SaveError, StateReader, StateWriter};
+
+/// Small xorshift generator. Game logic draws from a seeded generator instead of the system
+/// one so a run can be played again exactly.
+#[derive(Clone, Debug)]
+pub struct Random {
+    state: u32,
+}
+
+impl Random {
+    pub fn new(seed: u32) -> Random {
+        Random {
+            // Xorshift never leaves 0, so that seed is replaced
+            state: if seed == 0 { 0x9E37_79B9 } else { seed },
+        }
+    }
+
+    pub fn next_u32(&mut self) -> u32 {
+        self.state ^= self.state << 13;
+        self.state ^= self.state >> 17;
+        self.state ^= self.state << 5;
+        self.state
+    }
+
+    /// Returns a number between 0 and 255, like the random table of the original game.
+    pub fn next_byte(&mut self) -> i32 {
+        (self.next_u32() >> 24) as i32
+    }
+
+    /// Returns a number in `[0, 1)`.
+    pub fn next_f32(&mut self) -> f32 {
+        (self.next_u32() >> 8) as f32 / (1 << 24) as f32
+    }
+
+    /// Writes the state of the generator to a save, so the numbers drawn after loading are the
+    /// same.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u32(self.state);
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        *self = Random::new(reader.read_u32()?);
+        Ok(())
+    }
+}
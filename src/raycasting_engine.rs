@@ -2,101 +2,300 @@
 
 use rsfml::{
     graphics::{
-        Color, PrimitiveType, RenderStates, RenderTarget, RenderWindow, Vertex, VertexArray,
+        Color, PrimitiveType, RenderStates, RenderTarget, RenderWindow, Texture, Vertex,
+        VertexArray,
     },
-    system::{Vector2f, Vector2i},
-    window::Key,
+    system::{SfBox, Vector2f, Vector2i},
 };
 
-use crate::{event_handler::EventHandler, map::Map, texture_loader::TextureLoader};
+use crate::{
+    collision,
+    event_handler::EventHandler,
+    framebuffer::FrameBuffer,
+    input::Action,
+    map::{DoorUse, Map},
+    player_state::KeyColor,
+    push_wall::PushWall,
+    save::{SaveError, StateReader, StateWriter},
+    sprite::Sprite,
+    texture_loader::{TextureData, TextureLoader},
+};
+
+/// Tile where a ray stopped, see [cast_ray].
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RayHit {
+    pub tile: Vector2i,
+    /// 0 when the ray entered the tile through a vertical border (x side), 1 through a
+    /// horizontal one (y side)
+    pub side: i32,
+    /// Direction the ray walks the grid on each axis, -1 or 1
+    pub step: Vector2i,
+}
+
+/// Walks a ray through the tiles of the grid it crosses, in order, with a DDA, and returns the
+/// first tile `is_hit` accepts. The tile the ray starts in is not tested.
+///
+/// When the ray goes exactly through a tile corner it steps along y first.
+///
+/// # Arguments
+/// `ray_pos` - Start of the ray
+///
+/// `ray_dir` - Direction of the ray, any length but not zero
+///
+/// `is_hit` - Tells whether the ray stops in a tile, given the side it entered it from. It
+/// must stop the ray eventually, e.g. outside of the map
+pub fn cast_ray<F: FnMut(&Vector2i, i32) -> bool>(
+    ray_pos: &Vector2f,
+    ray_dir: &Vector2f,
+    mut is_hit: F,
+) -> RayHit {
+    let mut tile = Vector2i::new(ray_pos.x.floor() as i32, ray_pos.y.floor() as i32);
+    // Length of the ray between two tile borders on each axis, infinite along an axis
+    let delta_dist = Vector2f::new(
+        (1. + (ray_dir.y * ray_dir.y) / (ray_dir.x * ray_dir.x)).sqrt(),
+        (1. + (ray_dir.x * ray_dir.x) / (ray_dir.y * ray_dir.y)).sqrt(),
+    );
+    // Length of the ray to the next tile border on each axis
+    let mut side_dist = Vector2f::new(0., 0.);
+    let mut step = Vector2i::new(0, 0);
+    if ray_dir.x < 0. {
+        step.x = -1;
+        side_dist.x = (ray_pos.x - tile.x as f32) * delta_dist.x;
+    } else {
+        step.x = 1;
+        side_dist.x = (tile.x as f32 + 1. - ray_pos.x) * delta_dist.x;
+    }
+    if ray_dir.y < 0. {
+        step.y = -1;
+        side_dist.y = (ray_pos.y - tile.y as f32) * delta_dist.y;
+    } else {
+        step.y = 1;
+        side_dist.y = (tile.y as f32 + 1. - ray_pos.y) * delta_dist.y;
+    }
+
+    loop {
+        let side = if side_dist.x < side_dist.y {
+            side_dist.x += delta_dist.x;
+            tile.x += step.x;
+            0
+        } else {
+            side_dist.y += delta_dist.y;
+            tile.y += step.y;
+            1
+        };
+        if is_hit(&tile, side) {
+            return RayHit { tile, side, step };
+        }
+    }
+}
+
+/// Position and orientation of the player's view.
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Camera {
+    position: Vector2f,
+    direction: Vector2f,
+    plane: Vector2f,
+}
+
+impl Camera {
+    /// Blends two cameras, `alpha` going from `self` (0) to `other` (1).
+    fn lerp(&self, other: &Camera, alpha: f32) -> Camera {
+        let mix = |from: Vector2f, to: Vector2f| from + (to - from) * alpha;
+        Camera {
+            position: mix(self.position, other.position),
+            direction: mix(self.direction, other.direction),
+            plane: mix(self.plane, other.plane),
+        }
+    }
+}
+
+/// Walking speed of the player, in tiles per second.
+const MOVE_SPEED: f32 = 3.;
+
+/// How much faster the player moves while holding shift.
+const RUN_FACTOR: f32 = 2.;
+
+/// Radius of the player's body, in tiles. Walls never come closer to the camera.
+pub const PLAYER_RADIUS: f32 = 0.3;
+
+/// Turning speed of the player with the arrow keys, in degrees per second.
+const TURN_SPEED: f32 = 157.5;
+
+/// Colors of the ceiling and floor when they are not textured.
+const SKY_COLOR: Color = Color::rgb(63, 48, 21);
+const GROUND_COLOR: Color = Color::rgb(109, 108, 112);
 
 pub struct RaycastEngine {
     player_position: Vector2f,
     vector_direction: Vector2f,
     cam_plane: Vector2f,
+    player_radius: f32,
+    // Camera at the start of the last update, and the one frames are rendered from
+    previous: Camera,
+    view: Camera,
     map: Map,
+    // Size of the 3D view
     window_size: Vector2f,
-    vertex_array: Vec<Box<VertexArray>>,
-    textures_id: Vec<i32>,
-    ground: Vec<Box<VertexArray>>,
-    sky: Vec<Box<VertexArray>>,
     no_ground: bool,
+    // Distance to the wall drawn in each column
+    z_buffer: Vec<f32>,
+    frame: FrameBuffer,
+    // Texture the frame is uploaded to, created on the first draw
+    frame_texture: Option<SfBox<Texture>>,
+    frame_quad: VertexArray,
 }
 
 impl RaycastEngine {
-    pub fn new(map: Map, window_size: &Vector2f, no_ground: bool) -> RaycastEngine {
+    /// Creates an engine rendering a view of the given size.
+    ///
+    /// # Arguments
+    /// `map` - The map to render
+    ///
+    /// `view_size` - Size in pixels of the 3D view
+    ///
+    /// `no_ground` - Draws a flat floor and ceiling instead of textured ones
+    pub fn new(map: Map, view_size: &Vector2f, no_ground: bool) -> RaycastEngine {
+        let (width, height) = (view_size.x as u32, view_size.y as u32);
+        let mut frame_quad = VertexArray::new(PrimitiveType::Quads, 4);
+        for (i, &(x, y)) in [
+            (0., 0.),
+            (view_size.x, 0.),
+            (view_size.x, view_size.y),
+            (0., view_size.y),
+        ]
+        .iter()
+        .enumerate()
+        {
+            frame_quad[i] = Vertex::new(Vector2f::new(x, y), Color::WHITE, Vector2f::new(x, y));
+        }
+        let spawn = *map.get_spawn();
+        let camera = Camera {
+            position: spawn.position,
+            direction: spawn.direction,
+            plane: Vector2f::new(spawn.direction.y * 0.66, -spawn.direction.x * 0.66),
+        };
         RaycastEngine {
-            player_position: Vector2f { x: 22., y: 12. },
-            vector_direction: Vector2f { x: -1., y: 0. },
-            cam_plane: Vector2f { x: 0., y: 0.66 },
-            map,
-            window_size: Vector2f {
-                x: window_size.x,
-                y: window_size.y - 80.,
+            player_position: spawn.position,
+            vector_direction: spawn.direction,
+            // The camera plane is perpendicular to the direction, giving a ~66 degree FOV
+            cam_plane: Vector2f {
+                x: spawn.direction.y * 0.66,
+                y: -spawn.direction.x * 0.66,
             },
-            vertex_array: RaycastEngine::create_line_array(window_size),
-            textures_id: Vec::new(),
-            ground: RaycastEngine::create_ground_array(window_size),
-            sky: RaycastEngine::create_ground_array(window_size),
+            player_radius: PLAYER_RADIUS,
+            previous: camera,
+            view: camera,
+            map,
+            window_size: *view_size,
             no_ground,
+            z_buffer: Vec::new(),
+            frame: FrameBuffer::new(width, height),
+            frame_texture: None,
+            frame_quad,
         }
     }
 
-    pub fn update(&mut self, event_handler: &EventHandler) {
-        self.textures_id.clear();
-        let ray_pos = self.player_position.clone();
+    /// Moves the player and updates doors and push-walls.
+    ///
+    /// # Arguments
+    /// `event_handler` - The event handler to read input from
+    ///
+    /// `delta` - Seconds elapsed since the last update
+    ///
+    /// `occupied` - Tiles where actors stand, doors do not close on them
+    pub fn update(&mut self, event_handler: &EventHandler, delta: f32, occupied: &[Vector2i]) {
+        self.previous = self.camera();
+        let mut occupied = occupied.to_vec();
+        occupied.extend(collision::covered_tiles(
+            &self.player_position,
+            self.player_radius,
+        ));
+        self.update_events(event_handler, delta);
+        self.map.update_doors(delta, &occupied);
+        self.map.update_push_wall(delta, &occupied);
+        self.view = self.camera();
+    }
+
+    fn camera(&self) -> Camera {
+        Camera {
+            position: self.player_position,
+            direction: self.vector_direction,
+            plane: self.cam_plane,
+        }
+    }
+
+    /// Places the view between the camera before and after the last update, to draw frames
+    /// in between two updates.
+    ///
+    /// # Arguments
+    /// `alpha` - How far the frame is from the previous update (0) to the last one (1)
+    pub fn interpolate(&mut self, alpha: f32) {
+        self.view = self.previous.lerp(&self.camera(), alpha);
+    }
+
+    /// Renders the walls, floor, ceiling and sprites seen by the player into the frame buffer,
+    /// from the camera of the last update or the one set by [RaycastEngine::interpolate].
+    ///
+    /// # Arguments
+    /// `sprites` - The sprites to draw
+    ///
+    /// `texture_loader` - The textures to read pixels from, may be headless
+    pub fn render(&mut self, sprites: &[Sprite], texture_loader: &TextureLoader) -> &FrameBuffer {
+        self.z_buffer.clear();
+        let height = self.frame.get_height();
+        self.frame.fill_rows(0, height / 2, SKY_COLOR);
+        self.frame.fill_rows(height / 2, height, GROUND_COLOR);
+        let floor_textures = match (
+            texture_loader.get_id("ground"),
+            texture_loader.get_id("sky"),
+        ) {
+            (Some(ground), Some(sky)) if !self.no_ground => Some((
+                texture_loader.get_texture_data(ground),
+                texture_loader.get_texture_data(sky),
+            )),
+            _ => None,
+        };
+
+        let ray_pos = self.view.position;
         let mut ray_dir = Vector2f { x: 0., y: 0. };
-        let mut map_pos = Vector2i { x: 0, y: 0 };
-        let mut side_dist = Vector2f { x: 0., y: 0. };
-        let mut delta_dist = Vector2f { x: 0., y: 0. };
-        let mut step = Vector2i { x: 0, y: 0 };
         let mut draw_start: i32 = 0;
         let mut draw_end: i32 = 0;
+        let mut line_height: i32 = 0;
         let mut camera_x: f32;
-        let mut side: i32;
         let mut perpendicular_wall_dist: f32 = 0.;
         let mut wall_x: f32 = 0.;
+        let mut wall_offset: f32;
+        let mut texture_shift: f32;
         for x in 0..(self.window_size.x as i32) {
             // initialize
             camera_x = 2. * x as f32 / self.window_size.x - 1.;
-            ray_dir.x = self.vector_direction.x + self.cam_plane.x * camera_x;
-            ray_dir.y = self.vector_direction.y + self.cam_plane.y * camera_x;
-            map_pos.x = ray_pos.x as i32;
-            map_pos.y = ray_pos.y as i32;
-            delta_dist.x = (1. + (ray_dir.y * ray_dir.y) / (ray_dir.x * ray_dir.x)).sqrt();
-            delta_dist.y = (1. + (ray_dir.x * ray_dir.x) / (ray_dir.y * ray_dir.y)).sqrt();
-            side = 0;
+            ray_dir.x = self.view.direction.x + self.view.plane.x * camera_x;
+            ray_dir.y = self.view.direction.y + self.view.plane.y * camera_x;
+            wall_offset = 0.;
+            texture_shift = 0.;
 
             // calculate
-            self.calculate_step(
-                &ray_dir,
-                &mut step,
-                &ray_pos,
-                &map_pos,
-                &delta_dist,
-                &mut side_dist,
-            );
-
-            self.hit_wall(
-                &mut map_pos,
-                &mut side_dist,
-                &mut step,
-                &mut delta_dist,
-                &mut side,
-            );
+            let RayHit {
+                tile: map_pos,
+                side,
+                step,
+            } = self.hit_wall(&ray_pos, &ray_dir, &mut wall_offset, &mut texture_shift);
 
             self.calculate_wall_height(
                 side,
                 &mut draw_start,
                 &mut draw_end,
+                &mut line_height,
                 &map_pos,
                 &ray_pos,
                 &ray_dir,
                 &step,
+                wall_offset,
                 &mut perpendicular_wall_dist,
             );
+            self.z_buffer.push(perpendicular_wall_dist);
 
-            self.calculate_wall_texture(
+            self.draw_wall_column(
                 side,
                 &ray_dir,
                 x,
@@ -105,94 +304,157 @@ impl RaycastEngine {
                 &ray_pos,
                 draw_end,
                 draw_start,
+                line_height,
+                wall_offset,
+                texture_shift,
                 &mut wall_x,
+                texture_loader,
             );
 
-            if !self.no_ground {
-                self.calculate_ground(
+            if let Some((ground, sky)) = floor_textures {
+                self.draw_ground_column(
                     side,
                     &map_pos,
                     wall_x,
+                    wall_offset,
                     &ray_dir,
                     perpendicular_wall_dist,
                     &mut draw_end,
                     x,
+                    ground,
+                    sky,
                 );
             }
         }
-        self.update_events(event_handler);
+        self.draw_sprites(sprites, texture_loader);
+        &self.frame
+    }
+
+    fn draw_sprites(&mut self, sprites: &[Sprite], texture_loader: &TextureLoader) {
+        let distance = |sprite: &Sprite| {
+            let x = sprite.position.x - self.view.position.x;
+            let y = sprite.position.y - self.view.position.y;
+            x * x + y * y
+        };
+        let mut sorted: Vec<&Sprite> = sprites.iter().collect();
+        sorted.sort_by(|a, b| distance(b).partial_cmp(&distance(a)).unwrap());
+
+        // Inverse of the camera matrix [plane direction]
+        let inv_det = 1.
+            / (self.view.plane.x * self.view.direction.y
+                - self.view.direction.x * self.view.plane.y);
+        for sprite in sorted {
+            let relative = Vector2f::new(
+                sprite.position.x - self.view.position.x,
+                sprite.position.y - self.view.position.y,
+            );
+            let transform_x =
+                inv_det * (self.view.direction.y * relative.x - self.view.direction.x * relative.y);
+            // Depth of the sprite, like the perpendicular distance of walls
+            let transform_y =
+                inv_det * (-self.view.plane.y * relative.x + self.view.plane.x * relative.y);
+            if transform_y <= 0.1 {
+                continue;
+            }
+
+            let screen_x = (self.window_size.x / 2.) * (1. + transform_x / transform_y);
+            let size = (self.window_size.y / transform_y).abs();
+            let draw_start = self.window_size.y / 2. - size / 2.;
+            let draw_end = self.window_size.y / 2. + size / 2.;
+            let left = screen_x - size / 2.;
+            let first_stripe = (left.max(0.)) as i32;
+            let last_stripe = ((screen_x + size / 2.).min(self.window_size.x)) as i32;
+            let first_row = draw_start.max(0.) as i32;
+            let last_row = draw_end.min(self.window_size.y) as i32;
+
+            let texture = texture_loader.get_texture_data(sprite.texture_id);
+            let (texture_width, texture_height) =
+                (texture.get_width() as f32, texture.get_height() as f32);
+            for stripe in first_stripe..last_stripe {
+                // Walls in front of the sprite hide this column
+                if transform_y >= self.z_buffer[stripe as usize] {
+                    continue;
+                }
+                let texture_x = ((stripe as f32 - left) * texture_width / size) as u32;
+                for y in first_row..last_row {
+                    let texture_y = ((y as f32 - draw_start) * texture_height / size) as u32;
+                    let pixel = texture.get_pixel(texture_x, texture_y);
+                    // Transparent pixels let the wall behind show through
+                    if pixel[3] >= 128 {
+                        self.frame.set_pixel(stripe as u32, y as u32, pixel);
+                    }
+                }
+            }
+        }
     }
 
-    fn calculate_ground(
+    fn draw_ground_column(
         &mut self,
         side: i32,
         map_pos: &Vector2i,
         wall_x: f32,
+        wall_offset: f32,
         ray_dir: &Vector2f,
         perpendicular_wall_dist: f32,
         draw_end: &mut i32,
         x: i32,
+        ground: &TextureData,
+        sky: &TextureData,
     ) {
         if *draw_end < 0 {
             *draw_end = self.window_size.y as i32;
         }
 
-        self.ground.get_mut(x as usize).unwrap().clear();
-        self.sky.get_mut(x as usize).unwrap().clear();
-
-        let mut vertex = Vertex::default();
         let mut current_dist: f32;
         let mut weight: f32;
         let mut current_floor = Vector2f { x: 0., y: 0. };
-        let mut tex_coord = Vector2f { x: 0., y: 0. };
-        let mut pos = Vector2f { x: x as f32, y: 0. };
         let dist_player: f32 = 0.;
 
         let (map_pos_x, map_pos_y) = (map_pos.x as f32, map_pos.y as f32);
         let floor = if side == 0 && ray_dir.x > 0. {
             Vector2f {
-                x: map_pos_x,
+                x: map_pos_x + wall_offset,
                 y: map_pos_y + wall_x,
             }
         } else if side == 0 && ray_dir.x < 0. {
             Vector2f {
-                x: map_pos_x + 1.,
+                x: map_pos_x + 1. - wall_offset,
                 y: map_pos_y + wall_x,
             }
         } else if side == 1 && ray_dir.y > 0. {
             Vector2f {
                 x: map_pos_x + wall_x,
-                y: map_pos_y,
+                y: map_pos_y + wall_offset,
             }
         } else {
             Vector2f {
                 x: map_pos_x + wall_x,
-                y: map_pos_y + 1.,
+                y: map_pos_y + 1. - wall_offset,
             }
         };
 
         for y in (*draw_end + 1)..(self.window_size.y as i32) {
             current_dist = self.window_size.y / (2. * y as f32 - self.window_size.y as f32);
             weight = (current_dist - dist_player) / (perpendicular_wall_dist - dist_player);
-            current_floor.x = weight * floor.x + (1. - weight) * self.player_position.x;
-            current_floor.y = weight * floor.y + (1. - weight) * self.player_position.y;
-
-            tex_coord.x = ((current_floor.x * 128.) as i32 % 128) as f32;
-            tex_coord.y = ((current_floor.y * 128.) as i32 % 128) as f32;
-
-            pos.y = y as f32;
-            vertex.position.x = pos.x;
-            vertex.position.y = pos.y;
-            vertex.tex_coords.x = tex_coord.x;
-            vertex.tex_coords.y = tex_coord.y;
-            self.ground.get_mut(x as usize).unwrap().append(&vertex);
-
-            pos.y = self.window_size.y - y as f32;
-            vertex.position.x = pos.x;
-            vertex.position.y = pos.y;
-            vertex.tex_coords.x = tex_coord.x;
-            vertex.tex_coords.y = tex_coord.y;
-            self.sky.get_mut(x as usize).unwrap().append(&vertex);
+            current_floor.x = weight * floor.x + (1. - weight) * self.view.position.x;
+            current_floor.y = weight * floor.y + (1. - weight) * self.view.position.y;
+
+            // The ceiling mirrors the floor, sampled at the same spot
+            let texture_pixel = |texture: &TextureData| {
+                let tex_x = (current_floor.x * texture.get_width() as f32) as i32;
+                let tex_y = (current_floor.y * texture.get_height() as f32) as i32;
+                texture.get_pixel(
+                    tex_x.rem_euclid(texture.get_width() as i32) as u32,
+                    tex_y.rem_euclid(texture.get_height() as i32) as u32,
+                )
+            };
+            self.frame
+                .set_pixel(x as u32, y as u32, texture_pixel(ground));
+            self.frame.set_pixel(
+                x as u32,
+                (self.window_size.y as i32 - y) as u32,
+                texture_pixel(sky),
+            );
         }
     }
 
@@ -201,35 +463,39 @@ impl RaycastEngine {
         side: i32,
         draw_start: &mut i32,
         draw_end: &mut i32,
+        line_height: &mut i32,
         map_pos: &Vector2i,
         ray_pos: &Vector2f,
         ray_dir: &Vector2f,
         step: &Vector2i,
+        wall_offset: f32,
         perpendicular_wall_dist: &mut f32,
     ) {
         *perpendicular_wall_dist = if side == 0 {
-            (map_pos.x as f32 - ray_pos.x + (1 - step.x) as f32 / 2.) / ray_dir.x
+            (map_pos.x as f32 - ray_pos.x + (1 - step.x) as f32 / 2. + wall_offset * step.x as f32)
+                / ray_dir.x
         } else {
-            (map_pos.y as f32 - ray_pos.y + (1 - step.y) as f32 / 2.) / ray_dir.y
+            (map_pos.y as f32 - ray_pos.y + (1 - step.y) as f32 / 2. + wall_offset * step.y as f32)
+                / ray_dir.y
         }
         .abs();
 
-        let line_height: i32 = if *perpendicular_wall_dist as i32 == 0 {
+        *line_height = if *perpendicular_wall_dist as i32 == 0 {
             self.window_size.y as i32
         } else {
             ((self.window_size.y / *perpendicular_wall_dist) as i32).abs()
         };
-        *draw_start = (self.window_size.y as i32 / 2) - (line_height / 2);
+        *draw_start = (self.window_size.y as i32 / 2) - (*line_height / 2);
         if *draw_start < 0 {
             *draw_start = 0;
         }
-        *draw_end = line_height / 2 + self.window_size.y as i32 / 2;
+        *draw_end = *line_height / 2 + self.window_size.y as i32 / 2;
         if *draw_end > self.window_size.y as i32 {
             *draw_end = self.window_size.y as i32 - 1;
         }
     }
 
-    fn calculate_wall_texture(
+    fn draw_wall_column(
         &mut self,
         side: i32,
         ray_dir: &Vector2f,
@@ -239,158 +505,187 @@ impl RaycastEngine {
         ray_pos: &Vector2f,
         draw_end: i32,
         draw_start: i32,
+        line_height: i32,
+        wall_offset: f32,
+        texture_shift: f32,
         wall_x: &mut f32,
+        texture_loader: &TextureLoader,
     ) {
-        let mut texture_id = self
+        let block = self
             .map
-            .get_block(map_pos)
+            .get_drawn_block(map_pos)
             .expect(&format!("ERROR: Cannot get block ID {:?}", map_pos));
 
         *wall_x = if side == 1 {
             ray_pos.x
-                + ((map_pos.y as f32 - ray_pos.y + (1. - step.y as f32) / 2.) / ray_dir.y)
+                + ((map_pos.y as f32 - ray_pos.y
+                    + (1. - step.y as f32) / 2.
+                    + wall_offset * step.y as f32)
+                    / ray_dir.y)
                     * ray_dir.x
         } else {
             ray_pos.y
-                + ((map_pos.x as f32 - ray_pos.x + (1. - step.x as f32) / 2.) / ray_dir.x)
+                + ((map_pos.x as f32 - ray_pos.x
+                    + (1. - step.x as f32) / 2.
+                    + wall_offset * step.x as f32)
+                    / ray_dir.x)
                     * ray_dir.y
         };
         *wall_x -= wall_x.floor();
 
-        let mut texture_x = (*wall_x * 128.) as i32;
+        let texture = texture_loader.get_wall_texture_data(block, side == 1);
+        let texture_width = texture.get_width() as i32;
+        // Doors and push-walls slide their texture along with them
+        let mut texture_x =
+            ((*wall_x - texture_shift).rem_euclid(1.) * texture_width as f32) as i32;
         if side == 0 && ray_dir.x > 0. {
-            texture_x = 128 - texture_x - 1;
+            texture_x = texture_width - texture_x - 1;
         }
         if side == 1 && ray_dir.y < 0. {
-            texture_x = 128 - texture_x - 1;
+            texture_x = texture_width - texture_x - 1;
         }
-
-        if side == 1 {
-            texture_id += 5;
+        let texture_x = texture_x.max(0) as u32;
+
+        // Top of the whole wall slice, which may be above the view when close to it
+        let top = self.window_size.y as i32 / 2 - line_height / 2;
+        for y in draw_start..=draw_end.min(self.window_size.y as i32 - 1) {
+            // The last row of the slice falls on the bottom edge of the texture
+            let texture_y = ((y - top) * texture.get_height() as i32 / line_height.max(1))
+                .min(texture.get_height() as i32 - 1) as u32;
+            self.frame
+                .set_pixel(x as u32, y as u32, texture.get_pixel(texture_x, texture_y));
         }
+    }
 
-        self.textures_id.push(texture_id);
-        self.vertex_array.get_mut(x as usize).unwrap().clear();
-        self.vertex_array
-            .get_mut(x as usize)
-            .unwrap()
-            .append(&Vertex::new(
-                Vector2f::new(x as f32, draw_end as f32),
-                Color::WHITE,
-                Vector2f::new(texture_x as f32, 128.),
-            ));
-        self.vertex_array
-            .get_mut(x as usize)
-            .unwrap()
-            .append(&Vertex::new(
-                Vector2f::new(x as f32, draw_start as f32),
-                Color::WHITE,
-                Vector2f::new(texture_x as f32, 0.),
-            ));
-    }
-
-    fn calculate_step(
+    /// Casts a ray to the wall, door panel or push-wall face it hits. Doors and push-walls give
+    /// the depth of their face in the tile and how far their texture slid.
+    fn hit_wall(
         &self,
-        ray_dir: &Vector2f,
-        step: &mut Vector2i,
         ray_pos: &Vector2f,
-        map_pos: &Vector2i,
-        delta_dist: &Vector2f,
-        side_dist: &mut Vector2f,
-    ) {
-        if ray_dir.x < 0. {
-            step.x = -1;
-            side_dist.x = (ray_pos.x - map_pos.x as f32) * delta_dist.x;
-        } else {
-            step.x = 1;
-            side_dist.x = (map_pos.x as f32 + 1. - ray_pos.x) * delta_dist.x;
-        }
-        if ray_dir.y < 0. {
-            step.y = -1;
-            side_dist.y = (ray_pos.y - map_pos.y as f32) * delta_dist.y;
-        } else {
-            step.y = 1;
-            side_dist.y = (map_pos.y as f32 + 1. - ray_pos.y) * delta_dist.y;
+        ray_dir: &Vector2f,
+        wall_offset: &mut f32,
+        texture_shift: &mut f32,
+    ) -> RayHit {
+        // Push-walls are hit on the face of the block, whatever tile border the ray crossed
+        let mut push_wall_side = None;
+        let mut hit = cast_ray(ray_pos, ray_dir, |map_pos, side| {
+            match (self.map.get_block(map_pos), self.map.get_door(map_pos)) {
+                (Some(0), _) => match self.map.get_moving_wall() {
+                    Some(moving_wall) if moving_wall.covers(map_pos) => {
+                        match RaycastEngine::hit_push_wall(moving_wall, map_pos, ray_pos, ray_dir) {
+                            Some((wall_side, offset, shift)) => {
+                                push_wall_side = Some(wall_side);
+                                *wall_offset = offset;
+                                *texture_shift = shift;
+                                true
+                            }
+                            None => false,
+                        }
+                    }
+                    _ => false,
+                },
+                (Some(_), Some(door)) => {
+                    if door.hit_by_ray(side, ray_pos, ray_dir).is_some() {
+                        *wall_offset = 0.5;
+                        *texture_shift = door.get_openness();
+                        true
+                    } else {
+                        false
+                    }
+                }
+                _ => true,
+            }
+        });
+        if let Some(side) = push_wall_side {
+            hit.side = side;
         }
+        hit
     }
 
-    fn hit_wall(
-        &self,
-        map_pos: &mut Vector2i,
-        side_dist: &mut Vector2f,
-        step: &mut Vector2i,
-        delta_dist: &mut Vector2f,
-        side: &mut i32,
-    ) {
-        let mut hit: bool = false;
-        while !hit {
-            if side_dist.x < side_dist.y {
-                side_dist.x += delta_dist.x;
-                map_pos.x += step.x;
-                *side = 0;
+    /// Intersects a ray with the moving push-wall, keeping only the part of the block inside the
+    /// current tile. Returns the side hit, the depth of the face in the tile and the texture
+    /// shift of the block.
+    fn hit_push_wall(
+        moving_wall: &PushWall,
+        map_pos: &Vector2i,
+        ray_pos: &Vector2f,
+        ray_dir: &Vector2f,
+    ) -> Option<(i32, f32, f32)> {
+        let corner = moving_wall.get_position();
+        // Distances along the ray to both faces of the block on one axis, nearest first
+        let faces = |origin: f32, dir: f32, min: f32| {
+            let (t1, t2) = ((min - origin) / dir, (min + 1. - origin) / dir);
+            (t1.min(t2), t1.max(t2))
+        };
+        let (near_x, far_x) = faces(ray_pos.x, ray_dir.x, corner.x);
+        let (near_y, far_y) = faces(ray_pos.y, ray_dir.y, corner.y);
+        let near = near_x.max(near_y);
+        if near > far_x.min(far_y) || near < 0. {
+            return None;
+        }
+
+        let hit = Vector2f::new(ray_pos.x + ray_dir.x * near, ray_pos.y + ray_dir.y * near);
+        let (tile_x, tile_y) = (map_pos.x as f32, map_pos.y as f32);
+        if hit.x < tile_x - 1e-4
+            || hit.x > tile_x + 1. + 1e-4
+            || hit.y < tile_y - 1e-4
+            || hit.y > tile_y + 1. + 1e-4
+        {
+            return None;
+        }
+        if near_x >= near_y {
+            let offset = if ray_dir.x > 0. {
+                hit.x - tile_x
             } else {
-                side_dist.y += delta_dist.y;
-                map_pos.y += step.y;
-                *side = 1;
-            }
-            hit = match self.map.get_block(map_pos) {
-                Some(block) if block == 0 => false,
-                _ => true,
+                tile_x + 1. - hit.x
+            };
+            Some((0, offset, corner.y - corner.y.floor()))
+        } else {
+            let offset = if ray_dir.y > 0. {
+                hit.y - tile_y
+            } else {
+                tile_y + 1. - hit.y
             };
+            Some((1, offset, corner.x - corner.x.floor()))
         }
     }
 
-    fn update_events(&mut self, event_handler: &EventHandler) {
-        let mut pos = Vector2i { x: 0, y: 0 };
-        if event_handler.is_key_pressed(Key::W) {
-            pos.x = (self.player_position.x + (self.vector_direction.x * 0.1)) as i32;
-            pos.y = self.player_position.y as i32;
-            if self
-                .map
-                .get_block(&pos)
-                .expect(&format!("ERROR: Cannot get block ID {:?}", &pos))
-                == 0
-            {
-                self.player_position.x += self.vector_direction.x * 0.1;
-            }
-            pos.y = (self.player_position.y + (self.vector_direction.y * 0.1)) as i32;
-            pos.x = self.player_position.x as i32;
-            if self
-                .map
-                .get_block(&pos)
-                .expect(&format!("ERROR: Cannot get block ID {:?}", &pos))
-                == 0
-            {
-                self.player_position.y += self.vector_direction.y * 0.1;
-            }
-        }
-        if event_handler.is_key_pressed(Key::S) {
-            pos.x = (self.player_position.x - (self.vector_direction.x * 0.1)) as i32;
-            pos.y = self.player_position.y as i32;
-            if self
-                .map
-                .get_block(&pos)
-                .expect(&format!("ERROR: Cannot get block ID {:?}", &pos))
-                == 0
-            {
-                self.player_position.x -= self.vector_direction.x * 0.1;
-            }
-            pos.y = (self.player_position.y - (self.vector_direction.y * 0.1)) as i32;
-            pos.x = self.player_position.x as i32;
-            if self
-                .map
-                .get_block(&pos)
-                .expect(&format!("ERROR: Cannot get block ID {:?}", &pos))
-                == 0
-            {
-                self.player_position.y -= self.vector_direction.y * 0.1;
-            }
+    fn update_events(&mut self, event_handler: &EventHandler, delta: f32) {
+        // Keys give whole values, gamepad sticks anything in between
+        let forward = event_handler.get_action_value(Action::MoveForward)
+            - event_handler.get_action_value(Action::MoveBackward);
+        let strafe = event_handler.get_action_value(Action::StrafeRight)
+            - event_handler.get_action_value(Action::StrafeLeft);
+        let running = event_handler.is_action_held(Action::Run);
+        let speed = if running {
+            MOVE_SPEED * RUN_FACTOR
+        } else {
+            MOVE_SPEED
+        } * delta;
+        // The right of the view is along the camera plane
+        let right = Vector2f::new(self.vector_direction.y, -self.vector_direction.x);
+        let movement = self.vector_direction * forward + right * strafe;
+        let length = (movement.x * movement.x + movement.y * movement.y).sqrt();
+        if length > 0. {
+            // Moving diagonally is not faster
+            let movement = movement * (speed * length.min(1.) / length);
+            self.player_position = collision::slide(
+                &self.map,
+                &self.player_position,
+                self.player_radius,
+                &movement,
+            );
         }
 
-        let mouse_move = match event_handler.has_mouse_moved_event() {
+        let mut mouse_move = match event_handler.has_mouse_moved_event() {
             Some((x, _)) => x as f32 - (self.window_size.x / 2.) as f32,
             None => 0.,
         } / -250.;
+        // Turning right is a negative angle, like moving the mouse right
+        mouse_move += TURN_SPEED.to_radians()
+            * delta
+            * (event_handler.get_action_value(Action::TurnLeft)
+                - event_handler.get_action_value(Action::TurnRight));
 
         let old_dir_x = self.vector_direction.x;
         self.vector_direction.x = self.vector_direction.x * (mouse_move).cos()
@@ -405,45 +700,319 @@ impl RaycastEngine {
             old_cam_plane_x * (mouse_move).sin() + self.cam_plane.y * (mouse_move).cos();
     }
 
-    fn create_line_array(window_size: &Vector2f) -> Vec<Box<VertexArray>> {
-        let mut lines: Vec<Box<VertexArray>> = Vec::new();
-        for _ in 0..(window_size.x as i32) {
-            let mut line: Box<VertexArray> = Box::new(VertexArray::default());
-            line.set_primitive_type(PrimitiveType::Lines);
-            lines.push(line);
+    /// Opens or closes the door right in front of the player, or pushes a secret wall there.
+    /// Returns the key the player misses when the door is locked.
+    ///
+    /// # Arguments
+    /// `occupied` - Tiles where actors stand, doors do not close on them and push-walls do not
+    /// slide into them
+    ///
+    /// `has_key` - Whether the player holds a key
+    pub fn use_tile<F: Fn(KeyColor) -> bool>(
+        &mut self,
+        occupied: &[Vector2i],
+        has_key: F,
+    ) -> Option<KeyColor> {
+        let player_tile =
+            Vector2i::new(self.player_position.x as i32, self.player_position.y as i32);
+        // Use the tile next to the player along the axis the player mostly faces
+        let facing = if self.vector_direction.x.abs() > self.vector_direction.y.abs() {
+            Vector2i::new(self.vector_direction.x.signum() as i32, 0)
+        } else {
+            Vector2i::new(0, self.vector_direction.y.signum() as i32)
+        };
+        let target = player_tile + facing;
+        match self.map.use_door(&target, occupied, has_key) {
+            DoorUse::NoDoor => {
+                self.map.push_wall(&target, facing, occupied);
+                None
+            }
+            DoorUse::Used => None,
+            DoorUse::Locked(key) => Some(key),
         }
-        lines
     }
 
-    fn create_ground_array(window_size: &Vector2f) -> Vec<Box<VertexArray>> {
-        let mut lines: Vec<Box<VertexArray>> = Vec::new();
-        for _ in 0..(window_size.x as i32) {
-            let line: Box<VertexArray> = Box::new(VertexArray::default());
-            lines.push(line);
-        }
-        lines
+    pub fn get_map(&self) -> &Map {
+        &self.map
+    }
+
+    pub fn get_map_mut(&mut self) -> &mut Map {
+        &mut self.map
+    }
+
+    /// Sets the radius of the player's body, in tiles, [PLAYER_RADIUS] by default.
+    pub fn set_player_radius(&mut self, radius: f32) {
+        self.player_radius = radius;
+    }
+
+    /// Puts the player back on the spawn of the map.
+    pub fn respawn(&mut self) {
+        let spawn = *self.map.get_spawn();
+        self.player_position = spawn.position;
+        self.vector_direction = spawn.direction;
+        self.cam_plane = Vector2f {
+            x: spawn.direction.y * 0.66,
+            y: -spawn.direction.x * 0.66,
+        };
+        // Do not slide the view from where the player died
+        self.previous = self.camera();
+        self.view = self.previous;
     }
 
     pub fn get_player_pos(&self) -> Vector2f {
         self.player_position.clone()
     }
 
-    pub fn draw<'r>(&self, render_window: &'r mut RenderWindow, texture_loader: &'r TextureLoader) {
-        let mut render_states = RenderStates::default();
-        for (line_idx, line) in self.vertex_array.iter().enumerate() {
-            render_states.texture =
-                Some(texture_loader.get_texture(self.textures_id[line_idx as usize]));
-            render_window.draw_with_renderstates(&*(*line), render_states);
+    pub fn get_player_dir(&self) -> Vector2f {
+        self.vector_direction
+    }
+
+    /// Writes the player pose and the map to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_vector2f(&self.player_position);
+        writer.write_vector2f(&self.vector_direction);
+        writer.write_vector2f(&self.cam_plane);
+        self.map.save_state(writer);
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        self.player_position = reader.read_vector2f()?;
+        self.vector_direction = reader.read_vector2f()?;
+        self.cam_plane = reader.read_vector2f()?;
+        self.map.load_state(reader)?;
+        // Do not slide the view from where the player was before loading
+        self.previous = self.camera();
+        self.view = self.previous;
+        Ok(())
+    }
+
+    /// Uploads the last rendered frame and draws it at the top left of the window.
+    pub fn draw(&mut self, render_window: &mut RenderWindow) {
+        let (width, height) = (self.frame.get_width(), self.frame.get_height());
+        let texture = self.frame_texture.get_or_insert_with(|| {
+            Texture::new(width, height).expect("ERROR: Cannot create the view texture")
+        });
+        texture.update_from_pixels(self.frame.get_pixels(), width, height, 0, 0);
+        let render_states = RenderStates {
+            texture: Some(&**texture),
+            ..RenderStates::default()
+        };
+        render_window.draw_with_renderstates(&self.frame_quad, render_states);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::random::Random;
+
+    const REFERENCE_STEP: f64 = 1e-3;
+
+    const RED: [u8; 4] = [255, 0, 0, 255];
+
+    /// Renders a red sprite two tiles beyond a pillar, with or without the pillar, and returns
+    /// whether each column shows the sprite across the horizon.
+    fn sprite_columns(with_pillar: bool) -> Vec<bool> {
+        let pillar = if with_pillar { "#..#..#" } else { "#.....#" };
+        let level = format!(
+            "# = 1\nmap:\n#######\n#.....#\n#.....#\n{}\n#.....#\n#..^..#\n#######\n",
+            pillar
+        );
+        let mut texture_loader = TextureLoader::headless();
+        let plain = |color: [u8; 4]| {
+            let pixels: Vec<u8> = (0..64 * 64).flat_map(|_| color.to_vec()).collect();
+            rsfml::graphics::Image::create_from_pixels(64, 64, &pixels).unwrap()
+        };
+        for name in ["wall_1", "wall_1_dark"].iter() {
+            texture_loader
+                .load_named_image(name, &plain([90, 90, 90, 255]))
+                .unwrap();
         }
+        let sprite_id = texture_loader
+            .load_named_image("sprite_1", &plain(RED))
+            .unwrap();
+        let map = Map::parse_ascii(&level).unwrap();
+        let mut engine = RaycastEngine::new(map, &Vector2f::new(160., 100.), true);
+        let sprites = [Sprite::new(Vector2f::new(4.5, 1.5), sprite_id)];
+        let frame = engine.render(&sprites, &texture_loader);
+        (0..frame.get_width())
+            .map(|x| frame.get_pixel(x, frame.get_height() / 2) == Some(RED))
+            .collect()
+    }
+
+    #[test]
+    fn walls_in_front_hide_part_of_a_sprite() {
+        let open = sprite_columns(false);
+        let clipped = sprite_columns(true);
+        assert!(open.iter().any(|&red| red));
+        // The pillar hides the columns of the sprite behind it and leaves the others
+        assert!(clipped.iter().any(|&red| red));
+        assert!(open
+            .iter()
+            .zip(clipped.iter())
+            .any(|(&open, &clipped)| open && !clipped));
+        assert!(clipped
+            .iter()
+            .zip(open.iter())
+            .all(|(&clipped, &open)| !clipped || open));
+    }
 
-        render_states.texture = Some(texture_loader.get_texture(0));
-        for gr in self.ground.iter() {
-            render_window.draw_with_renderstates(&*(*gr), render_states);
+    /// Builds a map of solid tiles, surrounded by walls and with random pillars inside.
+    fn random_grid(random: &mut Random) -> (Vector2i, Vec<bool>) {
+        let size = Vector2i::new(
+            4 + (random.next_f32() * 12.) as i32,
+            4 + (random.next_f32() * 12.) as i32,
+        );
+        let mut solid = Vec::new();
+        for y in 0..size.y {
+            for x in 0..size.x {
+                let border = x == 0 || y == 0 || x == size.x - 1 || y == size.y - 1;
+                solid.push(border || random.next_f32() < 0.15);
+            }
         }
+        (size, solid)
+    }
+
+    fn is_solid(size: Vector2i, solid: &[bool], tile: &Vector2i) -> bool {
+        tile.x < 0
+            || tile.y < 0
+            || tile.x >= size.x
+            || tile.y >= size.y
+            || solid[(tile.y * size.x + tile.x) as usize]
+    }
 
-        render_states.texture = Some(texture_loader.get_texture(11));
-        for sky in self.sky.iter() {
-            render_window.draw_with_renderstates(&*(*sky), render_states);
+    /// Walks the ray in tiny steps and returns the first solid tile entered, with the side it
+    /// was entered from. Returns None when the ray passes too close to a tile corner to tell
+    /// which border it crossed first.
+    fn reference_hit(
+        size: Vector2i,
+        solid: &[bool],
+        origin: &Vector2f,
+        dir: &Vector2f,
+    ) -> Option<(Vector2i, i32)> {
+        let (origin_x, origin_y) = (origin.x as f64, origin.y as f64);
+        let length = (dir.x as f64).hypot(dir.y as f64);
+        let (dir_x, dir_y) = (dir.x as f64 / length, dir.y as f64 / length);
+        let mut tile = Vector2i::new(origin_x.floor() as i32, origin_y.floor() as i32);
+        let near_border = |value: f64| {
+            let fract = value - value.floor();
+            !(REFERENCE_STEP..=1. - REFERENCE_STEP).contains(&fract)
+        };
+        for i in 1.. {
+            let distance = i as f64 * REFERENCE_STEP;
+            let (x, y) = (origin_x + dir_x * distance, origin_y + dir_y * distance);
+            let next = Vector2i::new(x.floor() as i32, y.floor() as i32);
+            let side = match (next.x != tile.x, next.y != tile.y) {
+                (false, false) => continue,
+                (true, false) if !near_border(y) || dir_y == 0. => 0,
+                (false, true) if !near_border(x) || dir_x == 0. => 1,
+                _ => return None,
+            };
+            tile = next;
+            if is_solid(size, solid, &tile) {
+                return Some((tile, side));
+            }
         }
+        None
+    }
+
+    /// Picks a ray start in an open tile, sometimes right on a tile border or corner.
+    fn random_origin(random: &mut Random, size: Vector2i, solid: &[bool]) -> Vector2f {
+        loop {
+            let tile = Vector2i::new(
+                (random.next_f32() * size.x as f32) as i32,
+                (random.next_f32() * size.y as f32) as i32,
+            );
+            if is_solid(size, solid, &tile) {
+                continue;
+            }
+            let mut origin = Vector2f::new(
+                tile.x as f32 + random.next_f32(),
+                tile.y as f32 + random.next_f32(),
+            );
+            match random.next_byte() % 4 {
+                0 => origin.x = origin.x.floor(),
+                1 => origin.y = origin.y.floor(),
+                2 => origin = Vector2f::new(origin.x.floor(), origin.y.floor()),
+                _ => (),
+            }
+            return origin;
+        }
+    }
+
+    /// Picks a ray direction, sometimes along an axis or along a diagonal.
+    fn random_direction(random: &mut Random) -> Vector2f {
+        let angle = random.next_f32() * std::f32::consts::PI * 2.;
+        let sign = |value: f32| if value < 0.5 { -1. } else { 1. };
+        match random.next_byte() % 5 {
+            0 => Vector2f::new(sign(random.next_f32()), 0.),
+            1 => Vector2f::new(0., sign(random.next_f32())),
+            2 => Vector2f::new(sign(random.next_f32()), sign(random.next_f32())),
+            _ => Vector2f::new(angle.cos(), angle.sin()),
+        }
+    }
+
+    #[test]
+    fn cast_ray_matches_a_fine_step_walk() {
+        let mut random = Random::new(0x5EED);
+        let (mut checked, mut skipped) = (0, 0);
+        for _ in 0..5000 {
+            let (size, solid) = random_grid(&mut random);
+            let origin = random_origin(&mut random, size, &solid);
+            let dir = random_direction(&mut random);
+            let expected = match reference_hit(size, &solid, &origin, &dir) {
+                Some(expected) => expected,
+                None => {
+                    skipped += 1;
+                    continue;
+                }
+            };
+            let hit = cast_ray(&origin, &dir, |tile, _| is_solid(size, &solid, tile));
+            assert_eq!(
+                (hit.tile, hit.side),
+                expected,
+                "ray from {:?} towards {:?}",
+                origin,
+                dir
+            );
+            checked += 1;
+        }
+        assert!(
+            checked > skipped * 4,
+            "{} rays checked, {} skipped",
+            checked,
+            skipped
+        );
+    }
+
+    #[test]
+    fn cast_ray_along_axes_from_tile_borders() {
+        let hit_x = |x| move |tile: &Vector2i, _| tile.x == x;
+        let hit_y = |y| move |tile: &Vector2i, _| tile.y == y;
+        let origin = Vector2f::new(3., 3.);
+        let hit = cast_ray(&origin, &Vector2f::new(-1., 0.), hit_x(0));
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(0, 3), 0));
+        let hit = cast_ray(&origin, &Vector2f::new(1., 0.), hit_x(6));
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(6, 3), 0));
+        let hit = cast_ray(&origin, &Vector2f::new(0., -1.), hit_y(0));
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(3, 0), 1));
+        let hit = cast_ray(&origin, &Vector2f::new(0., 1.), hit_y(6));
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(3, 6), 1));
+    }
+
+    #[test]
+    fn cast_ray_grazing_a_corner() {
+        // Tiles (2, 3) and (3, 2) are solid, the ray passes just beside their common corner
+        let solid = |tile: &Vector2i, _| {
+            *tile == Vector2i::new(2, 3) || *tile == Vector2i::new(3, 2) || tile.x > 3
+        };
+        let origin = Vector2f::new(1.5, 1.5);
+        let hit = cast_ray(&origin, &Vector2f::new(1.5, 1.501), solid);
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(2, 3), 1));
+        let hit = cast_ray(&origin, &Vector2f::new(1.501, 1.5), solid);
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(3, 2), 0));
+        // Right through the corner, the ray steps along y first
+        let hit = cast_ray(&origin, &Vector2f::new(1., 1.), solid);
+        assert_eq!((hit.tile, hit.side), (Vector2i::new(2, 3), 1));
     }
 }
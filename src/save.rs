@@ -0,0 +1,455 @@
+//! Module for saved games: the state of a level in play, written to a file with a thumbnail of
+//! the view and read back into a game of the same level
+
+use std::{fmt, fs, path::Path};
+
+use rsfml::system::{Vector2f, Vector2i};
+
+use crate::framebuffer::FrameBuffer;
+
+/// Version of the saves written. Older or newer saves are refused, as their state does not
+/// match the game.
+pub const SAVE_VERSION: u16 = 1;
+
+/// File written and read by the quicksave and quickload keys.
+pub const QUICKSAVE_PATH: &str = "saves/quicksave.sav";
+
+/// Size of the thumbnail of the view stored in saves.
+pub const THUMBNAIL_WIDTH: u32 = 96;
+pub const THUMBNAIL_HEIGHT: u32 = 60;
+
+/// First bytes of a save file.
+const SAVE_MAGIC: &[u8; 4] = b"R3DS";
+
+/// Error raised while writing or reading a save.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SaveError {
+    Io(String),
+    Corrupt(String),
+    Version(u16),
+    OtherLevel(String),
+}
+
+impl fmt::Display for SaveError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SaveError::Io(message) => write!(f, "ERROR: {}", message),
+            SaveError::Corrupt(message) => write!(f, "ERROR: Corrupt save ({})", message),
+            SaveError::Version(version) => write!(
+                f,
+                "ERROR: The save has version {}, this game reads version {}",
+                version, SAVE_VERSION
+            ),
+            SaveError::OtherLevel(map_name) => {
+                write!(f, "ERROR: The save is of another level ({})", map_name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for SaveError {}
+
+impl SaveError {
+    /// Returns a short text telling the player why the game was not loaded.
+    pub fn get_summary(&self) -> &'static str {
+        match self {
+            SaveError::Io(_) => "Cannot read or write the save",
+            SaveError::Corrupt(_) => "The save is damaged",
+            SaveError::Version(_) => "The save is from another version",
+            SaveError::OtherLevel(_) => "The save is of another level",
+        }
+    }
+}
+
+/// Collects the state of the game, in the order it is read back by a [StateReader].
+#[derive(Clone, Debug, Default)]
+pub struct StateWriter {
+    bytes: Vec<u8>,
+}
+
+impl StateWriter {
+    pub fn new() -> StateWriter {
+        StateWriter::default()
+    }
+
+    pub fn write_u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    pub fn write_bool(&mut self, value: bool) {
+        self.bytes.push(value as u8);
+    }
+
+    pub fn write_u32(&mut self, value: u32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_i32(&mut self, value: i32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_f32(&mut self, value: f32) {
+        self.bytes.extend_from_slice(&value.to_le_bytes());
+    }
+
+    pub fn write_vector2f(&mut self, value: &Vector2f) {
+        self.write_f32(value.x);
+        self.write_f32(value.y);
+    }
+
+    pub fn write_vector2i(&mut self, value: &Vector2i) {
+        self.write_i32(value.x);
+        self.write_i32(value.y);
+    }
+
+    pub fn into_bytes(self) -> Vec<u8> {
+        self.bytes
+    }
+}
+
+/// Reads back the state collected by a [StateWriter].
+pub struct StateReader<'b> {
+    bytes: &'b [u8],
+    offset: usize,
+}
+
+impl<'b> StateReader<'b> {
+    pub fn new(bytes: &'b [u8]) -> StateReader<'b> {
+        StateReader { bytes, offset: 0 }
+    }
+
+    /// Reads the next `length` bytes as they are.
+    pub fn read_bytes(&mut self, length: usize) -> Result<&'b [u8], SaveError> {
+        let slice = self
+            .bytes
+            .get(self.offset..self.offset + length)
+            .ok_or_else(|| SaveError::Corrupt(String::from("the data is cut short")))?;
+        self.offset += length;
+        Ok(slice)
+    }
+
+    fn take<const N: usize>(&mut self) -> Result<[u8; N], SaveError> {
+        let mut array = [0; N];
+        array.copy_from_slice(self.read_bytes(N)?);
+        Ok(array)
+    }
+
+    pub fn read_u8(&mut self) -> Result<u8, SaveError> {
+        Ok(self.take::<1>()?[0])
+    }
+
+    pub fn read_bool(&mut self) -> Result<bool, SaveError> {
+        match self.read_u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(SaveError::Corrupt(format!("{} is not a boolean", value))),
+        }
+    }
+
+    pub fn read_u16(&mut self) -> Result<u16, SaveError> {
+        Ok(u16::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_u32(&mut self) -> Result<u32, SaveError> {
+        Ok(u32::from_le_bytes(self.take()?))
+    }
+
+    pub fn read_i32(&mut self) -> Result<i32, SaveError> {
+        Ok(i32::from_le_bytes(self.take()?))
+    }
+
+    /// Reads a number, refusing NaN and infinities: every float of the game state is a
+    /// position, a direction or a time.
+    pub fn read_f32(&mut self) -> Result<f32, SaveError> {
+        let value = f32::from_le_bytes(self.take()?);
+        if !value.is_finite() {
+            return Err(SaveError::Corrupt(format!(
+                "{} is not a finite number",
+                value
+            )));
+        }
+        Ok(value)
+    }
+
+    pub fn read_vector2f(&mut self) -> Result<Vector2f, SaveError> {
+        Ok(Vector2f::new(self.read_f32()?, self.read_f32()?))
+    }
+
+    pub fn read_vector2i(&mut self) -> Result<Vector2i, SaveError> {
+        Ok(Vector2i::new(self.read_i32()?, self.read_i32()?))
+    }
+
+    /// Reads the length of a list and checks it matches the game, e.g. the number of doors.
+    ///
+    /// # Arguments
+    /// `expected` - The length in the game the state is read into
+    ///
+    /// `what` - What the list holds, for the error
+    pub fn read_count(&mut self, expected: usize, what: &str) -> Result<(), SaveError> {
+        let count = self.read_u32()? as usize;
+        if count != expected {
+            return Err(SaveError::Corrupt(format!(
+                "{} {} saved, the level has {}",
+                count, what, expected
+            )));
+        }
+        Ok(())
+    }
+
+    /// Whether every byte of the state was read.
+    pub fn is_at_end(&self) -> bool {
+        self.offset == self.bytes.len()
+    }
+}
+
+/// A saved game: which level, a thumbnail of the view and the state of the game.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SaveGame {
+    // Where the level was loaded from, see [MapSource](crate::MapSource)
+    map_name: String,
+    map_checksum: u32,
+    thumbnail: FrameBuffer,
+    state: Vec<u8>,
+}
+
+impl SaveGame {
+    /// Creates a save.
+    ///
+    /// # Arguments
+    /// `map_name` - Where the level was loaded from, to load it again
+    ///
+    /// `map_checksum` - The [map_checksum](crate::demo::map_checksum) of the level as it
+    /// started
+    ///
+    /// `view` - The view of the game, shrunk to the thumbnail
+    ///
+    /// `state` - The state of the game, from a [StateWriter]
+    pub fn new(map_name: &str, map_checksum: u32, view: &FrameBuffer, state: Vec<u8>) -> SaveGame {
+        // The thumbnail is stored without alpha, like the view it shows
+        let mut thumbnail = view.scaled(THUMBNAIL_WIDTH, THUMBNAIL_HEIGHT);
+        for y in 0..THUMBNAIL_HEIGHT {
+            for x in 0..THUMBNAIL_WIDTH {
+                let [r, g, b, _] = thumbnail.get_pixel(x, y).unwrap();
+                thumbnail.set_pixel(x, y, [r, g, b, 255]);
+            }
+        }
+        SaveGame {
+            map_name: String::from(map_name),
+            map_checksum,
+            thumbnail,
+            state,
+        }
+    }
+
+    pub fn get_map_name(&self) -> &str {
+        &self.map_name
+    }
+
+    pub fn get_map_checksum(&self) -> u32 {
+        self.map_checksum
+    }
+
+    pub fn get_thumbnail(&self) -> &FrameBuffer {
+        &self.thumbnail
+    }
+
+    pub fn get_state(&self) -> &[u8] {
+        &self.state
+    }
+
+    /// Encodes the save. The thumbnail is stored as RGB, and the state is followed by a
+    /// checksum so a damaged file is refused before anything is read from it.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut writer = StateWriter::new();
+        writer.bytes.extend_from_slice(SAVE_MAGIC);
+        writer.bytes.extend_from_slice(&SAVE_VERSION.to_le_bytes());
+        writer.write_u32(self.map_checksum);
+        writer.write_u32(self.map_name.len() as u32);
+        writer.bytes.extend_from_slice(self.map_name.as_bytes());
+        writer.write_u32(self.thumbnail.get_width());
+        writer.write_u32(self.thumbnail.get_height());
+        for pixel in self.thumbnail.get_pixels().chunks(4) {
+            writer.bytes.extend_from_slice(&pixel[..3]);
+        }
+        writer.write_u32(self.state.len() as u32);
+        writer.bytes.extend_from_slice(&self.state);
+        writer.write_u32(checksum(&self.state));
+        writer.into_bytes()
+    }
+
+    /// Decodes a save written by [SaveGame::to_bytes].
+    pub fn from_bytes(bytes: &[u8]) -> Result<SaveGame, SaveError> {
+        if !bytes.starts_with(SAVE_MAGIC) {
+            return Err(SaveError::Corrupt(String::from("not a save file")));
+        }
+        let mut reader = StateReader::new(&bytes[SAVE_MAGIC.len()..]);
+        let version = reader.read_u16()?;
+        if version != SAVE_VERSION {
+            return Err(SaveError::Version(version));
+        }
+        let map_checksum = reader.read_u32()?;
+        let name_length = reader.read_u32()? as usize;
+        let map_name = String::from_utf8(reader.read_bytes(name_length)?.to_vec())
+            .map_err(|_| SaveError::Corrupt(String::from("level name is not UTF-8")))?;
+        let (width, height) = (reader.read_u32()?, reader.read_u32()?);
+        if width > THUMBNAIL_WIDTH * 4 || height > THUMBNAIL_HEIGHT * 4 {
+            return Err(SaveError::Corrupt(format!(
+                "thumbnail of {}x{} pixels",
+                width, height
+            )));
+        }
+        let rgb = reader.read_bytes((width * height * 3) as usize)?;
+        let pixels = rgb
+            .chunks(3)
+            .flat_map(|pixel| vec![pixel[0], pixel[1], pixel[2], 255])
+            .collect();
+        let thumbnail = FrameBuffer::from_pixels(width, height, pixels).unwrap();
+        let state_length = reader.read_u32()? as usize;
+        let state = reader.read_bytes(state_length)?.to_vec();
+        if reader.read_u32()? != checksum(&state) {
+            return Err(SaveError::Corrupt(String::from(
+                "the state does not match its checksum",
+            )));
+        }
+        if !reader.is_at_end() {
+            return Err(SaveError::Corrupt(String::from("data after the state")));
+        }
+        Ok(SaveGame {
+            map_name,
+            map_checksum,
+            thumbnail,
+            state,
+        })
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<SaveGame, SaveError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| {
+            SaveError::Io(format!("Cannot read save {} ({})", path.display(), err))
+        })?;
+        SaveGame::from_bytes(&bytes)
+    }
+
+    /// Writes the save, creating its directory. The file is written aside first and then
+    /// renamed, so a failed write leaves the previous save whole.
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<(), SaveError> {
+        let path = path.as_ref();
+        let io_error = |err: std::io::Error| {
+            SaveError::Io(format!("Cannot write save {} ({})", path.display(), err))
+        };
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir).map_err(io_error)?;
+        }
+        let partial = path.with_extension("partial");
+        fs::write(&partial, self.to_bytes()).map_err(io_error)?;
+        fs::rename(&partial, path).map_err(io_error)
+    }
+}
+
+/// FNV-1a hash of the state.
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811C_9DC5, |hash: u32, byte| {
+        (hash ^ *byte as u32).wrapping_mul(0x0100_0193)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::player_state::{PlayerState, MAX_HEALTH, MAX_SCORE};
+
+    fn save() -> SaveGame {
+        let mut view = FrameBuffer::new(320, 200);
+        for y in 190..200 {
+            for x in 310..320 {
+                view.set_pixel(x, y, [200, 100, 50, 255]);
+            }
+        }
+        let mut writer = StateWriter::new();
+        writer.write_vector2f(&Vector2f::new(2.5, -1.25));
+        writer.write_bool(true);
+        writer.write_u32(3);
+        SaveGame::new("map level.map", 42, &view, writer.into_bytes())
+    }
+
+    #[test]
+    fn saves_survive_their_file() {
+        let save = save();
+        assert_eq!(save.get_thumbnail().get_width(), THUMBNAIL_WIDTH);
+        assert_eq!(
+            save.get_thumbnail()
+                .get_pixel(THUMBNAIL_WIDTH - 1, THUMBNAIL_HEIGHT - 1),
+            Some([200, 100, 50, 255])
+        );
+        let read = SaveGame::from_bytes(&save.to_bytes()).unwrap();
+        assert_eq!(read, save);
+
+        let mut reader = StateReader::new(read.get_state());
+        assert_eq!(reader.read_vector2f().unwrap(), Vector2f::new(2.5, -1.25));
+        assert!(reader.read_bool().unwrap());
+        assert_eq!(
+            reader.read_count(2, "doors"),
+            Err(SaveError::Corrupt(String::from(
+                "3 doors saved, the level has 2"
+            )))
+        );
+        assert!(reader.is_at_end());
+        assert!(reader.read_u8().is_err());
+    }
+
+    #[test]
+    fn numbers_that_are_not_finite_are_refused() {
+        for value in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY].iter() {
+            let mut writer = StateWriter::new();
+            writer.write_f32(1.5);
+            writer.write_f32(*value);
+            let bytes = writer.into_bytes();
+            let mut reader = StateReader::new(&bytes);
+            assert_eq!(
+                reader.read_vector2f(),
+                Err(SaveError::Corrupt(format!(
+                    "{} is not a finite number",
+                    value
+                )))
+            );
+        }
+    }
+
+    #[test]
+    fn damaged_and_outdated_saves_are_refused() {
+        let bytes = save().to_bytes();
+        assert_eq!(
+            SaveGame::from_bytes(b"R3DM"),
+            Err(SaveError::Corrupt(String::from("not a save file")))
+        );
+        let mut old = bytes.clone();
+        old[4] = 0;
+        assert_eq!(SaveGame::from_bytes(&old), Err(SaveError::Version(0)));
+        let mut damaged = bytes.clone();
+        let last_state_byte = bytes.len() - 5;
+        damaged[last_state_byte] ^= 1;
+        assert_eq!(
+            SaveGame::from_bytes(&damaged).unwrap_err().to_string(),
+            "ERROR: Corrupt save (the state does not match its checksum)"
+        );
+        assert!(SaveGame::from_bytes(&bytes[..bytes.len() / 2]).is_err());
+
+        // Stats out of their range are refused when the game state is read
+        for (index, value) in [
+            (0, i32::MIN),
+            (0, MAX_HEALTH + 1),
+            (1, -1),
+            (3, MAX_SCORE + 1),
+        ]
+        .iter()
+        {
+            let mut writer = StateWriter::new();
+            PlayerState::new(1).save_state(&mut writer);
+            let mut state = writer.into_bytes();
+            state[index * 4..index * 4 + 4].copy_from_slice(&value.to_le_bytes());
+            assert!(PlayerState::new(1)
+                .load_state(&mut StateReader::new(&state))
+                .is_err());
+        }
+    }
+}
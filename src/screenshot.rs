@@ -0,0 +1,45 @@
+//! Module for rendering single frames without a window, for bug reports and regression tests
+
+use rsfml::{graphics::Image, system::Vector2f};
+
+use crate::{
+    framebuffer::FrameBuffer, game_mode::GameMode, map::Map, raycasting_engine::RaycastEngine,
+    texture_loader::TextureLoader,
+};
+
+/// Renders the view from the spawn of the map as the game shows it when the level starts,
+/// without the status bar and the weapon.
+///
+/// # Arguments
+/// `map` - The level, its spawn gives the camera position and direction
+///
+/// `texture_loader` - The textures to draw with, usually a headless loader
+///
+/// `view_size` - Size of the frame in pixels
+///
+/// `no_ground` - Draws a flat floor and ceiling instead of textured ones
+pub fn render_view(
+    map: Map,
+    texture_loader: &TextureLoader,
+    view_size: (u32, u32),
+    no_ground: bool,
+) -> FrameBuffer {
+    let sprites = GameMode::level_sprites(&map, texture_loader);
+    let mut r_engine = RaycastEngine::new(
+        map,
+        &Vector2f::new(view_size.0 as f32, view_size.1 as f32),
+        no_ground,
+    );
+    r_engine.render(&sprites, texture_loader).clone()
+}
+
+/// Writes a frame to an image file, the format follows the extension (e.g. `.png`).
+pub fn save_frame(frame: &FrameBuffer, path: &str) -> Result<(), String> {
+    let image =
+        Image::create_from_pixels(frame.get_width(), frame.get_height(), frame.get_pixels())
+            .ok_or_else(|| String::from("ERROR: Cannot create the image of the frame"))?;
+    if !image.save_to_file(path) {
+        return Err(format!("ERROR: Cannot write the frame to {}", path));
+    }
+    Ok(())
+}
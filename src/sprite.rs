@@ -0,0 +1,25 @@
+//! Module for the objects drawn as billboard sprites
+
+use rsfml::system::Vector2f;
+
+/// A flat image standing in the world and always facing the camera.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Sprite {
+    pub position: Vector2f,
+    pub texture_id: i32,
+}
+
+impl Sprite {
+    /// Creates a sprite.
+    ///
+    /// # Arguments
+    /// `position` - The point of the map the sprite stands on
+    ///
+    /// `texture_id` - The texture of the sprite, transparent pixels are not drawn
+    pub fn new(position: Vector2f, texture_id: i32) -> Sprite {
+        Sprite {
+            position,
+            texture_id,
+        }
+    }
+}
@@ -1,30 +1,418 @@
 //! Module for loading textures
 
-use rsfml::{graphics::Texture, system::SfBox};
+use std::{collections::HashMap, fmt, fs, ops::Range, path::Path};
+
+use rsfml::{
+    graphics::{Color, Image, Texture},
+    system::SfBox,
+};
+
+use crate::vswap::{Palette, Vswap, FIRST_SPRITE, FIRST_WALL_BLOCK, PAGE_SIZE};
+
+/// Width and height of the wall textures expected by the raycasting engine.
+pub const TEXTURE_SIZE: u32 = 128;
+
+/// Block whose textures are used for blocks without textures of their own.
+const DEFAULT_WALL_BLOCK: i32 = 1;
+
+/// Color made transparent in sprite images, the transparent entry of the Wolfenstein 3D palette.
+const SPRITE_MASK_COLOR: Color = Color::rgb(152, 0, 136);
 
 pub struct TextureLoader {
+    // GPU copies of the textures, empty for a headless loader
     textures: Vec<SfBox<Texture>>,
+    data: Vec<TextureData>,
+    headless: bool,
+    names: HashMap<String, i32>,
+    wall_textures: HashMap<i32, (i32, i32)>,
+}
+
+/// The pixels of a loaded texture, kept in memory for the software renderer.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TextureData {
+    width: u32,
+    height: u32,
+    pixels: Vec<u8>,
+}
+
+/// Every problem found while loading a texture manifest.
+#[derive(Debug, Default)]
+pub struct ManifestReport {
+    pub errors: Vec<String>,
+}
+
+/// Texture IDs of the pages loaded from a VSWAP file.
+pub struct VswapTextures {
+    pub walls: Range<i32>,
+    pub sprites: Range<i32>,
+}
+
+impl fmt::Display for ManifestReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for error in self.errors.iter() {
+            writeln!(f, "{}", error)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for ManifestReport {}
+
+impl TextureData {
+    pub fn get_width(&self) -> u32 {
+        self.width
+    }
+
+    pub fn get_height(&self) -> u32 {
+        self.height
+    }
+
+    /// Returns the RGBA color of a pixel, coordinates wrap around the texture.
+    pub fn get_pixel(&self, x: u32, y: u32) -> [u8; 4] {
+        let index = (((y % self.height) * self.width + x % self.width) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.pixels[index..index + 4]);
+        pixel
+    }
 }
 
 impl TextureLoader {
     pub fn new() -> TextureLoader {
         TextureLoader {
             textures: Vec::new(),
+            data: Vec::new(),
+            headless: false,
+            names: HashMap::new(),
+            wall_textures: HashMap::new(),
         }
     }
 
-    pub fn load_texture(&mut self, texture_path: &str) -> Result<(), String> {
-        let texture = Texture::from_file(texture_path);
+    /// Creates a loader keeping textures in memory only, which works without a display.
+    /// Only the software renderer can draw its textures.
+    pub fn headless() -> TextureLoader {
+        TextureLoader {
+            headless: true,
+            ..TextureLoader::new()
+        }
+    }
 
-        if let Some(tex) = texture {
-            self.textures.push(tex);
-            Ok(())
+    /// Loads every texture listed in a manifest file.
+    ///
+    /// Each line of the manifest is `<name> = <path>`, with paths relative to the manifest.
+    /// Blank lines and lines starting with `#` are ignored. Textures named `wall_<block>` and
+    /// `wall_<block>_dark` become the textures of that map block, `ground` is used for block 0.
+    /// Textures named `sprite_<n>` are sprites: their mask color becomes transparent and they
+    /// are scaled up to [TEXTURE_SIZE] if needed.
+    ///
+    /// Loading carries on after a failure so the report lists every missing texture at once.
+    pub fn from_manifest<P: AsRef<Path>>(
+        manifest_path: P,
+    ) -> Result<TextureLoader, ManifestReport> {
+        TextureLoader::new().load_manifest(manifest_path.as_ref())
+    }
+
+    /// Loads every texture listed in a manifest file into a [TextureLoader::headless] loader.
+    /// See [TextureLoader::from_manifest] for the format.
+    pub fn from_manifest_headless<P: AsRef<Path>>(
+        manifest_path: P,
+    ) -> Result<TextureLoader, ManifestReport> {
+        TextureLoader::headless().load_manifest(manifest_path.as_ref())
+    }
+
+    fn load_manifest(mut self, manifest_path: &Path) -> Result<TextureLoader, ManifestReport> {
+        let mut report = ManifestReport::default();
+        let contents = fs::read_to_string(manifest_path).map_err(|err| ManifestReport {
+            errors: vec![format!(
+                "ERROR: Cannot read texture manifest {} ({})",
+                manifest_path.display(),
+                err
+            )],
+        })?;
+        let base_path = manifest_path.parent().unwrap_or_else(|| Path::new(""));
+
+        for (line_idx, line) in contents.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (name, path) = match line.find('=') {
+                Some(separator) => (line[..separator].trim(), line[separator + 1..].trim()),
+                None => {
+                    report.errors.push(format!(
+                        "ERROR: {}:{}: expected `<name> = <path>`",
+                        manifest_path.display(),
+                        line_idx + 1
+                    ));
+                    continue;
+                }
+            };
+            if self.names.contains_key(name) {
+                report.errors.push(format!(
+                    "ERROR: {}:{}: texture {} is listed twice",
+                    manifest_path.display(),
+                    line_idx + 1,
+                    name
+                ));
+                continue;
+            }
+
+            let texture_path = base_path.join(path);
+            if !texture_path.is_file() {
+                report.errors.push(format!(
+                    "ERROR: Missing texture {} ({})",
+                    name,
+                    texture_path.display()
+                ));
+                continue;
+            }
+            let texture_path = texture_path.display().to_string();
+            let loaded = if name.starts_with("sprite_") {
+                self.load_sprite(&texture_path)
+            } else {
+                self.load_texture(&texture_path)
+                    .map(|()| self.data.len() as i32 - 1)
+            };
+            match loaded {
+                Ok(id) => {
+                    self.names.insert(name.to_string(), id);
+                }
+                Err(err) => report.errors.push(err),
+            }
+        }
+
+        self.register_named_walls();
+        if report.errors.is_empty() {
+            Ok(self)
         } else {
-            Err(format!("ERROR: Failed to load texture {}", texture_path))
+            Err(report)
+        }
+    }
+
+    fn register_named_walls(&mut self) {
+        if let Some(ground) = self.get_id("ground") {
+            self.set_wall_textures(0, ground, ground);
+        }
+        let walls: Vec<(i32, i32)> = self
+            .names
+            .iter()
+            .filter_map(|(name, &id)| {
+                let block = name.strip_prefix("wall_")?.parse::<i32>().ok()?;
+                Some((block, id))
+            })
+            .collect();
+        for (block, light) in walls {
+            let dark = self
+                .get_id(&format!("wall_{}_dark", block))
+                .unwrap_or(light);
+            self.set_wall_textures(block, light, dark);
+        }
+    }
+
+    pub fn load_texture(&mut self, texture_path: &str) -> Result<(), String> {
+        let image = Image::from_file(texture_path)
+            .ok_or_else(|| format!("ERROR: Failed to load texture {}", texture_path))?;
+        self.load_image(&image).map(|_| ())
+    }
+
+    /// Loads a sprite image, keying out [SPRITE_MASK_COLOR], and returns its texture ID.
+    /// Sprites must be square, [PAGE_SIZE] or [TEXTURE_SIZE] pixels wide.
+    pub fn load_sprite(&mut self, texture_path: &str) -> Result<i32, String> {
+        let mut image = Image::from_file(texture_path)
+            .ok_or_else(|| format!("ERROR: Failed to load texture {}", texture_path))?;
+        let size = image.size();
+        let scale = match (size.x, size.y) {
+            (width, height) if width == height && width == PAGE_SIZE as u32 => {
+                TEXTURE_SIZE as usize / PAGE_SIZE
+            }
+            (width, height) if width == height && width == TEXTURE_SIZE => 1,
+            (width, height) => {
+                return Err(format!(
+                    "ERROR: Sprite {} is {}x{}, expected {}x{} or {}x{}",
+                    texture_path, width, height, PAGE_SIZE, PAGE_SIZE, TEXTURE_SIZE, TEXTURE_SIZE
+                ))
+            }
+        };
+        image.create_mask_from_color(SPRITE_MASK_COLOR, 0);
+        self.load_scaled(image.pixel_data(), size.x as usize, scale)
+    }
+
+    /// Creates a texture from an image and returns its ID.
+    pub fn load_image(&mut self, image: &Image) -> Result<i32, String> {
+        if !self.headless {
+            let texture = Texture::from_image(image)
+                .ok_or_else(|| String::from("ERROR: Failed to create texture from image"))?;
+            self.textures.push(texture);
         }
+        let size = image.size();
+        self.data.push(TextureData {
+            width: size.x,
+            height: size.y,
+            pixels: image.pixel_data().to_vec(),
+        });
+        Ok(self.data.len() as i32 - 1)
     }
 
+    /// Creates a texture from an image and gives it a name, like a manifest entry.
+    pub fn load_named_image(&mut self, name: &str, image: &Image) -> Result<i32, String> {
+        let id = self.load_image(image)?;
+        self.names.insert(name.to_string(), id);
+        self.register_named_walls();
+        Ok(id)
+    }
+
+    /// Loads every wall and sprite page of a VSWAP file as a texture, scaled up to
+    /// [TEXTURE_SIZE].
+    ///
+    /// Wall pages come in light/dark pairs, pair `n` is registered as the wall texture of
+    /// block `FIRST_WALL_BLOCK + n` so maps can use them next to the built-in walls. Sprite
+    /// page `n` is named `sprite_<FIRST_SPRITE + n>`.
+    ///
+    /// # Arguments
+    /// `vswap` - The VSWAP file to read the pages from
+    ///
+    /// `palette` - The palette used to color the pages
+    pub fn load_vswap(
+        &mut self,
+        vswap: &Vswap,
+        palette: &Palette,
+    ) -> Result<VswapTextures, String> {
+        // Sparse pages get a blank texture, so the pages after them keep their numbers
+        let blank = vec![0; PAGE_SIZE * PAGE_SIZE * 4];
+        let first_wall = self.data.len() as i32;
+        for page in 0..vswap.wall_count() {
+            if vswap.is_sparse(page) {
+                self.load_page(&blank)?;
+                continue;
+            }
+            let pixels = vswap
+                .wall_rgba(page, palette)
+                .map_err(|err| err.to_string())?;
+            self.load_page(&pixels)?;
+        }
+        let first_sprite = self.data.len() as i32;
+        for sprite in 0..vswap.sprite_count() {
+            // Levels using a sprite left out are told it is missing
+            if vswap.is_sparse(vswap.wall_count() + sprite) {
+                self.load_page(&blank)?;
+                continue;
+            }
+            let pixels = vswap
+                .sprite_rgba(sprite, palette)
+                .map_err(|err| err.to_string())?;
+            let id = self.load_page(&pixels)?;
+            self.names
+                .insert(format!("sprite_{}", FIRST_SPRITE + sprite as i32), id);
+        }
+
+        for pair in 0..(vswap.wall_count() as i32 / 2) {
+            let light = first_wall + pair * 2;
+            self.set_wall_textures(FIRST_WALL_BLOCK + pair, light, light + 1);
+        }
+
+        Ok(VswapTextures {
+            walls: first_wall..first_sprite,
+            sprites: first_sprite..self.data.len() as i32,
+        })
+    }
+
+    fn load_page(&mut self, pixels: &[u8]) -> Result<i32, String> {
+        self.load_scaled(pixels, PAGE_SIZE, TEXTURE_SIZE as usize / PAGE_SIZE)
+    }
+
+    /// Creates a texture from square RGBA pixels enlarged `scale` times.
+    fn load_scaled(&mut self, pixels: &[u8], width: usize, scale: usize) -> Result<i32, String> {
+        let size = width * scale;
+        let mut scaled = Vec::with_capacity(size * size * 4);
+        for y in 0..size {
+            for x in 0..size {
+                let source = ((y / scale) * width + x / scale) * 4;
+                scaled.extend_from_slice(&pixels[source..source + 4]);
+            }
+        }
+        let image = Image::create_from_pixels(size as u32, size as u32, &scaled)
+            .ok_or_else(|| String::from("ERROR: Failed to create image from pixels"))?;
+        self.load_image(&image)
+    }
+
+    /// Returns the GPU texture of an ID.
+    ///
+    /// # Panics
+    /// Panics for a [TextureLoader::headless] loader, which has no GPU textures.
     pub fn get_texture(&self, index: i32) -> &Texture {
+        assert!(
+            !self.headless,
+            "ERROR: Headless textures cannot be drawn to a window"
+        );
         &self.textures[index as usize]
     }
+
+    /// Returns the pixels of a texture.
+    pub fn get_texture_data(&self, index: i32) -> &TextureData {
+        &self.data[index as usize]
+    }
+
+    /// Returns the ID of a texture listed in the manifest.
+    pub fn get_id(&self, name: &str) -> Option<i32> {
+        self.names.get(name).copied()
+    }
+
+    /// Returns a texture listed in the manifest.
+    pub fn get(&self, name: &str) -> Option<&Texture> {
+        self.get_id(name).map(|id| self.get_texture(id))
+    }
+
+    /// Returns the ID of a texture the game cannot run without.
+    ///
+    /// # Panics
+    /// Panics if the manifest does not list the texture, see [TextureLoader::check_names].
+    pub fn require_id(&self, name: &str) -> i32 {
+        self.get_id(name)
+            .unwrap_or_else(|| panic!("ERROR: Texture {} is missing from the manifest", name))
+    }
+
+    /// Reports every name of `names` that the manifest does not list.
+    pub fn check_names<S: AsRef<str>>(&self, names: &[S]) -> Result<(), ManifestReport> {
+        let errors: Vec<String> = names
+            .iter()
+            .filter(|name| self.get_id(name.as_ref()).is_none())
+            .map(|name| {
+                format!(
+                    "ERROR: Texture {} is missing from the manifest",
+                    name.as_ref()
+                )
+            })
+            .collect();
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ManifestReport { errors })
+        }
+    }
+
+    /// Sets the textures drawn for a map block, `dark` is used on the shaded side of walls.
+    pub fn set_wall_textures(&mut self, block: i32, light: i32, dark: i32) {
+        self.wall_textures.insert(block, (light, dark));
+    }
+
+    /// Returns the texture ID of a map block. Blocks without textures of their own are drawn
+    /// like block 1.
+    pub fn get_wall_texture_id(&self, block: i32, dark: bool) -> i32 {
+        let textures = self
+            .wall_textures
+            .get(&block)
+            .or_else(|| self.wall_textures.get(&DEFAULT_WALL_BLOCK));
+        match (textures, dark) {
+            (Some(&(light, _)), false) => light,
+            (Some(&(_, dark)), true) => dark,
+            (None, _) => 0,
+        }
+    }
+
+    pub fn get_wall_texture_data(&self, block: i32, dark: bool) -> &TextureData {
+        self.get_texture_data(self.get_wall_texture_id(block, dark))
+    }
+
+    pub fn get_wall_texture(&self, block: i32, dark: bool) -> &Texture {
+        self.get_texture(self.get_wall_texture_id(block, dark))
+    }
 }
@@ -0,0 +1,82 @@
+//! Module for running the simulation at a fixed rate, whatever the frame rate
+
+/// Most ticks simulated for one frame. After a longer pause (e.g. while the window is dragged)
+/// the game slows down instead of freezing to catch up.
+const MAX_TICKS_PER_FRAME: u32 = 17;
+
+/// Turns the real time of each frame into a number of fixed simulation ticks. The time left
+/// over is kept for the next frame and tells how far drawing is between two ticks.
+#[derive(Clone, Debug)]
+pub struct FixedTimestep {
+    rate: u32,
+    // Time not simulated yet, in ticks
+    accumulator: f64,
+}
+
+impl FixedTimestep {
+    /// Creates a timestep of `rate` ticks per second.
+    pub fn new(rate: u32) -> FixedTimestep {
+        FixedTimestep {
+            rate,
+            accumulator: 0.,
+        }
+    }
+
+    /// Returns the duration of a tick in seconds.
+    pub fn get_tick(&self) -> f32 {
+        1. / self.rate as f32
+    }
+
+    /// Adds the real time of a frame and returns how many ticks to simulate for it.
+    ///
+    /// # Arguments
+    /// `frame_time` - Seconds elapsed since the last frame
+    pub fn advance(&mut self, frame_time: f32) -> u32 {
+        self.accumulator += frame_time.max(0.) as f64 * self.rate as f64;
+        let ticks = self.accumulator.floor();
+        self.accumulator -= ticks;
+        (ticks as u32).min(MAX_TICKS_PER_FRAME)
+    }
+
+    /// Returns how far the current time is between the last tick and the next one, from 0
+    /// to 1, to interpolate what is drawn.
+    pub fn get_alpha(&self) -> f32 {
+        self.accumulator as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_second_of_frames_runs_one_tick_per_hertz() {
+        let mut timestep = FixedTimestep::new(70);
+        let ticks: u32 = (0..10).map(|_| timestep.advance(0.1)).sum();
+        assert_eq!(ticks, 70);
+        assert!(timestep.get_alpha() < 1e-3);
+    }
+
+    #[test]
+    fn fast_frames_accumulate_into_ticks() {
+        let mut timestep = FixedTimestep::new(70);
+        let mut ticks = 0;
+        for _ in 0..240 {
+            ticks += timestep.advance(1. / 240.);
+            assert!((0. ..1.).contains(&timestep.get_alpha()));
+        }
+        assert!((69..=70).contains(&ticks));
+        // Between two ticks, drawing is part way through the next one
+        let mut timestep = FixedTimestep::new(70);
+        assert_eq!(timestep.advance(1. / 140.), 0);
+        assert!((timestep.get_alpha() - 0.5).abs() < 1e-4);
+        assert_eq!(timestep.advance(1. / 140.), 1);
+    }
+
+    #[test]
+    fn long_frames_are_capped() {
+        let mut timestep = FixedTimestep::new(70);
+        assert_eq!(timestep.advance(5.), MAX_TICKS_PER_FRAME);
+        assert_eq!(timestep.advance(0.), 0);
+    }
+}
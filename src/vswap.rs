@@ -0,0 +1,436 @@
+//! Module for reading wall and sprite graphics from Wolfenstein 3D VSWAP files
+
+use std::{fmt, fs, path::Path};
+
+use rsfml::graphics::Color;
+
+/// Width and height of VSWAP wall and sprite pages.
+pub const PAGE_SIZE: usize = 64;
+
+/// Block ID given to the first VSWAP wall, lower block IDs are used by the built-in walls.
+pub const FIRST_WALL_BLOCK: i32 = 100;
+
+/// Sprite number given to the first VSWAP sprite, lower numbers are used by the built-in sprites.
+pub const FIRST_SPRITE: i32 = 100;
+
+/// Palette index used by the original sprites for transparent pixels.
+const TRANSPARENT_INDEX: usize = 255;
+
+/// Error raised while reading a VSWAP or palette file.
+#[derive(Debug, Clone, PartialEq)]
+pub enum VswapError {
+    Io(String),
+    Corrupt(String),
+    MissingPage(usize),
+}
+
+/// The 256 color VGA palette used to display VSWAP pages.
+#[derive(Clone, Debug)]
+pub struct Palette {
+    colors: Vec<Color>,
+}
+
+/// The page directory and page data of a VSWAP file.
+///
+/// Pages are stored in order: walls, then sprites, then digitized sounds.
+pub struct Vswap {
+    data: Vec<u8>,
+    offsets: Vec<u32>,
+    lengths: Vec<u16>,
+    sprite_start: usize,
+    sound_start: usize,
+}
+
+impl fmt::Display for VswapError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            VswapError::Io(message) => write!(f, "ERROR: {}", message),
+            VswapError::Corrupt(message) => write!(f, "ERROR: Corrupt VSWAP data ({})", message),
+            VswapError::MissingPage(index) => {
+                write!(f, "ERROR: Page {} does not exist in VSWAP", index)
+            }
+        }
+    }
+}
+
+impl std::error::Error for VswapError {}
+
+impl Palette {
+    /// Reads a palette from a raw 768 byte RGB file or from the color table of an 8-bit BMP
+    /// (any sprite exported from the original game, like `resources/SPR_CHAINATK3.bmp`).
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Palette, VswapError> {
+        let path = path.as_ref();
+        let bytes = fs::read(path).map_err(|err| {
+            VswapError::Io(format!("Cannot read palette {} ({})", path.display(), err))
+        })?;
+        if bytes.starts_with(b"BM") {
+            Palette::from_bmp(&bytes)
+        } else {
+            Palette::from_raw(&bytes)
+        }
+    }
+
+    /// Reads 256 RGB triplets. 6-bit VGA DAC values (0 to 63) are scaled up to 8 bits.
+    pub fn from_raw(bytes: &[u8]) -> Result<Palette, VswapError> {
+        let bytes = bytes.get(..256 * 3).ok_or_else(|| {
+            VswapError::Corrupt(String::from("palette needs 768 bytes of RGB data"))
+        })?;
+        let is_vga = bytes.iter().all(|&value| value < 64);
+        let scale = |value: u8| {
+            if is_vga {
+                (value << 2) | (value >> 4)
+            } else {
+                value
+            }
+        };
+        Ok(Palette {
+            colors: bytes
+                .chunks(3)
+                .map(|rgb| Color::rgb(scale(rgb[0]), scale(rgb[1]), scale(rgb[2])))
+                .collect(),
+        })
+    }
+
+    /// Reads the color table of an 8-bit BMP file.
+    pub fn from_bmp(bytes: &[u8]) -> Result<Palette, VswapError> {
+        let corrupt = || VswapError::Corrupt(String::from("not an 8-bit BMP file"));
+        let header_size = read_u32(bytes, 14).ok_or_else(corrupt)? as usize;
+        if read_u16(bytes, 28) != Some(8) {
+            return Err(corrupt());
+        }
+        let table_start = 14 + header_size;
+        let table = bytes
+            .get(table_start..table_start + 256 * 4)
+            .ok_or_else(corrupt)?;
+        Ok(Palette {
+            colors: table
+                .chunks(4)
+                .map(|bgra| Color::rgb(bgra[2], bgra[1], bgra[0]))
+                .collect(),
+        })
+    }
+
+    pub fn get_color(&self, index: u8) -> Color {
+        self.colors[index as usize]
+    }
+
+    /// Converts palette indices to RGBA pixels, `None` pixels become fully transparent.
+    pub fn to_rgba(&self, pixels: &[Option<u8>]) -> Vec<u8> {
+        let mut rgba = Vec::with_capacity(pixels.len() * 4);
+        for pixel in pixels.iter() {
+            match pixel {
+                Some(index) => {
+                    let color = self.get_color(*index);
+                    rgba.extend_from_slice(&[color.r, color.g, color.b, 255]);
+                }
+                None => rgba.extend_from_slice(&[0, 0, 0, 0]),
+            }
+        }
+        rgba
+    }
+}
+
+impl Vswap {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Vswap, VswapError> {
+        let path = path.as_ref();
+        let data = fs::read(path)
+            .map_err(|err| VswapError::Io(format!("Cannot read {} ({})", path.display(), err)))?;
+        Vswap::from_bytes(data)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Vswap, VswapError> {
+        let truncated = || VswapError::Corrupt(String::from("truncated page directory"));
+        let chunk_count = read_u16(&data, 0).ok_or_else(truncated)? as usize;
+        let sprite_start = read_u16(&data, 2).ok_or_else(truncated)? as usize;
+        let sound_start = read_u16(&data, 4).ok_or_else(truncated)? as usize;
+        if sprite_start > sound_start || sound_start > chunk_count {
+            return Err(VswapError::Corrupt(String::from(
+                "sprite and sound pages are out of order",
+            )));
+        }
+
+        let lengths_start = 6 + chunk_count * 4;
+        let mut offsets = Vec::with_capacity(chunk_count);
+        let mut lengths = Vec::with_capacity(chunk_count);
+        for chunk in 0..chunk_count {
+            offsets.push(read_u32(&data, 6 + chunk * 4).ok_or_else(truncated)?);
+            lengths.push(read_u16(&data, lengths_start + chunk * 2).ok_or_else(truncated)?);
+        }
+
+        Ok(Vswap {
+            data,
+            offsets,
+            lengths,
+            sprite_start,
+            sound_start,
+        })
+    }
+
+    pub fn wall_count(&self) -> usize {
+        self.sprite_start
+    }
+
+    pub fn sprite_count(&self) -> usize {
+        self.sound_start - self.sprite_start
+    }
+
+    /// Decodes a wall page into 64x64 palette indices, stored row by row.
+    ///
+    /// Walls are stored column by column in the file, so the page is transposed.
+    pub fn decode_wall(&self, index: usize) -> Result<Vec<u8>, VswapError> {
+        if index >= self.wall_count() {
+            return Err(VswapError::MissingPage(index));
+        }
+        let page = self.page(index)?;
+        if page.len() < PAGE_SIZE * PAGE_SIZE {
+            return Err(VswapError::Corrupt(format!(
+                "wall page {} is too short",
+                index
+            )));
+        }
+        let mut pixels = vec![0; PAGE_SIZE * PAGE_SIZE];
+        for x in 0..PAGE_SIZE {
+            for y in 0..PAGE_SIZE {
+                pixels[y * PAGE_SIZE + x] = page[x * PAGE_SIZE + y];
+            }
+        }
+        Ok(pixels)
+    }
+
+    /// Decodes a sprite into 64x64 palette indices, stored row by row. Pixels that are not
+    /// covered by any post of the sprite are transparent (`None`).
+    ///
+    /// # Arguments
+    /// `index` - The sprite number, counted from the first sprite page
+    pub fn decode_sprite(&self, index: usize) -> Result<Vec<Option<u8>>, VswapError> {
+        if index >= self.sprite_count() {
+            return Err(VswapError::MissingPage(self.sprite_start + index));
+        }
+        let page = self.page(self.sprite_start + index)?;
+        let corrupt = || VswapError::Corrupt(format!("sprite {} is malformed", index));
+
+        let left = read_u16(page, 0).ok_or_else(corrupt)? as usize;
+        let right = read_u16(page, 2).ok_or_else(corrupt)? as usize;
+        if left > right || right >= PAGE_SIZE {
+            return Err(corrupt());
+        }
+
+        let mut pixels = vec![None; PAGE_SIZE * PAGE_SIZE];
+        for x in left..=right {
+            let mut post = read_u16(page, 4 + (x - left) * 2).ok_or_else(corrupt)? as usize;
+            // Each post is (end * 2, pixel offset - start, start * 2), ending with a zero end
+            loop {
+                let end = read_u16(page, post).ok_or_else(corrupt)? as usize / 2;
+                if end == 0 {
+                    break;
+                }
+                let source = read_u16(page, post + 2).ok_or_else(corrupt)? as usize;
+                let start = read_u16(page, post + 4).ok_or_else(corrupt)? as usize / 2;
+                if start > end || end > PAGE_SIZE {
+                    return Err(corrupt());
+                }
+                for y in start..end {
+                    let index = *page.get(source + y).ok_or_else(corrupt)?;
+                    pixels[y * PAGE_SIZE + x] = Some(index);
+                }
+                post += 6;
+            }
+        }
+        Ok(pixels)
+    }
+
+    /// Decodes a wall page to RGBA pixels.
+    pub fn wall_rgba(&self, index: usize, palette: &Palette) -> Result<Vec<u8>, VswapError> {
+        let pixels = self.decode_wall(index)?;
+        let pixels: Vec<Option<u8>> = pixels.into_iter().map(Some).collect();
+        Ok(palette.to_rgba(&pixels))
+    }
+
+    /// Decodes a sprite to RGBA pixels. Transparent pixels have a zero alpha, as do pixels
+    /// using the palette's transparent index.
+    pub fn sprite_rgba(&self, index: usize, palette: &Palette) -> Result<Vec<u8>, VswapError> {
+        let pixels: Vec<Option<u8>> = self
+            .decode_sprite(index)?
+            .into_iter()
+            .map(|pixel| pixel.filter(|&index| index as usize != TRANSPARENT_INDEX))
+            .collect();
+        Ok(palette.to_rgba(&pixels))
+    }
+
+    /// Whether a page is stored without data. Shareware files leave out the pages of the
+    /// registered episodes this way.
+    pub fn is_sparse(&self, index: usize) -> bool {
+        match (self.offsets.get(index), self.lengths.get(index)) {
+            (Some(&start), Some(&length)) => start == 0 || length == 0,
+            _ => false,
+        }
+    }
+
+    fn page(&self, index: usize) -> Result<&[u8], VswapError> {
+        if index >= self.offsets.len() || self.is_sparse(index) {
+            return Err(VswapError::MissingPage(index));
+        }
+        let start = self.offsets[index] as usize;
+        let length = self.lengths[index] as usize;
+        self.data
+            .get(start..start + length)
+            .ok_or_else(|| VswapError::Corrupt(format!("page {} is out of bounds", index)))
+    }
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|bytes| u16::from_le_bytes([bytes[0], bytes[1]]))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|bytes| u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::texture_loader::TextureLoader;
+
+    /// Builds a VSWAP file from wall and sprite pages (no sound pages).
+    fn build_vswap(walls: &[Vec<u8>], sprites: &[Vec<u8>]) -> Vec<u8> {
+        let pages: Vec<&Vec<u8>> = walls.iter().chain(sprites.iter()).collect();
+        let header_size = 6 + pages.len() * 6;
+        let mut data = Vec::new();
+        data.extend((pages.len() as u16).to_le_bytes());
+        data.extend((walls.len() as u16).to_le_bytes());
+        data.extend((pages.len() as u16).to_le_bytes());
+        let mut offset = header_size;
+        for page in pages.iter() {
+            data.extend((offset as u32).to_le_bytes());
+            offset += page.len();
+        }
+        for page in pages.iter() {
+            data.extend((page.len() as u16).to_le_bytes());
+        }
+        for page in pages.iter() {
+            data.extend(page.iter());
+        }
+        data
+    }
+
+    /// A sprite with two columns: x = 10 covers rows 2..4 and x = 11 covers rows 5..6.
+    fn small_sprite() -> Vec<u8> {
+        let mut page = Vec::new();
+        page.extend(10u16.to_le_bytes());
+        page.extend(11u16.to_le_bytes());
+        // column offsets, each column has one post and its terminator
+        page.extend(8u16.to_le_bytes());
+        page.extend(16u16.to_le_bytes());
+        // column 10: rows 2..4 read bytes 24 and 25
+        for word in [4 * 2, 24 - 2, 2 * 2, 0].iter() {
+            page.extend((*word as u16).to_le_bytes());
+        }
+        // column 11: row 5 reads byte 26
+        for word in [6 * 2, 26 - 5, 5 * 2, 0].iter() {
+            page.extend((*word as u16).to_le_bytes());
+        }
+        page.extend([7, 8, 255]);
+        page
+    }
+
+    #[test]
+    fn decodes_walls_column_major() {
+        let mut wall = vec![0u8; PAGE_SIZE * PAGE_SIZE];
+        // pixel (x = 1, y = 2) is stored at column 1, row 2
+        wall[PAGE_SIZE + 2] = 42;
+        let vswap = Vswap::from_bytes(build_vswap(&[wall], &[])).unwrap();
+
+        assert_eq!(vswap.wall_count(), 1);
+        let pixels = vswap.decode_wall(0).unwrap();
+        assert_eq!(pixels[2 * PAGE_SIZE + 1], 42);
+        assert_eq!(pixels[PAGE_SIZE + 2], 0);
+    }
+
+    #[test]
+    fn decodes_sprite_posts() {
+        let vswap = Vswap::from_bytes(build_vswap(&[], &[small_sprite()])).unwrap();
+        assert_eq!(vswap.sprite_count(), 1);
+
+        let pixels = vswap.decode_sprite(0).unwrap();
+        assert_eq!(pixels[2 * PAGE_SIZE + 10], Some(7));
+        assert_eq!(pixels[3 * PAGE_SIZE + 10], Some(8));
+        assert_eq!(pixels[5 * PAGE_SIZE + 11], Some(255));
+        assert_eq!(pixels.iter().filter(|pixel| pixel.is_some()).count(), 3);
+    }
+
+    #[test]
+    fn applies_palette_and_transparency() {
+        let mut raw = vec![0u8; 768];
+        raw[7 * 3..7 * 3 + 3].copy_from_slice(&[63, 0, 32]);
+        let palette = Palette::from_raw(&raw).unwrap();
+        assert_eq!(palette.get_color(7), Color::rgb(255, 0, 130));
+
+        let vswap = Vswap::from_bytes(build_vswap(&[], &[small_sprite()])).unwrap();
+        let rgba = vswap.sprite_rgba(0, &palette).unwrap();
+        let at = |x: usize, y: usize| &rgba[(y * PAGE_SIZE + x) * 4..(y * PAGE_SIZE + x) * 4 + 4];
+        assert_eq!(at(10, 2), &[255, 0, 130, 255]);
+        assert_eq!(at(0, 0)[3], 0);
+        // index 255 is the transparent color of the original sprites
+        assert_eq!(at(11, 5)[3], 0);
+    }
+
+    #[test]
+    fn reads_bmp_color_tables() {
+        let mut bmp = b"BM".to_vec();
+        bmp.resize(14, 0);
+        bmp.extend(40u32.to_le_bytes());
+        bmp.resize(28, 0);
+        bmp.extend(8u16.to_le_bytes());
+        bmp.resize(54, 0);
+        for index in 0..256 {
+            bmp.extend([index as u8, 1, 2, 0]);
+        }
+        let palette = Palette::from_bmp(&bmp).unwrap();
+        assert_eq!(palette.get_color(9), Color::rgb(2, 1, 9));
+    }
+
+    #[test]
+    fn sparse_pages_are_skipped() {
+        let wall = vec![3u8; PAGE_SIZE * PAGE_SIZE];
+        let walls = [wall.clone(), Vec::new(), wall.clone(), wall];
+        let vswap = Vswap::from_bytes(build_vswap(&walls, &[Vec::new(), small_sprite()])).unwrap();
+        assert!(vswap.is_sparse(1));
+        assert!(vswap.is_sparse(4));
+        assert!(!vswap.is_sparse(5));
+        assert_eq!(vswap.decode_wall(1), Err(VswapError::MissingPage(1)));
+        assert_eq!(vswap.decode_sprite(0), Err(VswapError::MissingPage(4)));
+
+        let palette = Palette::from_raw(&[0; 768]).unwrap();
+        let mut texture_loader = TextureLoader::headless();
+        let textures = texture_loader.load_vswap(&vswap, &palette).unwrap();
+        // Sparse pages keep their place, so the wall pairs and sprites keep their numbers
+        assert_eq!(textures.walls.len(), 4);
+        assert_eq!(textures.sprites.len(), 2);
+        let pair = FIRST_WALL_BLOCK + 1;
+        assert_eq!(
+            texture_loader.get_wall_texture_id(pair, false),
+            textures.walls.start + 2
+        );
+        assert_eq!(
+            texture_loader.get_wall_texture_id(pair, true),
+            textures.walls.start + 3
+        );
+        assert_eq!(
+            texture_loader.get_id(&format!("sprite_{}", FIRST_SPRITE)),
+            None
+        );
+        assert_eq!(
+            texture_loader.get_id(&format!("sprite_{}", FIRST_SPRITE + 1)),
+            Some(textures.sprites.start + 1)
+        );
+    }
+
+    #[test]
+    fn rejects_missing_pages() {
+        let vswap = Vswap::from_bytes(build_vswap(&[], &[])).unwrap();
+        assert_eq!(vswap.decode_wall(0), Err(VswapError::MissingPage(0)));
+        assert!(Vswap::from_bytes(vec![1, 0]).is_err());
+    }
+}
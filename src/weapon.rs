@@ -5,10 +5,88 @@ use std::ops::Range;
 use rsfml::{
     graphics::{RectangleShape, RenderTarget, RenderWindow, Shape, Transformable},
     system::Vector2f,
-    window::{mouse::Button as MouseButton, Key},
 };
 
-use crate::{animation::*, event_handler::EventHandler, texture_loader::TextureLoader};
+use crate::{
+    animation::*,
+    event_handler::EventHandler,
+    input::{Action, WEAPON_ACTIONS},
+    player_state::PlayerState,
+    random::Random,
+    save::{SaveError, StateReader, StateWriter},
+    texture_loader::TextureLoader,
+};
+
+/// Texture name prefix of each weapon: pistol, machine gun, chaingun and knife.
+pub const WEAPON_TEXTURE_PREFIXES: [&str; 4] = ["gun", "gun2", "gun3", "cut"];
+
+/// Animation frames of a weapon are named `<prefix>_<frame>`.
+pub const WEAPON_FRAMES: Range<i32> = 1..7;
+
+/// Animation frame on which a weapon fires.
+const FIRE_FRAME: u32 = 2;
+
+/// How a weapon hurts what it hits.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WeaponStats {
+    /// Farthest distance the weapon hits at, in tiles
+    pub range: f32,
+    /// Largest angle between a shot and the aim, in radians
+    pub spread: f32,
+    /// Highest damage of a shot
+    pub damage: i32,
+    /// Distance from which the damage drops, down to a quarter at `range`
+    pub falloff_start: f32,
+}
+
+/// Stats of each weapon, in the order of [WEAPON_TEXTURE_PREFIXES].
+pub const WEAPON_STATS: [WeaponStats; 4] = [
+    // Pistol, slow but accurate far away
+    WeaponStats {
+        range: 32.,
+        spread: 0.02,
+        damage: 32,
+        falloff_start: 6.,
+    },
+    // Machine gun
+    WeaponStats {
+        range: 24.,
+        spread: 0.045,
+        damage: 28,
+        falloff_start: 4.,
+    },
+    // Chaingun, a spray of weaker shots best up close
+    WeaponStats {
+        range: 18.,
+        spread: 0.08,
+        damage: 24,
+        falloff_start: 3.,
+    },
+    // Knife, only hits at melee range
+    WeaponStats {
+        range: 1.2,
+        spread: 0.,
+        damage: 16,
+        falloff_start: 1.2,
+    },
+];
+
+impl WeaponStats {
+    /// Rolls the damage of a shot hitting at some distance.
+    pub fn damage_at(&self, distance: f32, random: &mut Random) -> i32 {
+        if distance > self.range {
+            return 0;
+        }
+        let falloff = if distance <= self.falloff_start {
+            1.
+        } else {
+            1. - 0.75 * (distance - self.falloff_start) / (self.range - self.falloff_start)
+        };
+        // Between half and all of the damage, like the random damage of the original
+        let roll = 0.5 + random.next_f32() * 0.5;
+        ((self.damage as f32 * falloff * roll).round() as i32).max(1)
+    }
+}
 
 pub struct Weapon<'s> {
     weapons: RectangleShape<'s>,
@@ -17,7 +95,9 @@ pub struct Weapon<'s> {
     shadows: RectangleShape<'s>,
     shadows_id: Vec<i32>,
     current_weapon: i32,
-    mouse_fire: bool,
+    trigger_held: bool,
+    shot_pending: bool,
+    fired: bool,
 }
 
 impl<'s> Weapon<'s> {
@@ -30,12 +110,17 @@ impl<'s> Weapon<'s> {
     pub fn new(window_size: &Vector2f, texture_loader: &'s TextureLoader) -> Weapon<'s> {
         Weapon {
             weapons: Weapon::initialize_weapons(window_size),
-            animations: Weapon::initialize_animation(),
+            animations: Weapon::initialize_animation(texture_loader),
             texture_loader,
             shadows: Weapon::initialize_shadows(window_size),
-            shadows_id: vec![18, 25, 32, 39],
+            shadows_id: WEAPON_TEXTURE_PREFIXES
+                .iter()
+                .map(|prefix| texture_loader.require_id(&format!("{}_shadow", prefix)))
+                .collect(),
             current_weapon: 0,
-            mouse_fire: false,
+            trigger_held: false,
+            shot_pending: false,
+            fired: false,
         }
     }
 
@@ -54,9 +139,11 @@ impl<'s> Weapon<'s> {
         tmp_shadow
     }
 
-    fn create_animation_by_range(texture_id_range: Range<i32>) -> Animation {
+    fn create_animation_by_prefix(prefix: &str, texture_loader: &TextureLoader) -> Animation {
         Animation::new(
-            texture_id_range.collect(),
+            WEAPON_FRAMES
+                .map(|frame| texture_loader.require_id(&format!("{}_{}", prefix, frame)))
+                .collect(),
             AnimationState::Stop,
             AnimationPlayMode::Once,
             0.07,
@@ -64,24 +151,29 @@ impl<'s> Weapon<'s> {
         )
     }
 
-    fn initialize_animation() -> Vec<Animation> {
-        let mut animations = Vec::new();
-        animations.push(Weapon::create_animation_by_range(12..18));
-        animations.push(Weapon::create_animation_by_range(19..25));
-        animations.push(Weapon::create_animation_by_range(26..32));
-        animations.push(Weapon::create_animation_by_range(33..39));
-        animations
+    fn initialize_animation(texture_loader: &TextureLoader) -> Vec<Animation> {
+        WEAPON_TEXTURE_PREFIXES
+            .iter()
+            .map(|prefix| Weapon::create_animation_by_prefix(prefix, texture_loader))
+            .collect()
     }
 
-    fn update_animations<'r>(&'r mut self) {
-        self.animations
+    fn update_animations<'r>(&'r mut self, delta: f32) {
+        let animation = self
+            .animations
             .get_mut(self.current_weapon as usize)
-            .unwrap()
-            .update();
+            .unwrap();
+        let previous_frame = animation.get_current_frame();
+        animation.update(delta);
+        let frame = animation.get_current_frame();
+        self.fired = self.shot_pending && frame == FIRE_FRAME && previous_frame != FIRE_FRAME;
+        if self.fired {
+            self.shot_pending = false;
+        }
     }
 
     fn update_reload<'r>(&'r mut self, event_handler: &EventHandler) {
-        if event_handler.is_key_pressed(Key::E) {
+        if event_handler.is_action_held(Action::Reload) {
             self.animations
                 .get_mut(self.current_weapon as usize)
                 .unwrap()
@@ -89,52 +181,125 @@ impl<'s> Weapon<'s> {
         }
     }
 
-    fn update_action<'r>(&'r mut self, event_handler: &EventHandler) {
-        if !self.mouse_fire {
-            if let Some(_) = event_handler.get_mouse_button_pressed_event(MouseButton::Left) {
-                self.animations
-                    .get_mut(self.current_weapon as usize)
-                    .unwrap()
-                    .set_state(AnimationState::Play);
-                self.mouse_fire = true
+    fn update_action<'r>(&'r mut self, event_handler: &EventHandler, player: &PlayerState) {
+        let can_fire = player.can_use_weapon(self.current_weapon as usize);
+        if !self.trigger_held {
+            if event_handler.has_action_pressed_event(Action::Fire) {
+                self.trigger_held = true;
+                if can_fire {
+                    self.animations
+                        .get_mut(self.current_weapon as usize)
+                        .unwrap()
+                        .set_state(AnimationState::Play);
+                    self.shot_pending = true;
+                }
             };
-        } else if let Some(_) = event_handler.get_mouse_button_released_event(MouseButton::Left) {
-            self.mouse_fire = false
-        } else {
+        } else if event_handler.has_action_released_event(Action::Fire) {
+            self.trigger_held = false
+        } else if can_fire {
             self.animations
                 .get_mut(self.current_weapon as usize)
                 .unwrap()
-                .set_state(AnimationState::Play)
+                .set_state(AnimationState::Play);
+            // Holding the trigger keeps firing
+            self.shot_pending = true;
         };
     }
 
-    fn update_selection<'r>(&'r mut self, event_handler: &EventHandler) {
-        if let Some(_) = event_handler.has_key_pressed_event(Key::Num1) {
-            self.current_weapon = 0
-        };
-        if let Some(_) = event_handler.has_key_pressed_event(Key::Num2) {
-            self.current_weapon = 1
-        };
-        if let Some(_) = event_handler.has_key_pressed_event(Key::Num3) {
-            self.current_weapon = 2
-        };
-        if let Some(_) = event_handler.has_key_pressed_event(Key::Num4) {
-            self.current_weapon = 3
+    fn update_selection<'r>(&'r mut self, event_handler: &EventHandler, player: &PlayerState) {
+        for (slot, action) in WEAPON_ACTIONS.iter().enumerate() {
+            if event_handler.has_action_pressed_event(*action) && player.can_use_weapon(slot) {
+                self.select(slot);
+            }
+        }
+        let step = if event_handler.has_action_pressed_event(Action::NextWeapon) {
+            1
+        } else if event_handler.has_action_pressed_event(Action::PreviousWeapon) {
+            WEAPON_ACTIONS.len() - 1
+        } else {
+            return;
         };
+        // The closest weapon in that direction the player can use
+        let current = self.current_weapon as usize;
+        let next = (1..WEAPON_ACTIONS.len())
+            .map(|offset| (current + offset * step) % WEAPON_ACTIONS.len())
+            .find(|&slot| player.can_use_weapon(slot));
+        if let Some(slot) = next {
+            self.select(slot);
+        }
+    }
+
+    /// Switches to another weapon.
+    ///
+    /// # Arguments
+    /// `slot` - The index of the weapon in [WEAPON_TEXTURE_PREFIXES]
+    pub fn select(&mut self, slot: usize) {
+        if self.current_weapon as usize != slot {
+            self.current_weapon = slot as i32;
+            self.shot_pending = false;
+        }
+    }
+
+    pub fn get_current_weapon(&self) -> usize {
+        self.current_weapon as usize
+    }
+
+    /// Writes the weapon in hand, its trigger and its animations to a save.
+    pub fn save_state(&self, writer: &mut StateWriter) {
+        writer.write_u8(self.current_weapon as u8);
+        writer.write_bool(self.trigger_held);
+        writer.write_bool(self.shot_pending);
+        writer.write_bool(self.fired);
+        for animation in self.animations.iter() {
+            animation.save_state(writer);
+        }
+    }
+
+    pub fn load_state(&mut self, reader: &mut StateReader) -> Result<(), SaveError> {
+        let current_weapon = reader.read_u8()? as usize;
+        if current_weapon >= WEAPON_TEXTURE_PREFIXES.len() {
+            return Err(SaveError::Corrupt(format!("weapon {}", current_weapon)));
+        }
+        self.current_weapon = current_weapon as i32;
+        self.trigger_held = reader.read_bool()?;
+        self.shot_pending = reader.read_bool()?;
+        self.fired = reader.read_bool()?;
+        for animation in self.animations.iter_mut() {
+            animation.load_state(reader)?;
+        }
+        Ok(())
     }
 
     /// Updates the weapon state
     ///
     /// # Arguments
     /// `event_handler` - The event handler to read input from to update state
-    pub fn update<'r>(&'r mut self, event_handler: &'r EventHandler) -> () {
-        self.update_selection(event_handler);
+    ///
+    /// `player` - The player state, only owned weapons with ammunition can be used
+    ///
+    /// `delta` - Seconds elapsed since the last update
+    pub fn update<'r>(
+        &'r mut self,
+        event_handler: &'r EventHandler,
+        player: &PlayerState,
+        delta: f32,
+    ) -> () {
+        self.update_selection(event_handler, player);
 
-        self.update_action(event_handler);
+        self.update_action(event_handler, player);
 
         self.update_reload(event_handler);
 
-        self.update_animations();
+        self.update_animations(delta);
+    }
+
+    /// Whether the weapon fired a shot during the last update.
+    pub fn has_fired(&self) -> bool {
+        self.fired
+    }
+
+    pub fn get_stats(&self) -> WeaponStats {
+        WEAPON_STATS[self.current_weapon as usize]
     }
 
     /// Updates the weapon animation on the next frame
@@ -157,3 +322,42 @@ impl<'s> Weapon<'s> {
         render_window.draw(&self.shadows);
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn damage_falls_off_to_a_quarter_at_range() {
+        let pistol = WEAPON_STATS[0];
+        // Rolls go from half to all of the damage
+        let mut random = Random::new(7);
+        for _ in 0..100 {
+            let damage = pistol.damage_at(2., &mut random);
+            assert!((16..=32).contains(&damage), "{}", damage);
+            let damage = pistol.damage_at(pistol.range, &mut random);
+            assert!((4..=8).contains(&damage), "{}", damage);
+        }
+        assert_eq!(pistol.damage_at(pistol.range + 0.1, &mut random), 0);
+
+        // The same roll does less damage past the falloff start
+        let (mut near, mut far) = (Random::new(7), Random::new(7));
+        let halfway = (pistol.falloff_start + pistol.range) / 2.;
+        assert!(
+            pistol.damage_at(halfway, &mut far) < pistol.damage_at(pistol.falloff_start, &mut near)
+        );
+    }
+
+    #[test]
+    fn guns_trade_range_for_spread() {
+        let guns = &WEAPON_STATS[..3];
+        for pair in guns.windows(2) {
+            assert!(pair[0].range > pair[1].range);
+            assert!(pair[0].spread < pair[1].spread);
+            assert!(pair[0].falloff_start > pair[1].falloff_start);
+        }
+        // The knife only reaches what stands next to the player
+        assert!(WEAPON_STATS[3].range < 1.5);
+        assert_eq!(WEAPON_STATS[3].damage_at(1.5, &mut Random::new(1)), 0);
+    }
+}
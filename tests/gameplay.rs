@@ -0,0 +1,390 @@
+//! Gameplay tests driving the game with scripted input, without a window or a keyboard.
+
+use rsfml::{
+    graphics::{Font, Image},
+    system::{Vector2f, Vector2u},
+    window::{joystick::Axis, mouse::Button as MouseButton, Event, Key},
+};
+use rustenstein3D::{
+    actor::{guard_texture_names, GUARD_HEALTH},
+    demo::Demo,
+    event_handler::EventHandler,
+    game::TICK_RATE,
+    game_mode::GameMode,
+    input::{Action, Binding, ACTIONS},
+    input_source::VirtualInput,
+    map::Map,
+    player_state::{KNIFE, MACHINE_GUN, PISTOL},
+    save::{SaveError, SaveGame, THUMBNAIL_WIDTH},
+    texture_loader::TextureLoader,
+    weapon::{WEAPON_FRAMES, WEAPON_TEXTURE_PREFIXES},
+};
+
+const LEVEL: &str = "\
+# = 1
+map:
+#########
+#.......#
+#.......#
+#...>...#
+#.......#
+#.......#
+#########
+";
+
+/// Loads a plain texture under every name the game needs.
+fn texture_loader() -> TextureLoader {
+    let mut names: Vec<String> = ["ground", "sky", "wall_1", "face_1", "face_2", "face_3"]
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    for weapon in WEAPON_TEXTURE_PREFIXES.iter() {
+        names.extend(WEAPON_FRAMES.map(|frame| format!("{}_{}", weapon, frame)));
+        names.push(format!("{}_shadow", weapon));
+    }
+    names.extend(guard_texture_names());
+    let image = Image::create_from_pixels(4, 4, &[128; 4 * 4 * 4]).unwrap();
+    let mut texture_loader = TextureLoader::headless();
+    for name in names.iter() {
+        texture_loader.load_named_image(name, &image).unwrap();
+    }
+    texture_loader
+}
+
+fn font() -> rsfml::system::SfBox<Font> {
+    Font::from_file(concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/resources/sansation.ttf"
+    ))
+    .unwrap()
+}
+
+/// Starts a game of a level, drawn with the plain textures of [texture_loader].
+fn new_game(level: &str) -> GameMode<'static> {
+    // Games borrow their textures and font, kept alive until the test ends
+    let texture_loader = Box::leak(Box::new(texture_loader()));
+    let font = Box::leak(Box::new(font()));
+    let map = Map::parse_ascii(level).unwrap();
+    GameMode::new(Vector2u::new(768, 480), texture_loader, font, map, false)
+}
+
+/// Simulates ticks of the game, reading the scripted input at each one.
+fn run(
+    game: &mut GameMode,
+    event_handler: &mut EventHandler,
+    input: &mut VirtualInput,
+    ticks: u32,
+) {
+    for _ in 0..ticks {
+        event_handler.update_events(input);
+        game.update(event_handler, 1. / TICK_RATE as f32);
+    }
+}
+
+fn assert_close(actual: Vector2f, expected: (f32, f32)) {
+    assert!(
+        (actual.x - expected.0).abs() < 1e-3 && (actual.y - expected.1).abs() < 1e-3,
+        "{:?} is not close to {:?}",
+        actual,
+        expected
+    );
+}
+
+#[test]
+fn holding_keys_walks_and_strafes() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    assert_close(game.get_player_pos(), (4.5, 3.5));
+
+    // Half a second forward, facing east
+    input.press(Binding::Key(Key::W));
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE / 2);
+    input.release(Binding::Key(Key::W));
+    run(&mut game, &mut event_handler, &mut input, 10);
+    assert_close(game.get_player_pos(), (6., 3.5));
+
+    // The right of the view is north
+    input.press(Binding::Key(Key::D));
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE / 2);
+    input.release(Binding::Key(Key::D));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_close(game.get_player_pos(), (6., 2.));
+}
+
+#[test]
+fn holding_an_arrow_turns() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+
+    input.press(Binding::Key(Key::Right));
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE);
+    let angle = (-157.5f32).to_radians();
+    assert_close(game.get_player_dir(), (angle.cos(), angle.sin()));
+    assert_close(game.get_player_pos(), (4.5, 3.5));
+}
+
+#[test]
+fn number_keys_and_the_wheel_switch_weapons() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    assert_eq!(game.get_current_weapon(), PISTOL);
+
+    input.tap(Binding::Key(Key::Num4));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_eq!(game.get_current_weapon(), KNIFE);
+    // Only the pistol and the knife are owned at the start
+    input.press(Binding::WheelUp);
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_eq!(game.get_current_weapon(), PISTOL);
+    input.press(Binding::WheelDown);
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_eq!(game.get_current_weapon(), KNIFE);
+    input.tap(Binding::Key(Key::Num2));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_ne!(game.get_current_weapon(), MACHINE_GUN);
+}
+
+#[test]
+fn the_last_bullet_keeps_the_range_of_its_gun() {
+    // A guard 5 tiles to the right, facing away so it stays put
+    let level = LEVEL.replace("# = 1\n", "# = 1\ng = 2 guard\n").replace(
+        "#...>...#\n#.......#\n#.......#\n#########",
+        "#.......#\n#.^....g#\n#.......#\n#########",
+    );
+    let mut game = new_game(&level);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    let mut shoot = |game: &mut GameMode| {
+        input.press(Binding::Mouse(MouseButton::Left));
+        run(game, &mut event_handler, &mut input, 1);
+        input.release(Binding::Mouse(MouseButton::Left));
+        run(game, &mut event_handler, &mut input, 39);
+    };
+    // Spend all bullets but one into the wall ahead
+    for _ in 0..7 {
+        shoot(&mut game);
+    }
+    assert_eq!(game.get_player().get_ammo(), 1);
+    assert_eq!(game.get_actors()[0].get_health(), GUARD_HEALTH);
+
+    // A quarter turn faces the guard
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    input.press(Binding::Key(Key::Left));
+    run(&mut game, &mut event_handler, &mut input, 40);
+    assert_close(game.get_player_dir(), (1., 0.));
+    input.release(Binding::Key(Key::Left));
+    input.press(Binding::Mouse(MouseButton::Left));
+    run(&mut game, &mut event_handler, &mut input, 40);
+    assert_eq!(game.get_player().get_ammo(), 0);
+    assert_eq!(game.get_current_weapon(), KNIFE);
+    assert!(game.get_actors()[0].get_health() < GUARD_HEALTH);
+}
+
+#[test]
+fn the_map_key_toggles_the_mini_map_once_per_press() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    assert!(game.is_mini_map_active());
+
+    input.press(Binding::Key(Key::M));
+    run(&mut game, &mut event_handler, &mut input, 20);
+    assert!(!game.is_mini_map_active());
+    input.release(Binding::Key(Key::M));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert!(!game.is_mini_map_active());
+    input.tap(Binding::Key(Key::M));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert!(game.is_mini_map_active());
+}
+
+#[test]
+fn the_quit_binding_keeps_bindings_while_rebinding() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    event_handler
+        .get_input_map_mut()
+        .rebind(Action::Quit, Binding::Key(Key::Q));
+    let kept = event_handler
+        .get_input_map()
+        .get_bindings(ACTIONS[0])
+        .to_vec();
+    let mut tap = |game: &mut GameMode, key: Key| {
+        input.press(Binding::Key(key));
+        run(game, &mut event_handler, &mut input, 1);
+        input.release(Binding::Key(key));
+        run(game, &mut event_handler, &mut input, 1);
+    };
+
+    tap(&mut game, Key::F10);
+    assert!(game.is_rebinding());
+    tap(&mut game, Key::Q);
+    tap(&mut game, Key::Escape);
+    assert!(game.is_rebinding());
+    let input_map = event_handler.get_input_map();
+    assert_eq!(input_map.get_bindings(ACTIONS[0]), kept.as_slice());
+    assert!(input_map
+        .get_bindings(ACTIONS[1])
+        .contains(&Binding::Key(Key::Escape)));
+}
+
+#[test]
+fn gamepad_sticks_walk_and_turn_as_far_as_they_are_pushed() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    input.connect_gamepad();
+
+    // Halfway past the deadzone walks at half speed
+    input.move_axis(Axis::Y, -62.5);
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE / 2);
+    input.move_axis(Axis::Y, 0.);
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_close(game.get_player_pos(), (5.25, 3.5));
+
+    // The right stick pushed all the way turns faster than the arrows
+    input.move_axis(Axis::U, 100.);
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE / 2);
+    let angle = (-157.5f32 * 1.5 / 2.).to_radians();
+    assert_close(game.get_player_dir(), (angle.cos(), angle.sin()));
+}
+
+#[test]
+fn gamepad_buttons_cycle_weapons_until_disconnected() {
+    let mut game = new_game(LEVEL);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    input.connect_gamepad();
+
+    input.tap(Binding::GamepadButton(4));
+    run(&mut game, &mut event_handler, &mut input, 1);
+    assert_eq!(game.get_current_weapon(), KNIFE);
+    // Held down, the button switches once
+    input.press(Binding::GamepadButton(5));
+    run(&mut game, &mut event_handler, &mut input, 20);
+    assert_eq!(game.get_current_weapon(), PISTOL);
+    input.release(Binding::GamepadButton(5));
+    run(&mut game, &mut event_handler, &mut input, 1);
+
+    input.disconnect_gamepad();
+    run(&mut game, &mut event_handler, &mut input, 1);
+    input.move_axis(Axis::Y, -100.);
+    run(&mut game, &mut event_handler, &mut input, TICK_RATE / 2);
+    assert_close(game.get_player_pos(), (4.5, 3.5));
+}
+
+#[test]
+fn demos_play_the_run_again_exactly() {
+    // A guard to shoot, and to shoot back with random damage
+    let level = LEVEL.replace("# = 1\n", "# = 1\ng = 4 guard\n").replace(
+        "#...>...#\n#.......#\n#.......#\n#########",
+        "#...>...#\n#......g#\n#.......#\n#########",
+    );
+    let mut demo = Demo::new("map level.map", &Map::parse_ascii(&level).unwrap());
+    let center_x = 768 / 2;
+
+    let mut game = new_game(&level);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    let mut record = |game: &mut GameMode, input: &mut VirtualInput, ticks: u32| {
+        for _ in 0..ticks {
+            event_handler.update_events(input);
+            demo.push_tick(event_handler.get_tick_input(center_x));
+            game.update(&mut event_handler, 1. / TICK_RATE as f32);
+        }
+    };
+    input.press(Binding::Key(Key::W));
+    record(&mut game, &mut input, 20);
+    input.push_event(Event::MouseMoved {
+        x: center_x + 40,
+        y: 240,
+    });
+    input.connect_gamepad();
+    input.move_axis(Axis::X, 47.);
+    record(&mut game, &mut input, 15);
+    input.release(Binding::Key(Key::W));
+    for _ in 0..6 {
+        input.tap(Binding::Mouse(MouseButton::Left));
+        record(&mut game, &mut input, 40);
+    }
+    input.press(Binding::Key(Key::Escape));
+    record(&mut game, &mut input, 3);
+
+    let demo = Demo::from_bytes(&demo.to_bytes()).unwrap();
+    let mut replayed = new_game(&level);
+    let mut event_handler = EventHandler::new();
+    for tick in demo.get_ticks() {
+        event_handler.replay_tick(tick, center_x);
+        replayed.update(&mut event_handler, 1. / TICK_RATE as f32);
+    }
+    assert_eq!(replayed.get_player_pos(), game.get_player_pos());
+    assert_eq!(replayed.get_player_dir(), game.get_player_dir());
+    assert_eq!(replayed.get_player(), game.get_player());
+    // The run did something worth repeating
+    assert_ne!(game.get_player().get_ammo(), 8);
+    assert_ne!(game.get_player_pos(), Vector2f::new(4.5, 3.5));
+}
+
+#[test]
+fn loaded_saves_carry_on_like_the_saved_game() {
+    // A door to open and a guard shooting at the player
+    let level = "\
+# = 1
+D = 1 door
+g = 4 guard
+map:
+#########
+#.......#
+####D####
+#...^..g#
+#.......#
+#########
+";
+    let mut game = new_game(level);
+    let (mut event_handler, mut input) = (EventHandler::new(), VirtualInput::new());
+    input.tap(Binding::Key(Key::Space));
+    run(&mut game, &mut event_handler, &mut input, 30);
+    input.tap(Binding::Mouse(MouseButton::Left));
+    input.tap(Binding::Key(Key::M));
+    run(&mut game, &mut event_handler, &mut input, 30);
+
+    let save = SaveGame::from_bytes(&game.save_game("map level.map").to_bytes()).unwrap();
+    assert_eq!(save.get_map_name(), "map level.map");
+    assert_eq!(save.get_thumbnail().get_width(), THUMBNAIL_WIDTH);
+    let mut loaded = new_game(level);
+    loaded.load_game(&save).unwrap();
+    assert_eq!(loaded.get_player_pos(), game.get_player_pos());
+    assert!(!loaded.is_mini_map_active());
+
+    // Both games play the same from there on, through the open door
+    let mut loaded_event_handler = EventHandler::new();
+    let mut loaded_input = VirtualInput::new();
+    for input in [&mut input, &mut loaded_input].iter_mut() {
+        input.press(Binding::Key(Key::W));
+        input.tap(Binding::Mouse(MouseButton::Left));
+    }
+    run(&mut game, &mut event_handler, &mut input, 60);
+    run(
+        &mut loaded,
+        &mut loaded_event_handler,
+        &mut loaded_input,
+        60,
+    );
+    assert!(game.get_player_pos().y < 2.);
+    assert_eq!(loaded.get_player_pos(), game.get_player_pos());
+    assert_eq!(loaded.get_player(), game.get_player());
+    assert_eq!(
+        loaded.save_game("map level.map").get_state(),
+        game.save_game("map level.map").get_state()
+    );
+
+    // Saves only load on their level, and whole
+    let mut other = new_game(LEVEL);
+    assert_eq!(
+        other.load_game(&save),
+        Err(SaveError::OtherLevel(String::from("map level.map")))
+    );
+    let state = save.get_state();
+    let truncated = SaveGame::new(
+        "map level.map",
+        save.get_map_checksum(),
+        save.get_thumbnail(),
+        state[..state.len() - 1].to_vec(),
+    );
+    assert!(new_game(level).load_game(&truncated).is_err());
+}
@@ -0,0 +1,260 @@
+//! Golden-image tests for the software renderer.
+//!
+//! Each case renders a camera pose of a small level with generated textures, so no display or
+//! texture files are needed, and compares the frame to `tests/golden/<name>.ppm`. Set
+//! `UPDATE_GOLDEN=1` to rewrite the golden images after an intended change of the renderer.
+//! A failing case writes `target/golden-diff/<name>.ppm`, with the differing pixels in red over
+//! the expected image.
+
+use std::{fs, path::PathBuf};
+
+use rsfml::{graphics::Image, system::Vector2f};
+use rustenstein3D::{
+    framebuffer::FrameBuffer,
+    map::{Map, Spawn},
+    screenshot::render_view,
+    texture_loader::TextureLoader,
+};
+
+const VIEW_SIZE: (u32, u32) = (128, 80);
+const TEXTURE_SIZE: u32 = 64;
+/// Largest difference on a color channel for two pixels to be the same
+const CHANNEL_TOLERANCE: u8 = 16;
+/// Fraction of the pixels allowed to differ, for rounding at the edges of walls
+const PIXEL_TOLERANCE: f32 = 0.005;
+
+const LEVEL: &str = "\
+# = 1
+2 = 2
+D = 2 door
+o = 1 sprite
+map:
+##########
+#........#
+#..2.....#
+#........#
+#...o....#
+#........#
+#.<......#
+####D#####
+";
+
+/// Creates an image whose color changes along both axes, so flipped or stretched texture
+/// columns show up in the frame.
+fn gradient_image(base: [u8; 3], brightness: f32) -> Image {
+    let mut pixels = Vec::new();
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let border = x == 0 || y == 0;
+            let shade = |channel: u8, ramp: u32| {
+                let value = if border {
+                    0.
+                } else {
+                    (channel as f32 + ramp as f32 * 2.).min(255.) * brightness
+                };
+                value as u8
+            };
+            pixels.extend(&[shade(base[0], x), shade(base[1], y), shade(base[2], 0), 255]);
+        }
+    }
+    Image::create_from_pixels(TEXTURE_SIZE, TEXTURE_SIZE, &pixels).unwrap()
+}
+
+/// Creates a checkerboard image of two colors.
+fn checker_image(first: [u8; 3], second: [u8; 3]) -> Image {
+    let mut pixels = Vec::new();
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let color = if (x / 8 + y / 8) % 2 == 0 {
+                first
+            } else {
+                second
+            };
+            pixels.extend(&[color[0], color[1], color[2], 255]);
+        }
+    }
+    Image::create_from_pixels(TEXTURE_SIZE, TEXTURE_SIZE, &pixels).unwrap()
+}
+
+/// Creates a disc with a darker left half on a transparent background.
+fn sprite_image() -> Image {
+    let mut pixels = Vec::new();
+    let center = TEXTURE_SIZE as f32 / 2.;
+    for y in 0..TEXTURE_SIZE {
+        for x in 0..TEXTURE_SIZE {
+            let (dx, dy) = (x as f32 + 0.5 - center, y as f32 + 0.5 - center);
+            if dx * dx + dy * dy > center * center {
+                pixels.extend(&[0, 0, 0, 0]);
+            } else if x < TEXTURE_SIZE / 2 {
+                pixels.extend(&[160, 120, 0, 255]);
+            } else {
+                pixels.extend(&[255, 220, 40, 255]);
+            }
+        }
+    }
+    Image::create_from_pixels(TEXTURE_SIZE, TEXTURE_SIZE, &pixels).unwrap()
+}
+
+fn texture_loader() -> TextureLoader {
+    let mut texture_loader = TextureLoader::headless();
+    let textures = [
+        ("ground", checker_image([90, 90, 90], [130, 130, 130])),
+        ("sky", checker_image([40, 40, 120], [70, 70, 160])),
+        ("wall_1", gradient_image([40, 0, 0], 1.)),
+        ("wall_1_dark", gradient_image([40, 0, 0], 0.5)),
+        ("wall_2", gradient_image([0, 0, 200], 1.)),
+        ("wall_2_dark", gradient_image([0, 0, 200], 0.5)),
+        ("sprite_1", sprite_image()),
+    ];
+    for (name, image) in textures.iter() {
+        texture_loader.load_named_image(name, image).unwrap();
+    }
+    texture_loader
+}
+
+fn golden_path(name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/golden")
+        .join(format!("{}.ppm", name))
+}
+
+/// Writes the RGB channels of a frame as a binary PPM image.
+fn write_ppm(frame: &FrameBuffer, path: &PathBuf) {
+    let mut bytes = format!("P6\n{} {}\n255\n", frame.get_width(), frame.get_height()).into_bytes();
+    for pixel in frame.get_pixels().chunks_exact(4) {
+        bytes.extend(&pixel[..3]);
+    }
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, bytes).unwrap();
+}
+
+/// Reads a binary PPM image written by [write_ppm].
+fn read_ppm(path: &PathBuf) -> Option<FrameBuffer> {
+    let bytes = fs::read(path).ok()?;
+    // The header is 4 fields separated by single whitespace characters
+    let mut fields = Vec::new();
+    let mut start = 0;
+    for (index, byte) in bytes.iter().enumerate() {
+        if byte.is_ascii_whitespace() {
+            fields.push(std::str::from_utf8(&bytes[start..index]).ok()?);
+            start = index + 1;
+            if fields.len() == 4 {
+                break;
+            }
+        }
+    }
+    if fields.len() != 4 || fields[0] != "P6" || fields[3] != "255" {
+        return None;
+    }
+    let (width, height) = (fields[1].parse().ok()?, fields[2].parse().ok()?);
+    let mut pixels = Vec::new();
+    for pixel in bytes[start..].chunks_exact(3) {
+        pixels.extend(&[pixel[0], pixel[1], pixel[2], 255]);
+    }
+    FrameBuffer::from_pixels(width, height, pixels)
+}
+
+/// Renders a pose of the test level and checks it against its golden image.
+fn check_golden(name: &str, position: (f32, f32), direction: (f32, f32), no_ground: bool) {
+    let texture_loader = texture_loader();
+    let mut map = Map::parse_ascii(LEVEL).unwrap();
+    map.set_spawn(Spawn::new(
+        Vector2f::new(position.0, position.1),
+        Vector2f::new(direction.0, direction.1),
+    ));
+    let frame = render_view(map, &texture_loader, VIEW_SIZE, no_ground);
+
+    let path = golden_path(name);
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        write_ppm(&frame, &path);
+        return;
+    }
+    let expected = read_ppm(&path).unwrap_or_else(|| {
+        panic!(
+            "Cannot read golden image {}, run with UPDATE_GOLDEN=1 to create it",
+            path.display()
+        )
+    });
+    assert_eq!(
+        (expected.get_width(), expected.get_height()),
+        (frame.get_width(), frame.get_height())
+    );
+
+    let mut diff = expected.clone();
+    let mut different = 0;
+    for y in 0..frame.get_height() {
+        for x in 0..frame.get_width() {
+            let (actual, wanted) = (
+                frame.get_pixel(x, y).unwrap(),
+                expected.get_pixel(x, y).unwrap(),
+            );
+            let same = (0..3)
+                .all(|c| (actual[c] as i32 - wanted[c] as i32).abs() <= CHANNEL_TOLERANCE as i32);
+            if same {
+                // Dim matching pixels so the differences stand out
+                diff.set_pixel(x, y, [wanted[0] / 3, wanted[1] / 3, wanted[2] / 3, 255]);
+            } else {
+                diff.set_pixel(x, y, [255, 0, 0, 255]);
+                different += 1;
+            }
+        }
+    }
+    let allowed = (frame.get_width() * frame.get_height()) as f32 * PIXEL_TOLERANCE;
+    if different as f32 > allowed {
+        let diff_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+            .join("target/golden-diff")
+            .join(format!("{}.ppm", name));
+        write_ppm(&diff, &diff_path);
+        write_ppm(&frame, &diff_path.with_extension("actual.ppm"));
+        panic!(
+            "{} pixels differ from {} (at most {} allowed), see {}",
+            different,
+            path.display(),
+            allowed as u32,
+            diff_path.display()
+        );
+    }
+}
+
+#[test]
+fn wall_seen_from_the_front_on_the_x_side() {
+    check_golden("facing_east", (5.5, 3.5), (1., 0.), false);
+}
+
+#[test]
+fn wall_seen_from_the_front_with_flipped_columns() {
+    check_golden("facing_west", (5.5, 3.5), (-1., 0.), false);
+}
+
+#[test]
+fn corner_shows_both_wall_sides() {
+    check_golden(
+        "corner",
+        (3.5, 4.5),
+        (
+            -std::f32::consts::FRAC_1_SQRT_2,
+            std::f32::consts::FRAC_1_SQRT_2,
+        ),
+        false,
+    );
+}
+
+#[test]
+fn closed_door_in_a_wall() {
+    check_golden("door", (4.5, 5.5), (0., 1.), false);
+}
+
+#[test]
+fn sprite_in_front_of_a_pillar() {
+    check_golden("sprite", (4.5, 6.5), (0., -1.), false);
+}
+
+#[test]
+fn wall_closer_than_one_tile() {
+    check_golden("close_wall", (1.3, 3.5), (-1., 0.), false);
+}
+
+#[test]
+fn flat_floor_and_ceiling_without_ground() {
+    check_golden("no_ground", (5.5, 3.5), (1., 0.), true);
+}